@@ -1,14 +1,26 @@
 const CopyWebpackPlugin = require("copy-webpack-plugin");
 const path = require('path');
 
-module.exports = {
-  entry: "./static.js",
-  output: {
-    path: path.resolve(__dirname, "dist"),
-    filename: "static.js",
+module.exports = [
+  {
+    entry: "./static.js",
+    output: {
+      path: path.resolve(__dirname, "dist"),
+      filename: "static.js",
+    },
+    mode: "development",
+    plugins: [
+      new CopyWebpackPlugin(['index.html'])
+    ],
   },
-  mode: "development",
-  plugins: [
-    new CopyWebpackPlugin(['index.html'])
-  ],
-};
+  // the encoder worker, loaded by the wasm module as `./worker.js`
+  {
+    entry: "./worker.js",
+    target: "webworker",
+    output: {
+      path: path.resolve(__dirname, "dist"),
+      filename: "worker.js",
+    },
+    mode: "development",
+  },
+];
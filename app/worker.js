@@ -0,0 +1,10 @@
+// Encode jobs run here, off the main thread. The worker loads its own
+// instance of the wasm module and hands every message to `encode_job`,
+// queueing the ones that arrive while the module is still loading.
+const wasm = import("drawing-wasm");
+
+self.onmessage = e => {
+  wasm
+    .then(module => module.encode_job(e.data))
+    .catch(err => self.postMessage({ type: "error", message: String(err) }));
+};
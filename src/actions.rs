@@ -0,0 +1,229 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, Document, HtmlCanvasElement, HtmlImageElement};
+
+use crate::codec;
+use crate::editor::Mount;
+use crate::state::{State, Tool};
+use crate::toolbar;
+use crate::transform;
+use crate::utils;
+
+pub static PEN_THIN: [f64; 5] = [1.0, 4.0, 8.0, 10.0, 15.0];
+
+// editor commands shared by the toolbar buttons and the keyboard shortcuts
+#[derive(Clone)]
+pub struct Actions {
+    document: Document,
+    mount: Mount,
+    context: CanvasRenderingContext2d,
+    state: Rc<RefCell<State>>,
+}
+
+impl Actions {
+    pub fn new(
+        document: &Document,
+        mount: &Mount,
+        state: &Rc<RefCell<State>>,
+    ) -> Result<Actions, JsValue> {
+        let context = utils::get_context(&mount.canvas)?;
+
+        Ok(Actions {
+            document: document.clone(),
+            mount: mount.clone(),
+            context,
+            state: state.clone(),
+        })
+    }
+
+    pub fn mount(&self) -> &Mount {
+        &self.mount
+    }
+
+    // the host hears of it once the change is done and nothing is borrowed
+    fn changed(&self) {
+        let hooks = self.state.borrow().get_hooks();
+        hooks.changed();
+    }
+
+    fn snapshot(&self) -> Result<web_sys::ImageData, JsValue> {
+        self.context.get_image_data(
+            0.0,
+            0.0,
+            self.state.borrow().get_width() as f64,
+            self.state.borrow().get_height() as f64,
+        )
+    }
+
+    pub fn pen(&self) -> Result<(), JsValue> {
+        self.set_tool(Tool::Pen)
+    }
+
+    pub fn eraser(&self) -> Result<(), JsValue> {
+        self.set_tool(Tool::Eraser)
+    }
+
+    fn set_tool(&self, tool: Tool) -> Result<(), JsValue> {
+        self.state.borrow_mut().set_tool(tool);
+        toolbar::render_state(&self.mount, &self.state.borrow())
+    }
+
+    pub fn set_pen_thin(&self, thin: f64) -> Result<(), JsValue> {
+        self.state.borrow_mut().set_pen_thin(thin);
+        toolbar::render_state(&self.mount, &self.state.borrow())
+    }
+
+    // step through `PEN_THIN`, direction is -1 or 1
+    pub fn step_pen_thin(&self, direction: i32) -> Result<(), JsValue> {
+        let current = self.state.borrow().get_pen_thin();
+        let index = PEN_THIN
+            .iter()
+            .position(|t| *t >= current)
+            .unwrap_or(PEN_THIN.len() - 1) as i32;
+        let next = (index + direction).max(0).min(PEN_THIN.len() as i32 - 1);
+        self.set_pen_thin(PEN_THIN[next as usize])
+    }
+
+    pub fn undo(&self) -> Result<(), JsValue> {
+        let image_data = self.snapshot()?;
+        let undo = self.state.borrow_mut().undo(image_data);
+        if let Some(u) = undo {
+            self.context.put_image_data(&u, 0.0, 0.0)?;
+        }
+        self.changed();
+
+        Ok(())
+    }
+
+    pub fn redo(&self) -> Result<(), JsValue> {
+        let image_data = self.snapshot()?;
+        let redo = self.state.borrow_mut().redo(image_data);
+        if let Some(r) = redo {
+            self.context.put_image_data(&r, 0.0, 0.0)?;
+        }
+        self.changed();
+
+        Ok(())
+    }
+
+    pub fn clear(&self) -> Result<(), JsValue> {
+        let image_data = self.snapshot()?;
+        self.state.borrow_mut().add_undo(image_data);
+        self.context.clear_rect(
+            0.0,
+            0.0,
+            self.state.borrow().get_width() as f64,
+            self.state.borrow().get_height() as f64,
+        );
+        self.state.borrow_mut().set_strokes(vec![]);
+        self.changed();
+
+        Ok(())
+    }
+
+    // append the canvas as a new frame
+    pub fn add_frame(&self) -> Result<(), JsValue> {
+        let url = self.mount.canvas.to_data_url_with_type("image/png")?;
+        self.state.borrow_mut().add_preview_image(url);
+        let last = self.state.borrow().get_preview_image_len() - 1;
+        let strokes = self.state.borrow().get_strokes();
+        self.state.borrow_mut().set_frame_strokes(last, strokes);
+        self.state.borrow_mut().set_current_frame(Some(last));
+
+        toolbar::render_preview(&self.document, &self.mount.preview, &self.state)?;
+        self.changed();
+        Ok(())
+    }
+
+    // draw `image` centered onto the canvas, scaled down when it does not fit
+    pub fn place_image(&self, image: &HtmlImageElement) -> Result<(), JsValue> {
+        let image_data = self.snapshot()?;
+        self.state.borrow_mut().add_undo(image_data);
+        draw_fitted(&self.context, image, &self.state.borrow())?;
+        self.changed();
+        Ok(())
+    }
+
+    // append `image` as a new frame without touching the canvas
+    pub fn add_image_frame(&self, image: &HtmlImageElement) -> Result<(), JsValue> {
+        let scratch = self
+            .document
+            .create_element("canvas")?
+            .dyn_into::<HtmlCanvasElement>()?;
+        scratch.set_width(self.state.borrow().get_width());
+        scratch.set_height(self.state.borrow().get_height());
+        let context = utils::get_context(&scratch)?;
+        draw_fitted(&context, image, &self.state.borrow())?;
+
+        let url = scratch.to_data_url_with_type("image/png")?;
+        self.state.borrow_mut().add_preview_image(url);
+        toolbar::render_preview(&self.document, &self.mount.preview, &self.state)?;
+        self.changed();
+        Ok(())
+    }
+
+    pub fn clear_frames(&self) -> Result<(), JsValue> {
+        self.state.borrow_mut().delete_all_images();
+        toolbar::render_preview(&self.document, &self.mount.preview, &self.state)?;
+        self.changed();
+        Ok(())
+    }
+
+    // move the frame selection by `offset` and load that frame onto the canvas
+    pub fn step_frame(&self, offset: i32) -> Result<(), JsValue> {
+        let len = self.state.borrow().get_preview_image_len() as i32;
+        if len == 0 {
+            return Ok(());
+        }
+        let index = match self.state.borrow().get_current_frame() {
+            Some(current) => (current as i32 + offset).max(0).min(len - 1),
+            None if offset < 0 => len - 1,
+            None => 0,
+        };
+        self.select_frame(index as usize)
+    }
+
+    pub fn select_frame(&self, index: usize) -> Result<(), JsValue> {
+        let data = match self.state.borrow().get_preview_image().get(index) {
+            Some(data) => data.clone(),
+            None => return Ok(()),
+        };
+
+        let image_data = self.snapshot()?;
+        self.state.borrow_mut().add_undo(image_data);
+        let img = codec::decode_data_url(&data)?;
+        self.context
+            .clear_rect(0.0, 0.0, img.width() as f64, img.height() as f64);
+        self.context
+            .put_image_data(&codec::rgba_to_image_data(&img)?, 0.0, 0.0)?;
+        let strokes = self.state.borrow().get_frame_strokes()[index].clone();
+        self.state.borrow_mut().set_strokes(strokes);
+        self.state.borrow_mut().set_current_frame(Some(index));
+
+        toolbar::render_preview(&self.document, &self.mount.preview, &self.state)
+    }
+}
+
+fn draw_fitted(
+    context: &CanvasRenderingContext2d,
+    image: &HtmlImageElement,
+    state: &State,
+) -> Result<(), JsValue> {
+    let (x, y, w, h) = transform::contain(
+        image.natural_width() as f64,
+        image.natural_height() as f64,
+        state.get_width() as f64,
+        state.get_height() as f64,
+    );
+    // the corner lands on a guide or grid line like a stroke would
+    let (x, y) = state.snap_point((x, y));
+    // the eraser mode would punch the image out instead of drawing it
+    context.save();
+    context.set_global_composite_operation("source-over")?;
+    context.draw_image_with_html_image_element_and_dw_and_dh(image, x, y, w, h)?;
+    context.restore();
+
+    Ok(())
+}
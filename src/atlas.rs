@@ -0,0 +1,685 @@
+use image::{imageops, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::decoder;
+use crate::transform::{self, Rect};
+
+// how frames are laid out on the sheet
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    // equal cells, `columns` 0 picks a roughly square grid
+    Grid { columns: u32 },
+    // rows of frames sorted by height, as small as the frames allow
+    Packed,
+}
+
+// the json array layout written by TexturePacker and Aseprite
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Atlas {
+    pub frames: Vec<AtlasFrame>,
+    #[serde(default)]
+    pub meta: AtlasMeta,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AtlasFrame {
+    #[serde(default)]
+    pub filename: String,
+    // where the frame is on the sheet
+    pub frame: AtlasRect,
+    #[serde(default)]
+    pub rotated: bool,
+    #[serde(default)]
+    pub trimmed: bool,
+    // where the (trimmed) frame goes inside the original frame
+    pub sprite_source_size: Option<AtlasRect>,
+    pub source_size: Option<AtlasSize>,
+    // milliseconds
+    pub duration: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AtlasMeta {
+    #[serde(default)]
+    pub app: String,
+    #[serde(default)]
+    pub image: String,
+    #[serde(default)]
+    pub format: String,
+    #[serde(default)]
+    pub size: AtlasSize,
+    #[serde(default)]
+    pub scale: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AtlasRect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AtlasSize {
+    pub w: u32,
+    pub h: u32,
+}
+
+impl From<Rect> for AtlasRect {
+    fn from(rect: Rect) -> AtlasRect {
+        AtlasRect {
+            x: rect.x,
+            y: rect.y,
+            w: rect.width,
+            h: rect.height,
+        }
+    }
+}
+
+pub struct SpriteSheet {
+    pub image: RgbaImage,
+    pub atlas: Atlas,
+}
+
+// `durations` are in seconds, one per frame
+pub fn pack(
+    frames: &[RgbaImage],
+    durations: &[f64],
+    layout: Layout,
+    trim: bool,
+    padding: u32,
+    image_name: &str,
+) -> SpriteSheet {
+    let pieces: Vec<(RgbaImage, Rect)> = frames
+        .iter()
+        .map(|img| {
+            let full = Rect {
+                x: 0,
+                y: 0,
+                width: img.width(),
+                height: img.height(),
+            };
+            // an empty frame still needs a pixel on the sheet
+            let rect = if trim {
+                transform::content_bounds(std::slice::from_ref(img)).unwrap_or(Rect {
+                    width: 1,
+                    height: 1,
+                    ..full
+                })
+            } else {
+                full
+            };
+            let piece = if rect == full {
+                img.clone()
+            } else {
+                transform::crop(img, rect)
+            };
+            (piece, rect)
+        })
+        .collect();
+
+    let sizes: Vec<(u32, u32)> = pieces
+        .iter()
+        .map(|(piece, _)| (piece.width(), piece.height()))
+        .collect();
+    let (positions, width, height) = match layout {
+        Layout::Grid { columns } => grid(&sizes, columns, padding),
+        Layout::Packed => shelves(&sizes, padding),
+    };
+
+    let mut image = RgbaImage::new(width.max(1), height.max(1));
+    let mut atlas_frames = vec![];
+    for (i, ((piece, rect), (x, y))) in pieces.iter().zip(positions).enumerate() {
+        imageops::replace(&mut image, piece, x, y);
+
+        let source = &frames[i];
+        atlas_frames.push(AtlasFrame {
+            filename: format!("frame {}", i),
+            frame: AtlasRect {
+                x,
+                y,
+                w: piece.width(),
+                h: piece.height(),
+            },
+            rotated: false,
+            trimmed: piece.dimensions() != source.dimensions(),
+            sprite_source_size: Some((*rect).into()),
+            source_size: Some(AtlasSize {
+                w: source.width(),
+                h: source.height(),
+            }),
+            duration: durations.get(i).map(|d| (d * 1000.0).round() as u32),
+        });
+    }
+
+    SpriteSheet {
+        atlas: Atlas {
+            frames: atlas_frames,
+            meta: AtlasMeta {
+                app: "drawasm".to_string(),
+                image: image_name.to_string(),
+                format: "RGBA8888".to_string(),
+                size: AtlasSize {
+                    w: image.width(),
+                    h: image.height(),
+                },
+                scale: "1".to_string(),
+            },
+        },
+        image,
+    }
+}
+
+// positions and sheet size for cells as big as the biggest frame
+fn grid(sizes: &[(u32, u32)], columns: u32, padding: u32) -> (Vec<(u32, u32)>, u32, u32) {
+    if sizes.is_empty() {
+        return (vec![], 0, 0);
+    }
+    let columns = match columns {
+        0 => (sizes.len() as f64).sqrt().ceil() as u32,
+        columns => columns.min(sizes.len() as u32),
+    };
+    let rows = (sizes.len() as u32).div_ceil(columns);
+    let cell_w = sizes.iter().map(|(w, _)| *w).max().unwrap_or(0);
+    let cell_h = sizes.iter().map(|(_, h)| *h).max().unwrap_or(0);
+
+    let positions = (0..sizes.len() as u32)
+        .map(|i| {
+            (
+                (i % columns) * (cell_w + padding),
+                (i / columns) * (cell_h + padding),
+            )
+        })
+        .collect();
+
+    (
+        positions,
+        columns * (cell_w + padding) - padding,
+        rows * (cell_h + padding) - padding,
+    )
+}
+
+// tallest frames first, a new row whenever the next one would pass a roughly square width
+fn shelves(sizes: &[(u32, u32)], padding: u32) -> (Vec<(u32, u32)>, u32, u32) {
+    let area: u64 = sizes
+        .iter()
+        .map(|(w, h)| u64::from(w + padding) * u64::from(h + padding))
+        .sum();
+    let widest = sizes.iter().map(|(w, _)| *w).max().unwrap_or(0);
+    let max_width = widest.max((area as f64).sqrt().ceil() as u32);
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|a, b| sizes[*b].1.cmp(&sizes[*a].1));
+
+    let mut positions = vec![(0, 0); sizes.len()];
+    let (mut x, mut y, mut row_h) = (0, 0, 0);
+    let (mut width, mut height) = (0, 0);
+    for i in order {
+        let (w, h) = sizes[i];
+        if x > 0 && x + w > max_width {
+            x = 0;
+            y += row_h + padding;
+            row_h = 0;
+        }
+        positions[i] = (x, y);
+        width = width.max(x + w);
+        height = height.max(y + h);
+        x += w + padding;
+        row_h = row_h.max(h);
+    }
+
+    (positions, width, height)
+}
+
+// how a sheet without a descriptor is cut, zero sizes or counts are worked out from the rest
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridSpec {
+    pub columns: u32,
+    pub rows: u32,
+    pub cell_w: u32,
+    pub cell_h: u32,
+    // around the whole sheet
+    pub margin: u32,
+    // between cells
+    pub spacing: u32,
+}
+
+impl GridSpec {
+    // `(columns, rows, cell_w, cell_h)` for a `width`x`height` sheet
+    fn resolve(&self, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+        let (columns, cell_w) = resolve_axis(width, self.columns, self.cell_w, self)?;
+        let (rows, cell_h) = resolve_axis(height, self.rows, self.cell_h, self)?;
+
+        Some((columns, rows, cell_w, cell_h))
+    }
+}
+
+fn resolve_axis(length: u32, count: u32, cell: u32, spec: &GridSpec) -> Option<(u32, u32)> {
+    let inner = length.checked_sub(spec.margin.checked_mul(2)?)?;
+    let (count, cell) = match (count, cell) {
+        (0, 0) => return None,
+        (0, cell) => (
+            inner.checked_add(spec.spacing)? / cell.checked_add(spec.spacing)?,
+            cell,
+        ),
+        (count, 0) => (
+            count,
+            inner.checked_sub((count - 1).checked_mul(spec.spacing)?)? / count,
+        ),
+        (count, cell) => (count, cell),
+    };
+    if count == 0 || cell == 0 {
+        return None;
+    }
+
+    Some((count, cell))
+}
+
+// cells in reading order, empty cells at the end of the sheet are dropped
+pub fn slice_grid(sheet: &RgbaImage, spec: GridSpec) -> Vec<RgbaImage> {
+    let (columns, rows, cell_w, cell_h) = match spec.resolve(sheet.width(), sheet.height()) {
+        Some(resolved) => resolved,
+        None => return vec![],
+    };
+
+    let mut frames = vec![];
+    // where the `index`th cell of `cell` pixels starts
+    let start = |index: u32, cell: u32| {
+        index
+            .checked_mul(cell.checked_add(spec.spacing)?)?
+            .checked_add(spec.margin)
+    };
+    for row in 0..rows {
+        for column in 0..columns {
+            let (x, y) = match (start(column, cell_w), start(row, cell_h)) {
+                (Some(x), Some(y)) => (x, y),
+                _ => continue,
+            };
+            let rect = Rect {
+                x,
+                y,
+                width: cell_w,
+                height: cell_h,
+            };
+            if !on_sheet(sheet, rect) {
+                continue;
+            }
+            frames.push(transform::crop(sheet, rect));
+        }
+    }
+    while frames
+        .last()
+        .is_some_and(|f| transform::content_bounds(std::slice::from_ref(f)).is_none())
+    {
+        frames.pop();
+    }
+
+    frames
+}
+
+// frames put back to their untrimmed size, durations in seconds when the descriptor has them.
+// every frame gets the size of the biggest one so they fit one document, `None` when all
+// of them together would be more than an imported animation may hold
+pub fn slice_atlas(sheet: &RgbaImage, atlas: &Atlas) -> Option<Vec<(RgbaImage, Option<f64>)>> {
+    let rects: Vec<(Rect, &AtlasFrame)> = atlas
+        .frames
+        .iter()
+        .filter_map(|frame| {
+            let rect = frame.frame;
+            // rotated frames are stored turned clockwise, so their rect is w and h swapped
+            let (w, h) = if frame.rotated {
+                (rect.h, rect.w)
+            } else {
+                (rect.w, rect.h)
+            };
+            let rect = Rect {
+                x: rect.x,
+                y: rect.y,
+                width: w,
+                height: h,
+            };
+            if !on_sheet(sheet, rect) {
+                return None;
+            }
+            Some((rect, frame))
+        })
+        .collect();
+
+    // the descriptor is not trusted with the size of the document
+    let source_size = |frame: &AtlasFrame| {
+        frame
+            .source_size
+            .map(|s| (s.w.min(transform::MAX_SIDE), s.h.min(transform::MAX_SIDE)))
+            .unwrap_or((frame.frame.w, frame.frame.h))
+    };
+    let width = rects
+        .iter()
+        .map(|(_, f)| source_size(f).0)
+        .max()
+        .unwrap_or(0);
+    let height = rects
+        .iter()
+        .map(|(_, f)| source_size(f).1)
+        .max()
+        .unwrap_or(0);
+    let total = (rects.len() as u64)
+        .saturating_mul(u64::from(width))
+        .saturating_mul(u64::from(height));
+    if total > decoder::MAX_TOTAL_PIXELS {
+        return None;
+    }
+
+    let frames = rects
+        .iter()
+        .map(|(rect, frame)| {
+            let piece = transform::crop(sheet, *rect);
+            let piece = if frame.rotated {
+                imageops::rotate270(&piece)
+            } else {
+                piece
+            };
+            let mut image = RgbaImage::new(width, height);
+            let (x, y) = frame
+                .sprite_source_size
+                .map(|r| (r.x, r.y))
+                .unwrap_or((0, 0));
+            imageops::replace(&mut image, &piece, x, y);
+            (image, frame.duration.map(|d| f64::from(d) / 1000.0))
+        })
+        .collect();
+
+    Some(frames)
+}
+
+// a rect whose edges overflow is off the sheet as well
+fn on_sheet(sheet: &RgbaImage, rect: Rect) -> bool {
+    let right = rect.x.checked_add(rect.width);
+    let bottom = rect.y.checked_add(rect.height);
+    right.is_some_and(|r| r <= sheet.width()) && bottom.is_some_and(|b| b <= sheet.height())
+}
+
+impl Atlas {
+    // besides the array layout, Aseprite's hash layout keys the frames by file name
+    pub fn from_json(s: &str) -> serde_json::Result<Atlas> {
+        #[derive(Deserialize)]
+        struct HashAtlas {
+            frames: FrameMap,
+            #[serde(default)]
+            meta: AtlasMeta,
+        }
+
+        if let Ok(atlas) = serde_json::from_str::<Atlas>(s) {
+            return Ok(atlas);
+        }
+        let hash: HashAtlas = serde_json::from_str(s)?;
+
+        Ok(Atlas {
+            frames: hash.frames.0,
+            meta: hash.meta,
+        })
+    }
+}
+
+// a json object of frames read in file order, a plain map would sort the names
+struct FrameMap(Vec<AtlasFrame>);
+
+impl<'de> Deserialize<'de> for FrameMap {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<FrameMap, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = FrameMap;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a map of frames")
+            }
+
+            fn visit_map<M: serde::de::MapAccess<'de>>(
+                self,
+                mut map: M,
+            ) -> Result<FrameMap, M::Error> {
+                let mut frames = vec![];
+                while let Some((filename, mut frame)) = map.next_entry::<String, AtlasFrame>()? {
+                    frame.filename = filename;
+                    frames.push(frame);
+                }
+                Ok(FrameMap(frames))
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    // a transparent `w`x`h` frame with an opaque square at `x`,`y`
+    fn frame(w: u32, h: u32, x: u32, y: u32, size: u32) -> RgbaImage {
+        RgbaImage::from_fn(w, h, |px, py| {
+            if px >= x && px < x + size && py >= y && py < y + size {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        })
+    }
+
+    fn raw(images: &[RgbaImage]) -> Vec<Vec<u8>> {
+        images.iter().map(|image| image.to_vec()).collect()
+    }
+
+    #[test]
+    fn grid_places_cells_in_rows() {
+        let frames = vec![frame(8, 8, 0, 0, 8); 5];
+        let sheet = pack(
+            &frames,
+            &[0.1; 5],
+            Layout::Grid { columns: 2 },
+            false,
+            1,
+            "sheet.png",
+        );
+
+        assert_eq!(sheet.image.dimensions(), (17, 26));
+        let rects: Vec<(u32, u32)> = sheet
+            .atlas
+            .frames
+            .iter()
+            .map(|f| (f.frame.x, f.frame.y))
+            .collect();
+        assert_eq!(rects, vec![(0, 0), (9, 0), (0, 9), (9, 9), (0, 18)]);
+        assert_eq!(sheet.atlas.frames[0].duration, Some(100));
+        assert_eq!(sheet.atlas.meta.size, AtlasSize { w: 17, h: 26 });
+    }
+
+    #[test]
+    fn trimmed_frames_keep_their_offset() {
+        let frames = vec![frame(16, 16, 4, 6, 3), frame(16, 16, 0, 0, 16)];
+        let sheet = pack(&frames, &[0.5, 0.5], Layout::Packed, true, 0, "sheet.png");
+        let first = &sheet.atlas.frames[0];
+
+        assert!(first.trimmed);
+        assert_eq!((first.frame.w, first.frame.h), (3, 3));
+        assert_eq!(
+            first.sprite_source_size,
+            Some(AtlasRect {
+                x: 4,
+                y: 6,
+                w: 3,
+                h: 3
+            })
+        );
+        assert_eq!(first.source_size, Some(AtlasSize { w: 16, h: 16 }));
+        assert!(!sheet.atlas.frames[1].trimmed);
+        let pixel = sheet.image.get_pixel(first.frame.x, first.frame.y);
+        assert_eq!(pixel.0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn packed_frames_do_not_overlap() {
+        let frames: Vec<RgbaImage> = (1..8).map(|s| frame(s * 3, s * 2, 0, 0, 64)).collect();
+        let sheet = pack(&frames, &[], Layout::Packed, false, 1, "sheet.png");
+        let rects: Vec<AtlasRect> = sheet.atlas.frames.iter().map(|f| f.frame).collect();
+
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.x + a.w <= sheet.image.width() && a.y + a.h <= sheet.image.height());
+            for b in rects.iter().skip(i + 1) {
+                let apart =
+                    a.x + a.w <= b.x || b.x + b.w <= a.x || a.y + a.h <= b.y || b.y + b.h <= a.y;
+                assert!(apart, "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn grid_works_out_missing_sizes() {
+        // 2x2 cells of 4px with a 1px margin and 2px spacing
+        let sheet = RgbaImage::from_pixel(12, 12, Rgba([0, 0, 255, 255]));
+        let by_count = GridSpec {
+            columns: 2,
+            rows: 2,
+            cell_w: 0,
+            cell_h: 0,
+            margin: 1,
+            spacing: 2,
+        };
+        let by_size = GridSpec {
+            columns: 0,
+            rows: 0,
+            cell_w: 4,
+            cell_h: 4,
+            ..by_count
+        };
+
+        assert_eq!(by_count.resolve(12, 12), Some((2, 2, 4, 4)));
+        assert_eq!(by_size.resolve(12, 12), Some((2, 2, 4, 4)));
+        assert_eq!(slice_grid(&sheet, by_count).len(), 4);
+    }
+
+    #[test]
+    fn grid_drops_trailing_empty_cells() {
+        let frames = vec![
+            frame(4, 4, 0, 0, 4),
+            frame(4, 4, 0, 0, 0),
+            frame(4, 4, 1, 1, 1),
+        ];
+        // room for a fourth cell that stays empty
+        let mut sheet = RgbaImage::new(16, 4);
+        for (i, frame) in frames.iter().enumerate() {
+            imageops::replace(&mut sheet, frame, i as u32 * 4, 0);
+        }
+        let spec = GridSpec {
+            columns: 4,
+            rows: 1,
+            cell_w: 0,
+            cell_h: 0,
+            margin: 0,
+            spacing: 0,
+        };
+
+        // the empty frame in the middle stays, the unused fourth cell does not
+        assert_eq!(raw(&slice_grid(&sheet, spec)), raw(&frames));
+    }
+
+    #[test]
+    fn out_of_range_numbers_are_dropped() {
+        let sheet = RgbaImage::from_pixel(8, 8, Rgba([0, 0, 255, 255]));
+        let spec = GridSpec {
+            columns: 2,
+            rows: 2,
+            cell_w: 0,
+            cell_h: 0,
+            margin: u32::MAX,
+            spacing: u32::MAX,
+        };
+        assert!(slice_grid(&sheet, spec).is_empty());
+        let spec = GridSpec {
+            cell_w: u32::MAX,
+            cell_h: 4,
+            margin: 0,
+            spacing: 1,
+            ..spec
+        };
+        assert!(slice_grid(&sheet, spec).is_empty());
+
+        let json = format!(
+            r#"{{"frames": [
+                {{"frame": {{"x": {max}, "y": 0, "w": 2, "h": 2}}}},
+                {{"frame": {{"x": 0, "y": 0, "w": 2, "h": 2}},
+                  "sourceSize": {{"w": {max}, "h": 3}}}}
+            ]}}"#,
+            max = u32::MAX
+        );
+        let sliced = slice_atlas(&sheet, &Atlas::from_json(&json).unwrap()).unwrap();
+        assert_eq!(sliced.len(), 1);
+        assert_eq!(sliced[0].0.dimensions(), (transform::MAX_SIDE, 3));
+    }
+
+    #[test]
+    fn refuses_too_many_full_size_frames() {
+        let sheet = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 255, 255]));
+        // every frame is blown up to a full size document
+        let frame = format!(
+            r#"{{"frame": {{"x": 0, "y": 0, "w": 2, "h": 2}}, "sourceSize": {{"w": {max}, "h": {max}}}}}"#,
+            max = transform::MAX_SIDE
+        );
+        let json = |count: usize| {
+            format!(
+                r#"{{"frames": [{}]}}"#,
+                vec![frame.as_str(); count].join(",")
+            )
+        };
+
+        let atlas = Atlas::from_json(&json(2)).unwrap();
+        assert_eq!(slice_atlas(&sheet, &atlas).map(|f| f.len()), Some(2));
+        let atlas = Atlas::from_json(&json(17)).unwrap();
+        assert!(slice_atlas(&sheet, &atlas).is_none());
+    }
+
+    #[test]
+    fn atlas_round_trip() {
+        let frames = vec![
+            frame(16, 16, 4, 6, 3),
+            frame(16, 16, 0, 0, 16),
+            frame(16, 16, 9, 2, 5),
+        ];
+        let sheet = pack(
+            &frames,
+            &[0.1, 0.2, 0.3],
+            Layout::Packed,
+            true,
+            1,
+            "sheet.png",
+        );
+        let json = serde_json::to_string(&sheet.atlas).unwrap();
+        let atlas = Atlas::from_json(&json).unwrap();
+        let sliced = slice_atlas(&sheet.image, &atlas).unwrap();
+
+        let images: Vec<RgbaImage> = sliced.iter().map(|(image, _)| image.clone()).collect();
+        assert_eq!(raw(&images), raw(&frames));
+        let durations: Vec<Option<f64>> = sliced.iter().map(|(_, d)| *d).collect();
+        assert_eq!(durations, vec![Some(0.1), Some(0.2), Some(0.3)]);
+    }
+
+    #[test]
+    fn reads_hash_layout_in_file_order() {
+        let json = r#"{
+            "frames": {
+                "walk 10": { "frame": { "x": 2, "y": 0, "w": 2, "h": 2 }, "duration": 50 },
+                "walk 2": { "frame": { "x": 0, "y": 0, "w": 2, "h": 2 } }
+            },
+            "meta": { "image": "walk.png" }
+        }"#;
+        let atlas = Atlas::from_json(json).unwrap();
+        let names: Vec<&str> = atlas.frames.iter().map(|f| f.filename.as_str()).collect();
+
+        assert_eq!(names, vec!["walk 10", "walk 2"]);
+        assert_eq!(atlas.frames[0].duration, Some(50));
+        assert_eq!(atlas.meta.image, "walk.png");
+    }
+}
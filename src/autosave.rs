@@ -0,0 +1,377 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    window, HtmlCanvasElement, IdbDatabase, IdbRequest, IdbTransaction, IdbTransactionMode, Storage,
+};
+
+use crate::actions::Actions;
+use crate::error::Error;
+use crate::notify;
+use crate::overlay::Overlay;
+use crate::project::Project;
+use crate::save;
+use crate::state::State;
+
+static DB_NAME: &str = "drawasm";
+static DB_VERSION: u32 = 1;
+static STORE_NAME: &str = "projects";
+// local storage is only a few megabytes per origin, bigger projects are not kept there
+static LOCAL_STORAGE_LIMIT: usize = 2_000_000;
+// milliseconds
+static AUTOSAVE_INTERVAL: i32 = 30_000;
+static CHANGE_DELAY: i32 = 1_000;
+// events after which the document may have changed
+static CHANGE_EVENTS: [&str; 4] = ["mouseup", "keyup", "change", "click"];
+
+type Done = Box<dyn FnOnce(Option<JsValue>)>;
+
+// where the project is kept between sessions
+#[derive(Clone)]
+struct Autosave {
+    // one saved session per key, so editors on the same page keep their own
+    key: String,
+    db: Option<IdbDatabase>,
+    state: Rc<RefCell<State>>,
+    last_saved: Rc<RefCell<String>>,
+    // a save that keeps failing is reported once, not on every change
+    failing: Rc<Cell<bool>>,
+}
+
+// offer to restore the last session, then keep saving the project in the background
+pub fn init_autosave(
+    key: &str,
+    overlay: &Overlay,
+    actions: &Actions,
+    state: &Rc<RefCell<State>>,
+) -> Result<(), JsValue> {
+    let key = key.to_string();
+    let canvas = actions.mount().canvas.clone();
+    let overlay = overlay.clone();
+    let actions = actions.clone();
+    let state = state.clone();
+
+    open_db(move |db| {
+        let autosave = Autosave {
+            key,
+            db,
+            state,
+            last_saved: Rc::new(RefCell::new(String::new())),
+            failing: Rc::new(Cell::new(false)),
+        };
+        let autosave_clone = autosave.clone();
+        let loaded = autosave.load(move |json| {
+            if let Some(json) = json {
+                let restored = restore(&json, &canvas, &overlay, &actions, &autosave_clone.state);
+                if restored.is_err() {
+                    // it would fail the same way on every start
+                    autosave_clone.discard();
+                }
+                notify::report("restore session", restored);
+            }
+            notify::report("autosave", autosave_clone.start());
+        });
+        notify::report("autosave", loaded);
+    })
+}
+
+fn restore(
+    json: &str,
+    canvas: &HtmlCanvasElement,
+    overlay: &Overlay,
+    actions: &Actions,
+    state: &Rc<RefCell<State>>,
+) -> Result<(), JsValue> {
+    let project = Project::from_json(json).map_err(Error::from)?;
+    // an empty session is not worth asking about
+    if project.frames.is_empty() {
+        return Ok(());
+    }
+    let message = format!(
+        "restore previous session? ({} frames)",
+        project.frames.len()
+    );
+    if window().unwrap().confirm_with_message(&message)? {
+        save::load_project(project, canvas, overlay, actions, state)?;
+    }
+
+    Ok(())
+}
+
+impl Autosave {
+    // save once things settle after a change, and every `AUTOSAVE_INTERVAL` regardless
+    fn start(&self) -> Result<(), JsValue> {
+        let window = window().unwrap();
+        let document = window.document().expect("Could not find `document`");
+
+        let handle_save = {
+            let autosave = self.clone();
+            Closure::wrap(Box::new(move || {
+                let saved = autosave.save();
+                autosave.report(saved);
+            }) as Box<dyn FnMut()>)
+        };
+        window.set_interval_with_callback_and_timeout_and_arguments_0(
+            handle_save.as_ref().unchecked_ref(),
+            AUTOSAVE_INTERVAL,
+        )?;
+        window
+            .add_event_listener_with_callback("pagehide", handle_save.as_ref().unchecked_ref())?;
+
+        let timeout = Rc::new(Cell::new(None));
+        let handle_change = {
+            let window = window.clone();
+            let save = handle_save.as_ref().clone();
+            Closure::wrap(Box::new(move || {
+                if let Some(handle) = timeout.take() {
+                    window.clear_timeout_with_handle(handle);
+                }
+                let handle = window.set_timeout_with_callback_and_timeout_and_arguments_0(
+                    save.unchecked_ref(),
+                    CHANGE_DELAY,
+                );
+                match handle {
+                    Ok(handle) => timeout.set(Some(handle)),
+                    Err(err) => notify::report("autosave", Err(err)),
+                }
+            }) as Box<dyn FnMut()>)
+        };
+        for event in CHANGE_EVENTS.iter() {
+            document.add_event_listener_with_callback_and_bool(
+                event,
+                handle_change.as_ref().unchecked_ref(),
+                true,
+            )?;
+        }
+
+        handle_save.forget();
+        handle_change.forget();
+
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let json = Project::from_state(&self.state.borrow()).to_json()?;
+        if *self.last_saved.borrow() == json {
+            return Ok(());
+        }
+
+        // small projects go to local storage, it is written before the page can go away
+        if write_local(&self.key, &json) {
+            *self.last_saved.borrow_mut() = json;
+            return Ok(());
+        }
+        match &self.db {
+            Some(db) => self.write_db(db, json),
+            None => Err(not_saved()),
+        }
+    }
+
+    // a failed save is shown the first time, then again only after a save went through
+    fn report(&self, saved: Result<(), Error>) {
+        match saved {
+            Ok(()) => self.failing.set(false),
+            Err(err) => {
+                if !self.failing.replace(true) {
+                    notify::error("autosave", &err);
+                }
+            }
+        }
+    }
+
+    // the project counts as saved once the transaction commits, local storage is
+    // tried again if it does not
+    fn write_db(&self, db: &IdbDatabase, json: String) -> Result<(), Error> {
+        let tx = db.transaction_with_str_and_mode(STORE_NAME, IdbTransactionMode::Readwrite)?;
+        let put = tx.object_store(STORE_NAME).and_then(|store| {
+            store.put_with_key(&JsValue::from_str(&json), &JsValue::from_str(&self.key))
+        });
+        if let Err(err) = put {
+            tx.abort().ok();
+            return Err(err.into());
+        }
+
+        let autosave = self.clone();
+        on_commit(
+            &tx,
+            Box::new(move |committed| {
+                if committed {
+                    // the fallback copy is older now
+                    if let Some(storage) = local_storage() {
+                        storage.remove_item(&storage_key(&autosave.key)).ok();
+                    }
+                } else if !write_local(&autosave.key, &json) {
+                    autosave.report(Err(not_saved()));
+                    return;
+                }
+                *autosave.last_saved.borrow_mut() = json;
+                autosave.report(Ok(()));
+            }),
+        );
+
+        Ok(())
+    }
+
+    // forget the saved session, wherever it is kept
+    fn discard(&self) {
+        if let Some(storage) = local_storage() {
+            storage.remove_item(&storage_key(&self.key)).ok();
+        }
+        if let Some(db) = &self.db {
+            let deleted = db
+                .transaction_with_str_and_mode(STORE_NAME, IdbTransactionMode::Readwrite)
+                .and_then(|tx| tx.object_store(STORE_NAME))
+                .and_then(|store| store.delete(&JsValue::from_str(&self.key)));
+            notify::report("autosave", deleted.map(|_| ()));
+        }
+    }
+
+    // hand the saved project, if any, to `on_load`
+    fn load(&self, on_load: impl FnOnce(Option<String>) + 'static) -> Result<(), JsValue> {
+        // a copy in local storage is removed once a newer one is in indexeddb
+        let local = read_local(&self.key);
+        let db = match &self.db {
+            Some(db) if local.is_none() => db,
+            _ => {
+                on_load(local);
+                return Ok(());
+            }
+        };
+
+        let request = db
+            .transaction_with_str(STORE_NAME)?
+            .object_store(STORE_NAME)?
+            .get(&JsValue::from_str(&self.key))?;
+        on_done(
+            &request,
+            Box::new(move |result| on_load(result.and_then(|r| r.as_string()))),
+        );
+
+        Ok(())
+    }
+}
+
+// `on_open` gets `None` when indexeddb can't be used and local storage has to do
+fn open_db(on_open: impl FnOnce(Option<IdbDatabase>) + 'static) -> Result<(), JsValue> {
+    let factory = match window().unwrap().indexed_db() {
+        Ok(Some(factory)) => factory,
+        _ => {
+            on_open(None);
+            return Ok(());
+        }
+    };
+    let request = match factory.open_with_u32(DB_NAME, DB_VERSION) {
+        Ok(request) => request,
+        Err(_) => {
+            on_open(None);
+            return Ok(());
+        }
+    };
+
+    let request_clone = request.clone();
+    let handle_upgrade = Closure::wrap(Box::new(move || {
+        notify::report("autosave", create_store(&request_clone));
+    }) as Box<dyn FnMut()>);
+    request.set_onupgradeneeded(Some(handle_upgrade.as_ref().unchecked_ref()));
+    handle_upgrade.forget();
+
+    on_done(
+        &request,
+        Box::new(move |result| {
+            on_open(result.and_then(|r| r.dyn_into::<IdbDatabase>().ok()));
+        }),
+    );
+
+    Ok(())
+}
+
+// a new or outdated database gets the store the projects are kept in
+fn create_store(request: &IdbRequest) -> Result<(), JsValue> {
+    let db = request.result()?.dyn_into::<IdbDatabase>()?;
+    if !db.object_store_names().contains(STORE_NAME) {
+        db.create_object_store(STORE_NAME)?;
+    }
+    Ok(())
+}
+
+// run `done` once with the result of `request`, or `None` if it failed
+fn on_done(request: &IdbRequest, done: Done) {
+    let done = Rc::new(RefCell::new(Some(done)));
+
+    let handle_success = {
+        let request = request.clone();
+        let done = done.clone();
+        Closure::wrap(Box::new(move || {
+            if let Some(done) = done.borrow_mut().take() {
+                done(request.result().ok());
+            }
+        }) as Box<dyn FnMut()>)
+    };
+    let handle_error = Closure::wrap(Box::new(move || {
+        if let Some(done) = done.borrow_mut().take() {
+            done(None);
+        }
+    }) as Box<dyn FnMut()>);
+
+    request.set_onsuccess(Some(handle_success.as_ref().unchecked_ref()));
+    request.set_onerror(Some(handle_error.as_ref().unchecked_ref()));
+    handle_success.forget();
+    handle_error.forget();
+}
+
+// run `done` once with whether `tx` committed, an error is followed by an abort
+fn on_commit(tx: &IdbTransaction, done: Box<dyn FnOnce(bool)>) {
+    let done = Rc::new(RefCell::new(Some(done)));
+
+    let handle_complete = {
+        let done = done.clone();
+        Closure::wrap(Box::new(move || {
+            if let Some(done) = done.borrow_mut().take() {
+                done(true);
+            }
+        }) as Box<dyn FnMut()>)
+    };
+    let handle_error = Closure::wrap(Box::new(move || {
+        if let Some(done) = done.borrow_mut().take() {
+            done(false);
+        }
+    }) as Box<dyn FnMut()>);
+
+    tx.set_oncomplete(Some(handle_complete.as_ref().unchecked_ref()));
+    tx.set_onerror(Some(handle_error.as_ref().unchecked_ref()));
+    tx.set_onabort(Some(handle_error.as_ref().unchecked_ref()));
+    handle_complete.forget();
+    handle_error.forget();
+}
+
+fn not_saved() -> Error {
+    Error::Js("the project could not be saved, it is lost when the page is closed".to_string())
+}
+
+fn write_local(key: &str, json: &str) -> bool {
+    let storage = match local_storage() {
+        Some(storage) => storage,
+        None => return false,
+    };
+    if json.len() > LOCAL_STORAGE_LIMIT {
+        // better nothing than an outdated session, it would be restored before indexeddb
+        storage.remove_item(&storage_key(key)).ok();
+        return false;
+    }
+
+    storage.set_item(&storage_key(key), json).is_ok()
+}
+
+fn local_storage() -> Option<Storage> {
+    window()?.local_storage().ok()?
+}
+
+fn read_local(key: &str) -> Option<String> {
+    local_storage()?.get_item(&storage_key(key)).ok()?
+}
+
+// e.g. `drawasm.autosave`
+fn storage_key(key: &str) -> String {
+    format!("{}.{}", DB_NAME, key)
+}
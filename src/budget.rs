@@ -0,0 +1,442 @@
+use image::{FilterType, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{window, Document, HtmlButtonElement, HtmlInputElement};
+
+use crate::editor::Mount;
+use crate::error::Error;
+use crate::generate::{self, JobControls};
+use crate::notify;
+use crate::palette::Color;
+use crate::quantize;
+use crate::state::State;
+use crate::transform;
+use crate::widget::Widget;
+use crate::worker::{Outcome, Task};
+
+// limits an export has to stay within, dimensions and frames are optional
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Budget {
+    pub bytes: usize,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub frames: Option<usize>,
+    // scaling stops before the longer side gets shorter than this
+    #[serde(default)]
+    pub min_side: Option<u32>,
+}
+
+pub struct Fitted {
+    pub apng: Vec<u8>,
+    pub fits: bool,
+    // what had to give to get there, e.g. "scaled to 240x240"
+    pub tradeoffs: Vec<String>,
+}
+
+// how far an attempt has gone from the original frames
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Settings {
+    // on top of what the dimension limits already need
+    scale: f64,
+    // keep every `step`th frame, on top of the frame limit
+    step: usize,
+    colors: Option<usize>,
+}
+
+// encode again and again, giving up a little more each time, until the apng fits.
+// pixel art keeps its document palette and is only scaled and thinned out
+pub fn fit(
+    images: Vec<RgbaImage>,
+    delays: &[f64],
+    palette: Option<&[Color]>,
+    budget: &Budget,
+    plays: u32,
+    progress: &mut dyn FnMut(&str, usize, usize),
+) -> Result<Fitted, Error> {
+    let (w, h) = images.first().map_or((1, 1), |img| img.dimensions());
+    let limit_w = budget.width.map_or(1.0, |max| max as f64 / w as f64);
+    let limit_h = budget.height.map_or(1.0, |max| max as f64 / h as f64);
+    let base_scale = limit_w.min(limit_h).min(1.0);
+    // how much further than the dimension limits `shrink` may scale
+    let min_scale = budget
+        .min_side
+        .map_or(0.0, |min| min as f64 / (w.max(h) as f64 * base_scale));
+    let base_step = budget
+        .frames
+        .filter(|max| *max > 0)
+        .map_or(1, |max| images.len().div_ceil(max));
+
+    let mut settings = Settings {
+        scale: 1.0,
+        step: 1,
+        colors: None,
+    };
+    let mut best: Option<(Vec<u8>, Settings)> = None;
+    let mut attempt = 1;
+    loop {
+        let stage = format!("fitting, attempt {}", attempt);
+        attempt += 1;
+        let (frames, frame_delays) = thin(&images, delays, base_step * settings.step);
+        let frames = scale(frames, base_scale * settings.scale, palette.is_some());
+        let (frames, reduced) = match (palette, settings.colors) {
+            (None, Some(colors)) => {
+                let reduced = quantize::median_cut(&frames, colors);
+                let frames = frames
+                    .iter()
+                    .map(|img| quantize::remap(img, &reduced, false))
+                    .collect();
+                (frames, Some(reduced))
+            }
+            _ => (frames, None),
+        };
+        let (apng, _) = generate::encode(
+            frames,
+            &frame_delays,
+            reduced.as_deref().or(palette),
+            true,
+            plays,
+            &mut |done, total| progress(&stage, done, total),
+        )?;
+
+        let fits = apng.len() <= budget.bytes;
+        if best.as_ref().is_none_or(|(b, _)| apng.len() < b.len()) {
+            best = Some((apng, settings));
+        }
+        if fits {
+            break;
+        }
+        match shrink(
+            settings,
+            images.len().div_ceil(base_step),
+            palette.is_some(),
+            min_scale,
+        ) {
+            Some(next) => settings = next,
+            None => break,
+        }
+    }
+
+    let (apng, settings) = best.unwrap();
+    let mut tradeoffs = vec![];
+    let scale = base_scale * settings.scale;
+    if scale < 1.0 {
+        let (sw, sh) = scaled_size(w, h, scale);
+        tradeoffs.push(format!("scaled to {}x{}", sw, sh));
+    }
+    let kept = images.len().div_ceil(base_step * settings.step);
+    if kept < images.len() {
+        tradeoffs.push(format!(
+            "dropped {} of {} frames",
+            images.len() - kept,
+            images.len()
+        ));
+    }
+    if let Some(colors) = settings.colors {
+        tradeoffs.push(format!("reduced to {} colors", colors));
+    }
+
+    Ok(Fitted {
+        fits: apng.len() <= budget.bytes,
+        apng,
+        tradeoffs,
+    })
+}
+
+// the next smaller attempt, colors go first since they cost the least to look at
+fn shrink(settings: Settings, frames: usize, indexed: bool, min_scale: f64) -> Option<Settings> {
+    let mut next = settings;
+    let (half, quarter) = (min_scale.max(0.5), min_scale.max(0.25));
+    if !indexed && settings.colors.is_none_or(|c| c > 64) {
+        next.colors = Some(settings.colors.map_or(256, |c| c / 2));
+    } else if settings.scale > half {
+        next.scale = (settings.scale * 0.85).max(half);
+    } else if settings.step < 2 && frames > 2 {
+        next.step = 2;
+    } else if !indexed && settings.colors.is_some_and(|c| c > 16) {
+        next.colors = settings.colors.map(|c| c / 2);
+    } else if settings.scale > quarter {
+        next.scale = (settings.scale * 0.85).max(quarter);
+    } else {
+        return None;
+    }
+    Some(next)
+}
+
+// every `step`th frame, each holding on for the frames dropped after it
+pub fn thin(images: &[RgbaImage], delays: &[f64], step: usize) -> (Vec<RgbaImage>, Vec<f64>) {
+    let step = step.max(1);
+    let frames = images.iter().step_by(step).cloned().collect();
+    let delays = delays.chunks(step).map(|c| c.iter().sum()).collect();
+    (frames, delays)
+}
+
+fn scale(images: Vec<RgbaImage>, scale: f64, pixel_art: bool) -> Vec<RgbaImage> {
+    if scale >= 1.0 {
+        return images;
+    }
+    let filter = if pixel_art {
+        FilterType::Nearest
+    } else {
+        FilterType::Triangle
+    };
+    images
+        .iter()
+        .map(|img| {
+            let (w, h) = scaled_size(img.width(), img.height(), scale);
+            transform::scale_image(img, w, h, filter)
+        })
+        .collect()
+}
+
+fn scaled_size(w: u32, h: u32, scale: f64) -> (u32, u32) {
+    (
+        ((w as f64 * scale).round() as u32).max(1),
+        ((h as f64 * scale).round() as u32).max(1),
+    )
+}
+
+pub fn init_budget(
+    mount: &Mount,
+    state: &Rc<RefCell<State>>,
+    worker_url: &str,
+) -> Result<(), JsValue> {
+    let document = window()
+        .unwrap()
+        .document()
+        .expect("Could not find `document`");
+
+    let generate = &mount.generate;
+
+    let element = Widget::new("div")
+        .class("row")
+        .class("budget")
+        .build(&document)?;
+
+    let kb = create_number(&document, "KB", "300")?;
+    element.append_child(&kb)?;
+    let width = create_number(&document, "max width", "")?;
+    element.append_child(&width)?;
+    let height = create_number(&document, "max height", "")?;
+    element.append_child(&height)?;
+    let frames = create_number(&document, "max frames", "")?;
+    element.append_child(&frames)?;
+
+    let button = document
+        .create_element("button")?
+        .dyn_into::<HtmlButtonElement>()?;
+    button.set_inner_html("fit to budget");
+    element.append_child(&button)?;
+
+    let report = document.create_element("span")?;
+    report.set_attribute("class", "budget-report")?;
+    element.append_child(&report)?;
+
+    let controls = JobControls::new(
+        &document,
+        &element,
+        std::slice::from_ref(&button),
+        &report,
+        worker_url,
+    )?;
+    let mount = mount.clone();
+    let state = state.clone();
+    let handle_click = Closure::wrap(Box::new(move || {
+        let result = match kb.value().parse::<f64>() {
+            Ok(kb) if kb > 0.0 => {
+                let budget = Budget {
+                    bytes: (kb * 1024.0) as usize,
+                    width: parse(&width),
+                    height: parse(&height),
+                    frames: parse(&frames).map(|f| f as usize),
+                    min_side: None,
+                };
+                export(&controls, &mount, &state, budget)
+            }
+            _ => Err(Error::Input(
+                "size budget has to be a positive number of KB".to_string(),
+            )),
+        };
+        notify::report("fit to budget", result);
+    }) as Box<dyn FnMut()>);
+    button.set_onclick(Some(handle_click.as_ref().unchecked_ref()));
+    handle_click.forget();
+
+    generate.append_child(&element)?;
+
+    Ok(())
+}
+
+// fitted on the worker, downloaded if it fits
+fn export(
+    controls: &JobControls,
+    mount: &Mount,
+    state: &Rc<RefCell<State>>,
+    budget: Budget,
+) -> Result<(), Error> {
+    let frames = generate::frame_files(&state.borrow().get_preview_image())?;
+    let task = Task::Budget {
+        delays: state.borrow().get_frame_delays(),
+        palette: if state.borrow().is_pixel_art() {
+            Some(state.borrow().get_palette())
+        } else {
+            None
+        },
+        budget,
+    };
+
+    let (mount, state) = (mount.clone(), state.clone());
+    controls.run("fit to budget", &task, frames, move |apng, outcome| {
+        if outcome.accepted {
+            notify::report(
+                "fit to budget",
+                generate::download_apng(&apng, &state.borrow(), &mount),
+            );
+        }
+    });
+
+    Ok(())
+}
+
+// what `Task::Budget` runs on the worker
+pub fn run(
+    images: Vec<RgbaImage>,
+    delays: &[f64],
+    palette: Option<&[Color]>,
+    budget: &Budget,
+    progress: &mut dyn FnMut(&str, usize, usize),
+) -> Result<(Vec<u8>, Outcome), Error> {
+    let fitted = fit(images, delays, palette, budget, 0, progress)?;
+
+    let mut report = if fitted.fits {
+        format!("fits: {}", generate::format_size(fitted.apng.len()))
+    } else {
+        format!(
+            "does not fit, smallest is {}",
+            generate::format_size(fitted.apng.len())
+        )
+    };
+    if !fitted.tradeoffs.is_empty() {
+        report.push_str(&format!(" ({})", fitted.tradeoffs.join(", ")));
+    }
+
+    Ok((
+        fitted.apng,
+        Outcome {
+            report,
+            accepted: fitted.fits,
+            violations: vec![],
+        },
+    ))
+}
+
+fn create_number(
+    document: &Document,
+    placeholder: &str,
+    value: &str,
+) -> Result<HtmlInputElement, JsValue> {
+    let input = document
+        .create_element("input")?
+        .dyn_into::<HtmlInputElement>()?;
+    input.set_attribute("type", "number")?;
+    input.set_attribute("min", "1")?;
+    input.set_attribute("placeholder", placeholder)?;
+    input.set_attribute("title", placeholder)?;
+    input.set_attribute("style", "width: 80px;")?;
+    input.set_value(value);
+
+    Ok(input)
+}
+
+fn parse(input: &HtmlInputElement) -> Option<u32> {
+    input.value().parse().ok().filter(|v| *v > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder;
+    use image::Rgba;
+
+    // hashed pixels, about the worst case for compression
+    fn noise(w: u32, h: u32, seed: u32) -> RgbaImage {
+        RgbaImage::from_fn(w, h, |x, y| {
+            let v = (x * 7919 + y * 104_729 + seed * 15_485_863).wrapping_mul(2_654_435_761);
+            Rgba([(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, 255])
+        })
+    }
+
+    #[test]
+    fn thinning_keeps_the_duration() {
+        let images: Vec<_> = (0..5).map(|i| noise(2, 2, i)).collect();
+        let (frames, delays) = thin(&images, &[0.1, 0.2, 0.1, 0.2, 0.3], 2);
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(delays.len(), 3);
+        assert!((delays.iter().sum::<f64>() - 0.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn shrinking_runs_out() {
+        let mut settings = Settings {
+            scale: 1.0,
+            step: 1,
+            colors: None,
+        };
+        let mut steps = 0;
+        while let Some(next) = shrink(settings, 10, false, 0.0) {
+            assert_ne!(next, settings);
+            settings = next;
+            steps += 1;
+        }
+
+        assert!(steps < 30);
+        assert_eq!(settings.colors, Some(16));
+        assert_eq!(settings.step, 2);
+    }
+
+    #[test]
+    fn fits_the_budget_and_limits() {
+        let images: Vec<_> = (0..4).map(|i| noise(48, 48, i)).collect();
+        let budget = Budget {
+            bytes: 8 * 1024,
+            width: Some(32),
+            height: None,
+            frames: Some(2),
+            min_side: None,
+        };
+        let mut stages = vec![];
+        let fitted = fit(images, &[0.1; 4], None, &budget, 0, &mut |stage, _, _| {
+            stages.push(stage.to_string())
+        })
+        .unwrap();
+
+        assert!(fitted.fits);
+        assert!(fitted.apng.len() <= budget.bytes);
+        assert!(fitted.tradeoffs[0].starts_with("scaled to"));
+        assert!(fitted
+            .tradeoffs
+            .contains(&"dropped 2 of 4 frames".to_string()));
+        assert_eq!(stages.first().unwrap(), "fitting, attempt 1");
+    }
+
+    #[test]
+    fn never_scales_below_the_minimum_side() {
+        let images: Vec<_> = (0..3).map(|i| noise(32, 16, i)).collect();
+        let budget = Budget {
+            bytes: 64,
+            width: None,
+            height: None,
+            frames: None,
+            min_side: Some(24),
+        };
+        let fitted = fit(images, &[0.1; 3], None, &budget, 0, &mut |_, _, _| {}).unwrap();
+
+        // out of room, but still as large as it has to be
+        assert!(!fitted.fits);
+        let info = decoder::animation_info(&fitted.apng).unwrap();
+        assert_eq!((info.width, info.height), (24, 12));
+    }
+}
@@ -0,0 +1,46 @@
+use image::{ColorType, ImageFormat, RgbaImage};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::Clamped;
+use web_sys::ImageData;
+
+use crate::error::Error;
+
+static DATA_URL_PREFIX: &str = "data:image/png;base64,";
+
+// decode a frame stored as `data:image/png;base64,...` into rgba pixels
+pub fn decode_data_url(data: &str) -> Result<RgbaImage, Error> {
+    decode_png(&data_url_bytes(data)?)
+}
+
+// the png file inside a stored frame
+pub fn data_url_bytes(data: &str) -> Result<Vec<u8>, Error> {
+    let v = data.replace(DATA_URL_PREFIX, "");
+    Ok(base64::decode(&v)?)
+}
+
+pub fn decode_png(bytes: &[u8]) -> Result<RgbaImage, Error> {
+    let img = image::load_from_memory_with_format(bytes, ImageFormat::PNG)?;
+
+    Ok(img.to_rgba())
+}
+
+// encode rgba pixels back into the png data url format kept in `State`
+pub fn encode_data_url(img: &RgbaImage) -> Result<String, Error> {
+    let mut buf = Vec::new();
+    image::png::PNGEncoder::new(&mut buf).encode(
+        img,
+        img.width(),
+        img.height(),
+        ColorType::RGBA(8),
+    )?;
+
+    Ok(format!("{}{}", DATA_URL_PREFIX, base64::encode(&buf)))
+}
+
+pub fn image_data_to_rgba(data: &ImageData) -> Option<RgbaImage> {
+    RgbaImage::from_raw(data.width(), data.height(), data.data().0)
+}
+
+pub fn rgba_to_image_data(img: &RgbaImage) -> Result<ImageData, JsValue> {
+    ImageData::new_with_u8_clamped_array_and_sh(Clamped(&**img), img.width(), img.height())
+}
@@ -0,0 +1,540 @@
+use image::{AnimationDecoder, ImageDecoder, ImageFormat, Pixel, Rgba, RgbaImage};
+use std::fmt;
+
+use crate::encoder;
+use crate::transform;
+
+static PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+static GIF_SIGNATURE: &[u8] = b"GIF8";
+// a full size document, anything bigger is refused before its pixels are allocated
+pub static MAX_PIXELS: u64 = transform::MAX_SIDE as u64 * transform::MAX_SIDE as u64;
+// every frame of an animation together, so many small frames can't add up to
+// more than a handful of full size documents either
+pub static MAX_TOTAL_PIXELS: u64 = 16 * MAX_PIXELS;
+
+// a fully composited frame, ready to be edited like one drawn on the canvas
+pub struct DecodedFrame {
+    pub image: RgbaImage,
+    // seconds, `None` for a still png that has no timing of its own
+    pub delay: Option<f64>,
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    UnknownFormat,
+    Broken(&'static str),
+    Image(image::ImageError),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::UnknownFormat => write!(f, "only png, apng and gif can be imported"),
+            DecodeError::Broken(reason) => write!(f, "broken animation: {}", reason),
+            DecodeError::Image(err) => write!(f, "broken animation: {}", err),
+        }
+    }
+}
+
+impl From<image::ImageError> for DecodeError {
+    fn from(err: image::ImageError) -> DecodeError {
+        DecodeError::Image(err)
+    }
+}
+
+// apng (or a still png) and gif, told apart by their signature
+pub fn decode_animation(bytes: &[u8]) -> Result<Vec<DecodedFrame>, DecodeError> {
+    if bytes.starts_with(PNG_SIGNATURE) {
+        decode_apng(bytes)
+    } else if bytes.starts_with(GIF_SIGNATURE) {
+        decode_gif(bytes)
+    } else {
+        Err(DecodeError::UnknownFormat)
+    }
+}
+
+// the image crate already composites gif frames onto the logical screen
+fn decode_gif(bytes: &[u8]) -> Result<Vec<DecodedFrame>, DecodeError> {
+    let decoder = image::gif::Decoder::new(bytes)?;
+    let (width, height) = decoder.dimensions();
+    check_size(width, height)?;
+    check_total(gif_frame_count(bytes)?, width, height)?;
+    let frames = decoder.into_frames().collect_frames()?;
+
+    Ok(frames
+        .into_iter()
+        .map(|frame| {
+            let delay = frame.delay();
+            DecodedFrame {
+                delay: Some(f64::from(*delay.numer()) / f64::from(*delay.denom()) / 1000.0),
+                image: frame.into_buffer(),
+            }
+        })
+        .collect())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Dispose {
+    None,
+    Background,
+    Previous,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Blend {
+    Source,
+    Over,
+}
+
+// one fcTL and the image data that follows it
+struct FrameControl {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    delay: f64,
+    dispose: Dispose,
+    blend: Blend,
+    data: Vec<u8>,
+}
+
+impl FrameControl {
+    fn parse(data: &[u8]) -> Result<FrameControl, DecodeError> {
+        if data.len() < 26 {
+            return Err(DecodeError::Broken("short fcTL chunk"));
+        }
+        let delay_num = u16::from_be_bytes([data[20], data[21]]);
+        // a zero denominator means hundredths of a second
+        let delay_den = match u16::from_be_bytes([data[22], data[23]]) {
+            0 => 100,
+            den => den,
+        };
+
+        Ok(FrameControl {
+            width: read_u32(&data[4..]),
+            height: read_u32(&data[8..]),
+            x: read_u32(&data[12..]),
+            y: read_u32(&data[16..]),
+            delay: f64::from(delay_num) / f64::from(delay_den),
+            dispose: match data[24] {
+                1 => Dispose::Background,
+                2 => Dispose::Previous,
+                _ => Dispose::None,
+            },
+            blend: match data[25] {
+                1 => Blend::Over,
+                _ => Blend::Source,
+            },
+            data: vec![],
+        })
+    }
+}
+
+// every frame is cut out into a standalone png for the image crate to decode,
+// then drawn onto the canvas following the blend and dispose ops of its fcTL
+fn decode_apng(bytes: &[u8]) -> Result<Vec<DecodedFrame>, DecodeError> {
+    let mut ihdr = None;
+    // chunks like PLTE and tRNS that every frame shares
+    let mut shared = vec![];
+    let mut default_image = vec![];
+    let mut frames: Vec<FrameControl> = vec![];
+    let mut seen_idat = false;
+
+    for chunk in chunks(bytes) {
+        let (c_type, data) = chunk?;
+        match c_type {
+            b"IHDR" => ihdr = Some(data.to_vec()),
+            b"acTL" => {}
+            b"fcTL" => frames.push(FrameControl::parse(data)?),
+            b"IDAT" => {
+                seen_idat = true;
+                default_image.extend_from_slice(data);
+                // the default image is only a frame when a fcTL came before it
+                if let Some(frame) = frames.last_mut() {
+                    frame.data.extend_from_slice(data);
+                }
+            }
+            b"fdAT" => {
+                let frame = frames
+                    .last_mut()
+                    .ok_or(DecodeError::Broken("fdAT before fcTL"))?;
+                frame
+                    .data
+                    .extend_from_slice(data.get(4..).unwrap_or_default());
+            }
+            b"IEND" => break,
+            _ if !seen_idat => shared.push((c_type.to_vec(), data.to_vec())),
+            _ => {}
+        }
+    }
+
+    let ihdr = ihdr.ok_or(DecodeError::Broken("missing IHDR chunk"))?;
+    if ihdr.len() < 13 {
+        return Err(DecodeError::Broken("short IHDR chunk"));
+    }
+    let (width, height) = (read_u32(&ihdr), read_u32(&ihdr[4..]));
+    check_size(u64::from(width), u64::from(height))?;
+
+    // a plain png is a single frame
+    if frames.is_empty() {
+        let image = image::load_from_memory_with_format(bytes, ImageFormat::PNG)?.to_rgba();
+        return Ok(vec![DecodedFrame { image, delay: None }]);
+    }
+    if default_image.is_empty() {
+        return Err(DecodeError::Broken("missing IDAT chunk"));
+    }
+    check_total(frames.len(), u64::from(width), u64::from(height))?;
+
+    let mut canvas = RgbaImage::new(width, height);
+    let mut decoded = vec![];
+    for (i, frame) in frames.iter().enumerate() {
+        if frame.x.saturating_add(frame.width) > width
+            || frame.y.saturating_add(frame.height) > height
+        {
+            return Err(DecodeError::Broken("frame outside of the image"));
+        }
+        let png = standalone_png(&ihdr, &shared, frame)
+            .map_err(|_| DecodeError::Broken("could not rebuild frame"))?;
+        let image = image::load_from_memory_with_format(&png, ImageFormat::PNG)?.to_rgba();
+
+        let previous = canvas.clone();
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let target = canvas.get_pixel_mut(frame.x + x, frame.y + y);
+            match frame.blend {
+                Blend::Source => *target = *pixel,
+                Blend::Over => target.blend(pixel),
+            }
+        }
+        decoded.push(DecodedFrame {
+            image: canvas.clone(),
+            delay: Some(frame.delay),
+        });
+
+        // the first frame has nothing to go back to, so previous acts like background
+        let dispose = match frame.dispose {
+            Dispose::Previous if i == 0 => Dispose::Background,
+            dispose => dispose,
+        };
+        match dispose {
+            Dispose::None => {}
+            Dispose::Background => {
+                for y in frame.y..frame.y + frame.height {
+                    for x in frame.x..frame.x + frame.width {
+                        canvas.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                    }
+                }
+            }
+            Dispose::Previous => canvas = previous,
+        }
+    }
+
+    Ok(decoded)
+}
+
+fn check_size(width: u64, height: u64) -> Result<(), DecodeError> {
+    if width.saturating_mul(height) > MAX_PIXELS {
+        return Err(DecodeError::Broken("image too large"));
+    }
+    Ok(())
+}
+
+fn check_total(frames: usize, width: u64, height: u64) -> Result<(), DecodeError> {
+    let total = (frames as u64).saturating_mul(width).saturating_mul(height);
+    if total > MAX_TOTAL_PIXELS {
+        return Err(DecodeError::Broken("animation too large"));
+    }
+    Ok(())
+}
+
+// walks the blocks of a gif to count its images without decompressing any
+fn gif_frame_count(bytes: &[u8]) -> Result<usize, DecodeError> {
+    // the header and logical screen descriptor, then the global color table
+    let flags = *bytes.get(10).ok_or(DecodeError::Broken("truncated gif"))?;
+    let mut pos = 13;
+    if flags & 0x80 != 0 {
+        pos += 3 << ((flags & 0x07) + 1);
+    }
+
+    let mut frames = 0;
+    loop {
+        match bytes.get(pos) {
+            // extension: a label, then sub-blocks
+            Some(0x21) => pos += 2,
+            // image descriptor, its local color table and the lzw code size
+            Some(0x2c) => {
+                let flags = *bytes
+                    .get(pos + 9)
+                    .ok_or(DecodeError::Broken("truncated gif"))?;
+                pos += 10;
+                if flags & 0x80 != 0 {
+                    pos += 3 << ((flags & 0x07) + 1);
+                }
+                pos += 1;
+                frames += 1;
+            }
+            Some(0x3b) => return Ok(frames),
+            Some(_) => return Err(DecodeError::Broken("unknown gif block")),
+            // some encoders leave out the trailer
+            None => return Ok(frames),
+        }
+        // sub-blocks, each led by its length, up to an empty one
+        loop {
+            let len = *bytes.get(pos).ok_or(DecodeError::Broken("truncated gif"))? as usize;
+            pos += 1 + len;
+            if len == 0 {
+                break;
+            }
+        }
+    }
+}
+
+// the size, frame count and length of a png or apng, read from its chunks
+// without decoding any pixels
+#[derive(Debug, PartialEq)]
+pub struct AnimationInfo {
+    pub width: u32,
+    pub height: u32,
+    pub frames: usize,
+    // seconds
+    pub duration: f64,
+    // 0 loops forever, as does a plain png
+    pub plays: u32,
+}
+
+pub fn animation_info(bytes: &[u8]) -> Result<AnimationInfo, DecodeError> {
+    if !bytes.starts_with(PNG_SIGNATURE) {
+        return Err(DecodeError::UnknownFormat);
+    }
+    let mut size = None;
+    let mut frames = 0;
+    let mut duration = 0.0;
+    let mut plays = 0;
+    for chunk in chunks(bytes) {
+        let (c_type, data) = chunk?;
+        match c_type {
+            b"IHDR" if data.len() >= 13 => size = Some((read_u32(data), read_u32(&data[4..]))),
+            b"acTL" if data.len() >= 8 => plays = read_u32(&data[4..]),
+            b"fcTL" => {
+                frames += 1;
+                duration += FrameControl::parse(data)?.delay;
+            }
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+    let (width, height) = size.ok_or(DecodeError::Broken("missing IHDR chunk"))?;
+
+    Ok(AnimationInfo {
+        width,
+        height,
+        // a plain png is a single frame
+        frames: frames.max(1),
+        duration,
+        plays,
+    })
+}
+
+// the type and data of every chunk after the signature
+fn chunks(bytes: &[u8]) -> impl Iterator<Item = Result<(&[u8], &[u8]), DecodeError>> {
+    let mut pos = PNG_SIGNATURE.len();
+    std::iter::from_fn(move || {
+        if pos >= bytes.len() || bytes.len() - pos < 8 {
+            return None;
+        }
+        let len = read_u32(&bytes[pos..]) as usize;
+        let c_type = &bytes[pos + 4..pos + 8];
+        let end = pos.checked_add(8).and_then(|p| p.checked_add(len));
+        let chunk = match end.and_then(|end| bytes.get(pos + 8..end)) {
+            Some(data) => Ok((c_type, data)),
+            None => {
+                // nothing after a truncated chunk can be trusted
+                pos = bytes.len();
+                return Some(Err(DecodeError::Broken("truncated chunk")));
+            }
+        };
+        // the crc follows the data
+        pos = end.map_or(bytes.len(), |end| end.saturating_add(4));
+        Some(chunk)
+    })
+}
+
+fn standalone_png(
+    ihdr: &[u8],
+    shared: &[(Vec<u8>, Vec<u8>)],
+    frame: &FrameControl,
+) -> std::io::Result<Vec<u8>> {
+    let mut buf = PNG_SIGNATURE.to_vec();
+
+    let mut header = ihdr.to_vec();
+    header[..4].copy_from_slice(&frame.width.to_be_bytes());
+    header[4..8].copy_from_slice(&frame.height.to_be_bytes());
+    encoder::write_chunk(&mut buf, b"IHDR", &header)?;
+
+    for (c_type, data) in shared {
+        let mut name = [0; 4];
+        name.copy_from_slice(c_type);
+        encoder::write_chunk(&mut buf, &name, data)?;
+    }
+    encoder::write_chunk(&mut buf, b"IDAT", &frame.data)?;
+    encoder::write_chunk(&mut buf, b"IEND", &[])?;
+
+    Ok(buf)
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_be_bytes([data[0], data[1], data[2], data[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::{encode_apng, FrameData};
+    use apng::{BlendOp, DisposeOp};
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> Vec<u8> {
+        color
+            .iter()
+            .cycle()
+            .take((width * height * 4) as usize)
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn composites_sub_frames() {
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+        let frames = vec![
+            FrameData::full(4, 4, solid(4, 4, red), 1, 10),
+            FrameData {
+                x: 1,
+                y: 1,
+                width: 2,
+                height: 2,
+                data: solid(2, 2, blue),
+                delay_num: 50,
+                delay_den: 0,
+                dispose_op: DisposeOp::ApngDisposeOpBackground,
+                blend_op: BlendOp::ApngBlendOpOver,
+            },
+            FrameData {
+                x: 0,
+                y: 0,
+                width: 1,
+                height: 1,
+                data: solid(1, 1, [0, 0, 0, 0]),
+                delay_num: 1,
+                delay_den: 4,
+                dispose_op: DisposeOp::ApngDisposeOpNone,
+                blend_op: BlendOp::ApngBlendOpOver,
+            },
+        ];
+        let bytes = encode_apng(4, 4, None, &frames, 0).unwrap();
+        let decoded = decode_animation(&bytes).unwrap();
+
+        let delays: Vec<Option<f64>> = decoded.iter().map(|f| f.delay).collect();
+        assert_eq!(delays, vec![Some(0.1), Some(0.5), Some(0.25)]);
+        assert_eq!(decoded[0].image.get_pixel(1, 1).0, red);
+        assert_eq!(decoded[1].image.get_pixel(1, 1).0, blue);
+        assert_eq!(decoded[1].image.get_pixel(0, 0).0, red);
+        // the blue square was disposed to transparent before the last frame
+        assert_eq!(decoded[2].image.get_pixel(1, 1).0, [0, 0, 0, 0]);
+        assert_eq!(decoded[2].image.get_pixel(0, 0).0, red);
+    }
+
+    #[test]
+    fn still_png_is_one_frame() {
+        let bytes = encoder::encode_png(2, 2, None, &solid(2, 2, [1, 2, 3, 255])).unwrap();
+        let decoded = decode_animation(&bytes).unwrap();
+
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].image.get_pixel(1, 1).0, [1, 2, 3, 255]);
+        // follows the frame speed of the document
+        assert_eq!(decoded[0].delay, None);
+    }
+
+    #[test]
+    fn rejects_huge_images_before_decoding() {
+        let mut bytes = encoder::encode_png(2, 2, None, &solid(2, 2, [0, 0, 0, 255])).unwrap();
+        // IHDR data starts after the signature, the chunk length and its type
+        bytes[16..20].copy_from_slice(&100_000u32.to_be_bytes());
+        bytes[20..24].copy_from_slice(&100_000u32.to_be_bytes());
+        assert!(matches!(
+            decode_animation(&bytes),
+            Err(DecodeError::Broken("image too large"))
+        ));
+    }
+
+    #[test]
+    fn rejects_long_animations_before_decoding() {
+        let frames: Vec<FrameData> = (0..17)
+            .map(|_| FrameData::full(2, 2, solid(2, 2, [0, 0, 0, 255]), 1, 10))
+            .collect();
+        let mut bytes = encode_apng(2, 2, None, &frames, 0).unwrap();
+        // each frame is within bounds, all of them together are not
+        bytes[16..20].copy_from_slice(&transform::MAX_SIDE.to_be_bytes());
+        bytes[20..24].copy_from_slice(&transform::MAX_SIDE.to_be_bytes());
+        assert!(matches!(
+            decode_animation(&bytes),
+            Err(DecodeError::Broken("animation too large"))
+        ));
+    }
+
+    #[test]
+    fn rejects_overlong_chunks() {
+        let mut bytes = encoder::encode_png(2, 2, None, &solid(2, 2, [0, 0, 0, 255])).unwrap();
+        bytes[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(
+            decode_animation(&bytes),
+            Err(DecodeError::Broken("truncated chunk"))
+        ));
+    }
+
+    #[test]
+    fn counts_gif_frames() {
+        let mut bytes = vec![];
+        {
+            let mut encoder = image::gif::Encoder::new(&mut bytes);
+            for color in &[[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]] {
+                let image = RgbaImage::from_pixel(3, 2, Rgba(*color));
+                encoder
+                    .encode_frames(vec![image::Frame::new(image)])
+                    .unwrap();
+            }
+        }
+        assert_eq!(gif_frame_count(&bytes).unwrap(), 3);
+        assert_eq!(decode_animation(&bytes).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn reads_info_without_decoding() {
+        let frames = vec![
+            FrameData::full(3, 2, solid(3, 2, [255, 0, 0, 255]), 1, 10),
+            FrameData::full(3, 2, solid(3, 2, [0, 255, 0, 255]), 1, 4),
+        ];
+        let bytes = encode_apng(3, 2, None, &frames, 2).unwrap();
+        let info = animation_info(&bytes).unwrap();
+        assert_eq!(
+            info,
+            AnimationInfo {
+                width: 3,
+                height: 2,
+                frames: 2,
+                duration: 0.35,
+                plays: 2,
+            }
+        );
+
+        let still = encoder::encode_png(5, 4, None, &solid(5, 4, [0, 0, 0, 255])).unwrap();
+        let info = animation_info(&still).unwrap();
+        assert_eq!((info.width, info.height, info.frames), (5, 4, 1));
+    }
+
+    #[test]
+    fn rejects_unknown_format() {
+        assert!(matches!(
+            decode_animation(b"not an image"),
+            Err(DecodeError::UnknownFormat)
+        ));
+    }
+}
@@ -6,45 +6,139 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, MouseEvent};
 
-use crate::state::State;
+use crate::error::Error;
+use crate::notify;
+use crate::overlay::Overlay;
+use crate::state::{State, Tool};
+use crate::stroke::Stroke;
+use crate::utils;
+
+// the biggest cursor image browsers agree to show
+static MAX_CURSOR: f64 = 128.0;
+
+// the brush outline as large as it draws on screen, a ring or a square for pixel art.
+// it is traced in white under black so it shows on any pixels
+pub fn brush_cursor(size: f64, square: bool) -> String {
+    let size = size.max(3.0);
+    let side = (size + 4.0).ceil();
+    if side > MAX_CURSOR {
+        return "crosshair".to_string();
+    }
+    let center = side / 2.0;
+    let outline = |color: &str, width: u32| {
+        if square {
+            format!(
+                r#"<rect x="{0}" y="{0}" width="{1}" height="{1}" fill="none" stroke="{2}" stroke-width="{3}"/>"#,
+                center - size / 2.0,
+                size,
+                color,
+                width
+            )
+        } else {
+            format!(
+                r#"<circle cx="{0}" cy="{0}" r="{1}" fill="none" stroke="{2}" stroke-width="{3}"/>"#,
+                center,
+                size / 2.0,
+                color,
+                width
+            )
+        }
+    };
+    let svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{0}">{1}{2}</svg>"#,
+        side,
+        outline("#ffffff", 3),
+        outline("#000000", 1)
+    );
+
+    format!(
+        "url(\"data:image/svg+xml;base64,{}\") {} {}, crosshair",
+        base64::encode(&svg),
+        center as u32,
+        center as u32
+    )
+}
 
 // setup mouse event listener for drawing and start
 pub fn canvas_draw_start(
     canvas: &HtmlCanvasElement,
+    overlay: &Overlay,
     state: &Rc<RefCell<State>>,
 ) -> Result<(), JsValue> {
-    let context = canvas
-        .get_context("2d")
-        .expect("Could not get context")
-        .unwrap()
-        .dyn_into::<CanvasRenderingContext2d>()
-        .unwrap();
+    let context = utils::get_context(canvas)?;
     let pressed = Rc::new(Cell::new(false));
+    let last = Rc::new(Cell::new((0.0, 0.0)));
 
     // mousedown
     {
         let context = context.clone();
+        let overlay = overlay.clone();
         let state = state.clone();
         let pressed = pressed.clone();
+        let last = last.clone();
 
         let mouse_down = Closure::wrap(Box::new(move |event: MouseEvent| {
+            let (new_x, new_y) = position(&state, &event);
+
+            // alt+click moves the symmetry center instead of drawing
+            if event.alt_key() {
+                state.borrow_mut().set_symmetry_center(Some((new_x, new_y)));
+                overlay.render(&state);
+                return;
+            }
+
+            let image_data = match context.get_image_data(
+                0.0,
+                0.0,
+                state.borrow().get_width() as f64,
+                state.borrow().get_height() as f64,
+            ) {
+                Ok(image_data) => image_data,
+                Err(err) => {
+                    // without an undo snapshot the stroke is not started
+                    notify::error("draw", &Error::from(err));
+                    return;
+                }
+            };
             pressed.set(true);
-            let image_data = context
-                .get_image_data(
-                    0.0,
-                    0.0,
-                    state.borrow().get_width() as f64,
-                    state.borrow().get_height() as f64,
-                )
-                .unwrap();
             state.borrow_mut().add_undo(image_data);
 
-            let new_x = event.offset_x() as f64;
-            let new_y = event.offset_y() as f64;
-            context.begin_path();
-            context.set_stroke_style(&JsValue::from(state.borrow().get_color()));
+            // one recorded stroke per symmetric copy
+            let tool = state.borrow().get_tool();
+            let erase = tool == Tool::Eraser;
+            let strokes = {
+                let state = state.borrow();
+                let copies = state
+                    .get_symmetry()
+                    .points(state.get_symmetry_center(), (new_x, new_y));
+                copies
+                    .into_iter()
+                    .map(|p| {
+                        Stroke::new(
+                            p,
+                            &state.get_color(),
+                            state.get_pen_thin(),
+                            erase,
+                            state.is_pixel_art(),
+                        )
+                    })
+                    .collect()
+            };
+            state.borrow_mut().add_strokes(strokes);
+
+            notify::report(
+                "draw",
+                context.set_global_composite_operation(tool.composite_operation()),
+            );
+            context.set_stroke_style_str(&state.borrow().get_color());
             context.set_line_width(state.borrow().get_pen_thin());
-            context.move_to(new_x, new_y);
+            context.set_line_cap("round");
+            context.set_line_join("round");
+            context.set_fill_style_str(&state.borrow().get_color());
+            last.set((new_x, new_y));
+            if state.borrow().is_pixel_art() {
+                stroke_segment(&context, &state, (new_x, new_y), (new_x, new_y));
+            }
         }) as Box<dyn FnMut(_)>);
 
         canvas
@@ -56,15 +150,25 @@ pub fn canvas_draw_start(
     // mouseup
     {
         let context = context.clone();
+        let state = state.clone();
         let pressed = pressed.clone();
+        let last = last.clone();
 
         let mouse_up = Closure::wrap(Box::new(move |event: MouseEvent| {
+            if !pressed.get() {
+                return;
+            }
             pressed.set(false);
-            let new_x = event.offset_x() as f64;
-            let new_y = event.offset_y() as f64;
-            context.fill_rect(new_x, new_y, 1.0, 1.0);
-            context.line_to(new_x, new_y);
-            context.stroke();
+            let (new_x, new_y) = position(&state, &event);
+            stroke_segment(&context, &state, last.get(), (new_x, new_y));
+            // images placed on the canvas later must not be erased
+            notify::report(
+                "draw",
+                context.set_global_composite_operation(Tool::Pen.composite_operation()),
+            );
+
+            let hooks = state.borrow().get_hooks();
+            hooks.changed();
         }) as Box<dyn FnMut(_)>);
 
         canvas.add_event_listener_with_callback("mouseup", mouse_up.as_ref().unchecked_ref())?;
@@ -75,14 +179,15 @@ pub fn canvas_draw_start(
     // mousemove
     {
         let context = context.clone();
+        let state = state.clone();
         let pressed = pressed.clone();
+        let last = last.clone();
 
         let mouse_move = Closure::wrap(Box::new(move |event: MouseEvent| {
             if pressed.get() {
-                let new_x = event.offset_x() as f64;
-                let new_y = event.offset_y() as f64;
-                context.line_to(new_x, new_y);
-                context.stroke();
+                let (new_x, new_y) = position(&state, &event);
+                stroke_segment(&context, &state, last.get(), (new_x, new_y));
+                last.set((new_x, new_y));
             }
         }) as Box<dyn FnMut(_)>);
 
@@ -94,3 +199,92 @@ pub fn canvas_draw_start(
 
     Ok(())
 }
+
+// draw one segment of the stroke together with its symmetric copies
+fn stroke_segment(
+    context: &CanvasRenderingContext2d,
+    state: &Rc<RefCell<State>>,
+    from: (f64, f64),
+    to: (f64, f64),
+) {
+    let symmetry = state.borrow().get_symmetry();
+    let center = state.borrow().get_symmetry_center();
+    let starts = symmetry.points(center, from);
+    let ends = symmetry.points(center, to);
+    state.borrow_mut().extend_strokes(&ends);
+
+    if state.borrow().is_pixel_art() {
+        let size = state.borrow().get_pen_thin().round().max(1.0);
+        for (s, e) in starts.iter().zip(ends.iter()) {
+            plot_pixel_line(context, size, *s, *e);
+        }
+        return;
+    }
+
+    context.begin_path();
+    for (s, e) in starts.iter().zip(ends.iter()) {
+        context.move_to(s.0, s.1);
+        context.line_to(e.0, e.1);
+    }
+    context.stroke();
+}
+
+// mouse position in document pixels, the canvas may be shown zoomed.
+// pulled onto guides, and onto the grid while its snapping is on
+fn position(state: &Rc<RefCell<State>>, event: &MouseEvent) -> (f64, f64) {
+    let state = state.borrow();
+    let zoom = state.get_zoom() as f64;
+    state.snap_point((
+        event.offset_x() as f64 / zoom,
+        event.offset_y() as f64 / zoom,
+    ))
+}
+
+// aliased line of square dots without any anti-aliasing (bresenham)
+fn plot_pixel_line(
+    context: &CanvasRenderingContext2d,
+    size: f64,
+    from: (f64, f64),
+    to: (f64, f64),
+) {
+    let (mut x0, mut y0) = (from.0.floor() as i64, from.1.floor() as i64);
+    let (x1, y1) = (to.0.floor() as i64, to.1.floor() as i64);
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let sy = if y0 < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    let offset = (size / 2.0).floor();
+
+    loop {
+        context.fill_rect(x0 as f64 - offset, y0 as f64 - offset, size, size);
+        if x0 == x1 && y0 == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x0 += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y0 += sy;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_is_centered_on_the_brush() {
+        let cursor = brush_cursor(16.0, false);
+        assert!(cursor.starts_with("url(\"data:image/svg+xml;base64,"));
+        assert!(cursor.ends_with("\") 10 10, crosshair"));
+
+        // tiny brushes still get a visible outline
+        assert!(brush_cursor(1.0, true).ends_with("\") 3 3, crosshair"));
+        assert_eq!(brush_cursor(200.0, false), "crosshair");
+    }
+}
@@ -0,0 +1,471 @@
+use js_sys::{Function, Object, Reflect, Uint8Array};
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{window, Document, Element, HtmlCanvasElement, Node};
+
+use crate::actions::Actions;
+use crate::autosave;
+use crate::budget;
+use crate::draw;
+use crate::error::Error;
+use crate::generate::{self, Job};
+use crate::hooks::Hooks;
+use crate::icons::Icons;
+use crate::import;
+use crate::keyboard;
+use crate::overlay::{self, Overlay};
+use crate::presets;
+use crate::project::Project;
+use crate::resize;
+use crate::ruler;
+use crate::save;
+use crate::sequence;
+use crate::spritesheet;
+use crate::state::State;
+use crate::svg;
+use crate::toolbar;
+use crate::worker;
+
+static TOOLBAR_HEIGHT: u32 = 50;
+// the session the standalone app restores on reload
+static STANDALONE_AUTOSAVE: &str = "autosave";
+
+thread_local! {
+    // the active flag of every mount so far, a new one takes over from the others
+    static MOUNTS: RefCell<Vec<Weak<Cell<bool>>>> = const { RefCell::new(vec![]) };
+}
+
+// the elements one editor is built into
+#[derive(Clone)]
+pub struct Mount {
+    pub root: Element,
+    pub toolbar: Element,
+    pub preview_toolbar: Element,
+    pub preview: Element,
+    pub canvas: HtmlCanvasElement,
+    pub generate: Element,
+    // with several editors on a page, shortcuts and pastes go to the one last clicked,
+    // or to the newest one until anything is clicked
+    active: Rc<Cell<bool>>,
+}
+
+impl Mount {
+    // the layout of app/index.html
+    pub fn from_document(document: &Document) -> Result<Mount, JsValue> {
+        let by_id = |id: &str| {
+            document
+                .get_element_by_id(id)
+                .ok_or_else(|| JsValue::from_str(&format!("Could not find `#{}`", id)))
+        };
+        let root = document
+            .body()
+            .expect("Could not find `body` element")
+            .into();
+
+        Mount::new(
+            root,
+            by_id("toolbar")?,
+            by_id("preview-toolbar")?,
+            by_id("preview")?,
+            by_id("draw")?.dyn_into::<HtmlCanvasElement>()?,
+            by_id("generate")?,
+        )
+    }
+
+    // the same layout as app/index.html, built inside `root` with classes instead of ids
+    pub fn build(document: &Document, root: &Element) -> Result<Mount, JsValue> {
+        let create = |parent: &Element, tag: &str, class: &str| -> Result<Element, JsValue> {
+            let element = document.create_element(tag)?;
+            element.set_attribute("class", class)?;
+            parent.append_child(&element)?;
+            Ok(element)
+        };
+
+        let app = create(root, "div", "app")?;
+        let toolbars = create(&app, "div", "drawasm-toolbars")?;
+        let toolbar = create(&toolbars, "div", "drawasm-toolbar")?;
+        let preview_toolbar = create(&toolbars, "div", "drawasm-preview-toolbar")?;
+        let main = create(&app, "div", "app-main")?;
+        let canvas = create(&main, "canvas", "drawasm-draw")?.dyn_into::<HtmlCanvasElement>()?;
+        let sub = create(&main, "div", "sub-main")?;
+        let preview = create(&sub, "div", "drawasm-preview")?;
+        let generate = create(&sub, "div", "drawasm-generate")?;
+
+        Mount::new(
+            root.clone(),
+            toolbar,
+            preview_toolbar,
+            preview,
+            canvas,
+            generate,
+        )
+    }
+
+    fn new(
+        root: Element,
+        toolbar: Element,
+        preview_toolbar: Element,
+        preview: Element,
+        canvas: HtmlCanvasElement,
+        generate: Element,
+    ) -> Result<Mount, JsValue> {
+        let active = Rc::new(Cell::new(true));
+        MOUNTS.with(|mounts| {
+            let mut mounts = mounts.borrow_mut();
+            mounts.retain(|other| match other.upgrade() {
+                Some(other) => {
+                    other.set(false);
+                    true
+                }
+                None => false,
+            });
+            mounts.push(Rc::downgrade(&active));
+        });
+        {
+            let root = root.clone();
+            let active = active.clone();
+            let handle_mousedown = Closure::wrap(Box::new(move |event: web_sys::Event| {
+                let target = event.target().and_then(|t| t.dyn_into::<Node>().ok());
+                active.set(root.contains(target.as_ref()));
+            }) as Box<dyn FnMut(_)>);
+            window()
+                .unwrap()
+                .add_event_listener_with_callback_and_bool(
+                    "mousedown",
+                    handle_mousedown.as_ref().unchecked_ref(),
+                    true,
+                )?;
+            handle_mousedown.forget();
+        }
+
+        Ok(Mount {
+            root,
+            toolbar,
+            preview_toolbar,
+            preview,
+            canvas,
+            generate,
+            active,
+        })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.get()
+    }
+}
+
+pub struct Options {
+    // document size, worked out from the room around the canvas when not given
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    // names from `toolbar::TOOLS`, `None` shows them all
+    pub tools: Option<Vec<String>>,
+    // where the session is kept between visits, `None` turns autosave off
+    pub autosave: Option<String>,
+    // where the encoder worker script is served from
+    pub worker_url: String,
+    pub icons: Icons,
+    pub hooks: Hooks,
+}
+
+impl Options {
+    pub fn standalone() -> Options {
+        Options {
+            width: None,
+            height: None,
+            tools: None,
+            autosave: Some(STANDALONE_AUTOSAVE.to_string()),
+            worker_url: worker::WORKER_URL.to_string(),
+            icons: Icons::default(),
+            hooks: Hooks::default(),
+        }
+    }
+
+    // `{ width, height, tools, icons, autosave, workerUrl, onChange, onFrames, onExport }`,
+    // every field optional
+    fn from_config(config: &JsValue) -> Result<Options, JsValue> {
+        let get = |key: &str| Reflect::get(config, &JsValue::from_str(key));
+        let size = |key: &str| -> Result<Option<u32>, JsValue> {
+            Ok(get(key)?.as_f64().filter(|v| *v >= 1.0).map(|v| v as u32))
+        };
+
+        let tools = get("tools")?;
+        let tools = if tools.is_undefined() || tools.is_null() {
+            None
+        } else {
+            let tools: Vec<String> = js_sys::Array::from(&tools)
+                .iter()
+                .filter_map(|tool| tool.as_string())
+                .collect();
+            if let Some(unknown) = tools.iter().find(|t| !toolbar::TOOLS.contains(&t.as_str())) {
+                return Err(Error::Input(format!(
+                    "unknown tool `{}`, the tools are {}",
+                    unknown,
+                    toolbar::TOOLS.join(", ")
+                ))
+                .into());
+            }
+            Some(tools)
+        };
+
+        // `{ pen: "<svg ...>", undo: "/icons/undo.png" }`, names from `icons::ICONS`
+        let mut icons = Icons::default();
+        let overrides = get("icons")?;
+        if overrides.is_object() {
+            for entry in Object::entries(overrides.unchecked_ref()).iter() {
+                let entry = js_sys::Array::from(&entry);
+                if let (Some(name), Some(icon)) =
+                    (entry.get(0).as_string(), entry.get(1).as_string())
+                {
+                    icons.set(&name, icon)?;
+                }
+            }
+        }
+
+        let hooks = Hooks::default();
+        for (key, event) in [
+            ("onChange", "change"),
+            ("onFrames", "frames"),
+            ("onExport", "export"),
+        ] {
+            if let Ok(listener) = get(key)?.dyn_into::<Function>() {
+                hooks.on(event, listener)?;
+            }
+        }
+
+        Ok(Options {
+            width: size("width")?,
+            height: size("height")?,
+            tools,
+            autosave: get("autosave")?.as_string(),
+            worker_url: get("workerUrl")?
+                .as_string()
+                .unwrap_or_else(|| worker::WORKER_URL.to_string()),
+            icons,
+            hooks,
+        })
+    }
+}
+
+// an editor on a page of the host's choosing.
+// `new Editor({ root })` builds the whole layout inside `root`, or pass `toolbar`,
+// `previewToolbar`, `preview`, `canvas` and `generate` to use existing elements
+#[wasm_bindgen]
+pub struct Editor {
+    overlay: Overlay,
+    actions: Actions,
+    state: Rc<RefCell<State>>,
+}
+
+#[wasm_bindgen]
+impl Editor {
+    #[wasm_bindgen(constructor)]
+    pub fn new(config: JsValue) -> Result<Editor, JsValue> {
+        let document = window()
+            .unwrap()
+            .document()
+            .expect("Could not find `document`");
+
+        let element = |key: &str| -> Result<Option<Element>, JsValue> {
+            Ok(Reflect::get(&config, &JsValue::from_str(key))?
+                .dyn_into::<Element>()
+                .ok())
+        };
+        let mount = match element("root")? {
+            Some(root) => Mount::build(&document, &root)?,
+            None => {
+                let required = |key: &str| {
+                    element(key)?.ok_or_else(|| {
+                        JsValue::from(Error::Input(format!(
+                            "Editor needs a `root` element or a `{}` element",
+                            key
+                        )))
+                    })
+                };
+                let canvas = required("canvas")?.dyn_into::<HtmlCanvasElement>()?;
+                let root = canvas
+                    .parent_element()
+                    .unwrap_or_else(|| document.body().unwrap().into());
+                Mount::new(
+                    root,
+                    required("toolbar")?,
+                    required("previewToolbar")?,
+                    required("preview")?,
+                    canvas,
+                    required("generate")?,
+                )?
+            }
+        };
+
+        start(&document, &mount, Options::from_config(&config)?)
+    }
+
+    // `event` is one of `hooks::EVENTS`, listeners are called after the change, never during it
+    pub fn on(&self, event: &str, listener: Function) -> Result<(), JsValue> {
+        let hooks = self.state.borrow().get_hooks();
+        Ok(hooks.on(event, listener)?)
+    }
+
+    pub fn off(&self, event: &str, listener: &Function) {
+        let hooks = self.state.borrow().get_hooks();
+        hooks.off(event, listener);
+    }
+
+    #[wasm_bindgen(js_name = addFrame)]
+    pub fn add_frame(&self) -> Result<(), JsValue> {
+        self.actions.add_frame()
+    }
+
+    #[wasm_bindgen(js_name = selectFrame)]
+    pub fn select_frame(&self, index: usize) -> Result<(), JsValue> {
+        self.actions.select_frame(index)
+    }
+
+    #[wasm_bindgen(js_name = clearFrames)]
+    pub fn clear_frames(&self) -> Result<(), JsValue> {
+        self.actions.clear_frames()
+    }
+
+    #[wasm_bindgen(js_name = frameCount)]
+    pub fn frame_count(&self) -> usize {
+        self.state.borrow().get_preview_image_len()
+    }
+
+    pub fn undo(&self) -> Result<(), JsValue> {
+        self.actions.undo()
+    }
+
+    pub fn redo(&self) -> Result<(), JsValue> {
+        self.actions.redo()
+    }
+
+    pub fn clear(&self) -> Result<(), JsValue> {
+        self.actions.clear()
+    }
+
+    // the frames as an optimized apng, encoded right away on the calling thread
+    #[wasm_bindgen(js_name = exportApng)]
+    pub fn export_apng(&self) -> Result<Uint8Array, JsValue> {
+        let (images, job) = {
+            let state = self.state.borrow();
+            if state.get_preview_image_len() == 0 {
+                return Err(Error::NoFrames.into());
+            }
+            (
+                generate::decode_frames(&state.get_preview_image())?,
+                Job::from_state(&state, true, None, false),
+            )
+        };
+        let (apng, _) = generate::generate(images, &job, &mut |_, _, _| {})?;
+
+        let state = self.state.borrow();
+        let file_name = format!("{}.png", save::export_stem(&state));
+        state
+            .get_hooks()
+            .exported(&generate::apng_export(&apng, file_name, &state));
+        Ok(Uint8Array::from(apng.as_slice()))
+    }
+
+    // the project file `saveProject` made, replacing the document
+    #[wasm_bindgen(js_name = loadProject)]
+    pub fn load_project(&self, json: &str) -> Result<(), JsValue> {
+        let project = Project::from_json(json).map_err(Error::from)?;
+        save::load_project(
+            project,
+            &self.actions.mount().canvas,
+            &self.overlay,
+            &self.actions,
+            &self.state,
+        )
+    }
+
+    #[wasm_bindgen(js_name = saveProject)]
+    pub fn save_project(&self) -> Result<String, JsValue> {
+        Ok(Project::from_state(&self.state.borrow())
+            .to_json()
+            .map_err(Error::from)?)
+    }
+}
+
+// lay out the canvas and build every tool and panel into `mount`
+pub fn start(document: &Document, mount: &Mount, options: Options) -> Result<Editor, JsValue> {
+    let canvas = &mount.canvas;
+    let (canvas_w, canvas_h) = match (options.width, options.height) {
+        (Some(w), Some(h)) => (w, h),
+        (w, h) => {
+            let (root_w, root_h) = get_root_dimensions(&mount.root);
+            let (pre_w, _) = get_el_dimensions(&mount.preview);
+            // leave room for the rulers around the canvas
+            (
+                w.unwrap_or(root_w - (pre_w + 5 + overlay::RULER_SIZE)),
+                h.unwrap_or(root_h - (TOOLBAR_HEIGHT + 100 + 5 + overlay::RULER_SIZE)),
+            )
+        }
+    };
+
+    canvas.set_width(canvas_w);
+    canvas.set_height(canvas_h);
+
+    let (pre_w, pre_h) = get_el_dimensions(&mount.preview);
+    mount.preview.set_attribute(
+        "style",
+        format!("width: {}px; height: {}px;", pre_w, pre_h).as_str(),
+    )?;
+
+    mount
+        .toolbar
+        .set_attribute("style", format!("width: {}px;", canvas_w + 5).as_str())?;
+
+    let state = Rc::new(RefCell::new(State::new(canvas_w, canvas_h)));
+    state.borrow_mut().set_hooks(options.hooks);
+
+    let overlay = Overlay::new(document, canvas)?;
+    overlay.render(&state);
+
+    draw::canvas_draw_start(canvas, &overlay, &state)?;
+    ruler::init_rulers(canvas, &overlay, &state)?;
+    let actions = Actions::new(document, mount, &state)?;
+    toolbar::init_toolbar(
+        mount,
+        &overlay,
+        &actions,
+        &state,
+        options.tools.as_deref(),
+        &options.icons,
+    )?;
+    generate::init_generate(mount, &state, &options.worker_url)?;
+    budget::init_budget(mount, &state, &options.worker_url)?;
+    presets::init_presets(mount, &state, &options.worker_url)?;
+    sequence::init_sequence(mount, &state)?;
+    svg::init_svg(mount, &state)?;
+    spritesheet::init_spritesheet(mount, &overlay, &actions, &state)?;
+    resize::init_resize(mount, &overlay, &state)?;
+    keyboard::init_keyboard(mount, &actions)?;
+    save::init_save(mount, &overlay, &actions, &state)?;
+    import::init_import(mount, &overlay, &actions, &state)?;
+    if let Some(key) = options.autosave {
+        autosave::init_autosave(&key, &overlay, &actions, &state)?;
+    }
+
+    Ok(Editor {
+        overlay,
+        actions,
+        state,
+    })
+}
+
+fn get_el_dimensions(el: &Element) -> (u32, u32) {
+    let width = (el.client_width() as u32).clamp(250, 1500);
+    let height = (el.client_height() as u32).clamp(450, 1800);
+
+    (width, height)
+}
+
+fn get_root_dimensions(root: &Element) -> (u32, u32) {
+    let width = (root.client_width() as u32).clamp(600, 3000);
+    let height = (root.client_height() as u32).clamp(400, 2000);
+
+    (width, height)
+}
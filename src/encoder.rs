@@ -0,0 +1,179 @@
+use apng::{BlendOp, DisposeOp};
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+use std::io::{self, Write};
+
+use crate::palette::Color;
+
+// png/apng writer for the cases the apng crate does not cover:
+// indexed color and per frame sub rectangles.
+// without a palette pixels are 8 bit rgba, with one they are palette indices
+fn bytes_per_pixel(palette: Option<&[Color]>) -> usize {
+    match palette {
+        Some(_) => 1,
+        None => 4,
+    }
+}
+
+fn color_type(palette: Option<&[Color]>) -> u8 {
+    match palette {
+        Some(_) => 3,
+        None => 6,
+    }
+}
+
+pub struct FrameData {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    // 4 bytes per pixel for rgba, one palette index per pixel for indexed
+    pub data: Vec<u8>,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl FrameData {
+    // a full size frame at the origin
+    pub fn full(
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+        delay_num: u16,
+        delay_den: u16,
+    ) -> FrameData {
+        FrameData {
+            x: 0,
+            y: 0,
+            width,
+            height,
+            data,
+            delay_num,
+            delay_den,
+            dispose_op: DisposeOp::ApngDisposeOpNone,
+            blend_op: BlendOp::ApngBlendOpSource,
+        }
+    }
+}
+
+// calculate frame speed, seconds as a fraction of 1/100s
+pub fn frame_delay(seconds: f64) -> (u16, u16) {
+    ((seconds * 100_f64).round() as u16, 100)
+}
+
+// the first frame doubles as the default image so it has to cover the whole canvas
+pub fn encode_apng(
+    width: u32,
+    height: u32,
+    palette: Option<&[Color]>,
+    frames: &[FrameData],
+    plays: u32,
+) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    write_header(&mut buf, width, height, palette)?;
+
+    let mut actl = vec![];
+    actl.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+    actl.extend_from_slice(&plays.to_be_bytes());
+    write_chunk(&mut buf, b"acTL", &actl)?;
+
+    let mut seq_num: u32 = 0;
+    for (i, frame) in frames.iter().enumerate() {
+        let mut fctl = vec![];
+        fctl.extend_from_slice(&seq_num.to_be_bytes());
+        fctl.extend_from_slice(&frame.width.to_be_bytes());
+        fctl.extend_from_slice(&frame.height.to_be_bytes());
+        fctl.extend_from_slice(&frame.x.to_be_bytes());
+        fctl.extend_from_slice(&frame.y.to_be_bytes());
+        fctl.extend_from_slice(&frame.delay_num.to_be_bytes());
+        fctl.extend_from_slice(&frame.delay_den.to_be_bytes());
+        fctl.push(frame.dispose_op as u8);
+        fctl.push(frame.blend_op as u8);
+        write_chunk(&mut buf, b"fcTL", &fctl)?;
+        seq_num += 1;
+
+        let image = compress(frame.width, &frame.data, palette)?;
+        if i == 0 {
+            write_chunk(&mut buf, b"IDAT", &image)?;
+        } else {
+            let mut fdat = seq_num.to_be_bytes().to_vec();
+            fdat.extend_from_slice(&image);
+            write_chunk(&mut buf, b"fdAT", &fdat)?;
+            seq_num += 1;
+        }
+    }
+
+    write_chunk(&mut buf, b"IEND", &[])?;
+
+    Ok(buf)
+}
+
+pub fn encode_png(
+    width: u32,
+    height: u32,
+    palette: Option<&[Color]>,
+    data: &[u8],
+) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    write_header(&mut buf, width, height, palette)?;
+    let image = compress(width, data, palette)?;
+    write_chunk(&mut buf, b"IDAT", &image)?;
+    write_chunk(&mut buf, b"IEND", &[])?;
+
+    Ok(buf)
+}
+
+// png signature, IHDR and for indexed images PLTE and tRNS
+fn write_header(
+    buf: &mut Vec<u8>,
+    width: u32,
+    height: u32,
+    palette: Option<&[Color]>,
+) -> io::Result<()> {
+    buf.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut ihdr = vec![];
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[8, color_type(palette), 0, 0, 0]);
+    write_chunk(buf, b"IHDR", &ihdr)?;
+
+    if let Some(palette) = palette {
+        let plte: Vec<u8> = palette.iter().flat_map(|c| c[..3].to_vec()).collect();
+        write_chunk(buf, b"PLTE", &plte)?;
+
+        // alpha is only needed up to the last entry that is not opaque
+        let trns: Vec<u8> = palette.iter().map(|c| c[3]).collect();
+        if let Some(last) = trns.iter().rposition(|a| *a != 255) {
+            write_chunk(buf, b"tRNS", &trns[..=last])?;
+        }
+    }
+
+    Ok(())
+}
+
+fn compress(width: u32, data: &[u8], palette: Option<&[Color]>) -> io::Result<Vec<u8>> {
+    let row = width as usize * bytes_per_pixel(palette);
+    let mut zlib = ZlibEncoder::new(Vec::new(), Compression::best());
+    for line in data.chunks(row) {
+        // filter type 0 (none) per scanline
+        zlib.write_all(&[0])?;
+        zlib.write_all(line)?;
+    }
+    zlib.finish()
+}
+
+pub fn write_chunk(buf: &mut Vec<u8>, c_type: &[u8; 4], c_data: &[u8]) -> io::Result<()> {
+    buf.write_all(&(c_data.len() as u32).to_be_bytes())?;
+    buf.write_all(c_type)?;
+    buf.write_all(c_data)?;
+
+    let mut crc = Crc::new();
+    crc.update(c_type);
+    crc.update(c_data);
+    buf.write_all(&crc.sum().to_be_bytes())?;
+
+    Ok(())
+}
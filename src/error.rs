@@ -0,0 +1,103 @@
+use std::fmt;
+use std::io;
+use wasm_bindgen::prelude::*;
+
+use crate::decoder::DecodeError;
+use crate::project::ProjectError;
+
+// everything that can go wrong outside of plain dom calls
+#[derive(Debug)]
+pub enum Error {
+    NoFrames,
+    // a stored frame, numbered from 0, that could not be read back
+    Frame(usize, Box<Error>),
+    Base64(base64::DecodeError),
+    Image(image::ImageError),
+    Animation(DecodeError),
+    Project(ProjectError),
+    Atlas(serde_json::Error),
+    Io(io::Error),
+    // a value typed or a file picked that can not be used
+    Input(String),
+    // a browser api that threw
+    Js(String),
+    // an encode job that failed on the worker, already put into words there
+    Worker(String),
+}
+
+impl Error {
+    pub fn frame(index: usize, err: Error) -> Error {
+        Error::Frame(index, Box::new(err))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoFrames => write!(f, "not added image"),
+            Error::Frame(index, err) => write!(f, "frame {}: {}", index + 1, err),
+            Error::Base64(err) => write!(f, "broken frame data: {}", err),
+            Error::Image(err) => write!(f, "{}", err),
+            Error::Animation(err) => write!(f, "{}", err),
+            Error::Project(err) => write!(f, "{}", err),
+            Error::Atlas(err) => write!(f, "broken sprite sheet json: {}", err),
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Input(message) => write!(f, "{}", message),
+            Error::Js(message) => write!(f, "{}", message),
+            Error::Worker(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<base64::DecodeError> for Error {
+    fn from(err: base64::DecodeError) -> Error {
+        Error::Base64(err)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(err: image::ImageError) -> Error {
+        Error::Image(err)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(err: DecodeError) -> Error {
+        Error::Animation(err)
+    }
+}
+
+impl From<ProjectError> for Error {
+    fn from(err: ProjectError) -> Error {
+        Error::Project(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<JsValue> for Error {
+    fn from(value: JsValue) -> Error {
+        // thrown values are usually Error objects, sometimes plain strings
+        let message = value
+            .as_string()
+            .or_else(|| {
+                js_sys::Reflect::get(&value, &JsValue::from_str("message"))
+                    .ok()
+                    .and_then(|m| m.as_string())
+            })
+            .unwrap_or_else(|| format!("{:?}", value));
+        Error::Js(message)
+    }
+}
+
+impl From<Error> for JsValue {
+    fn from(err: Error) -> JsValue {
+        JsValue::from_str(&err.to_string())
+    }
+}
@@ -1,41 +1,57 @@
-use apng;
-use apng::Encoder;
-use apng::{Frame, PNGImage};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
-use std::io::BufWriter;
 use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{
-    window, Blob, BlobPropertyBag, Document, Element, Event, HtmlButtonElement, HtmlInputElement,
-    Url,
+    window, Document, Element, Event, HtmlAnchorElement, HtmlButtonElement, HtmlInputElement,
+    HtmlProgressElement, Url,
 };
 
+use crate::codec;
+use crate::decoder;
+use crate::editor::Mount;
+use crate::encoder::{self, FrameData};
+use crate::error::Error;
+use crate::hooks::Export;
+use crate::notify;
+use crate::optimize;
+use crate::palette::{self, Color};
+use crate::quantize;
+use crate::resize;
+use crate::save;
 use crate::state::State;
+use crate::toolbar;
+use crate::widget::Widget;
+use crate::worker::{EncodeWorker, JobEvent, Outcome, Task};
 
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = console)]
     fn log(msg: &str);
-    fn alert(s: &str);
 }
 
-macro_rules! console_log {
-    ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
-}
+// maximum palette sizes, "full" keeps 32 bit color
+static COLORS: [&str; 7] = ["full", "256", "128", "64", "32", "16", "8"];
+static OPEN_CLASS: &str = "apng-open";
 
-pub fn init_generate(state: &Rc<RefCell<State>>) -> Result<(), JsValue> {
+pub fn init_generate(
+    mount: &Mount,
+    state: &Rc<RefCell<State>>,
+    worker_url: &str,
+) -> Result<(), JsValue> {
     let document = window()
         .unwrap()
         .document()
         .expect("Could not find `document`");
 
-    let generate = document.get_element_by_id("generate").unwrap();
+    let generate = &mount.generate;
 
     let slider = create_frame_speed_slider(&document, state)?;
     generate.append_child(&slider)?;
 
-    let button = create_generate_button(&document, state)?;
+    let button = create_generate_button(&document, mount, state, worker_url)?;
     generate.append_child(&button)?;
 
     Ok(())
@@ -74,18 +90,30 @@ fn create_frame_speed_slider(
         format!("{}", state.borrow().get_frame_speed()).as_str(),
     )?;
 
-    let state = state.clone();
+    let state_clone = state.clone();
     let val_clone = val.clone();
     let handle_input = Closure::wrap(Box::new(move |e: Event| {
         let target = e.target().unwrap().dyn_into::<HtmlInputElement>().unwrap();
         let frame_speed = target.value();
-        val_clone.set_inner_html(&frame_speed);
-        let frame_speed: f64 = frame_speed.parse().unwrap();
-        state.borrow_mut().set_frame_speed(frame_speed);
+        if let Ok(speed) = frame_speed.parse::<f64>() {
+            val_clone.set_inner_html(&frame_speed);
+            state_clone.borrow_mut().set_frame_speed(speed);
+        }
     }) as Box<dyn FnMut(_)>);
 
     slider.add_event_listener_with_callback("input", handle_input.as_ref().unchecked_ref())?;
     handle_input.forget();
+
+    // the frame delays follow the speed, listeners hear of it once the slider is let go
+    let state = state.clone();
+    let handle_change = Closure::wrap(Box::new(move || {
+        let state = state.borrow();
+        state
+            .get_hooks()
+            .frames_changed(state.get_current_frame(), &state.get_frame_delays());
+    }) as Box<dyn FnMut()>);
+    slider.add_event_listener_with_callback("change", handle_change.as_ref().unchecked_ref())?;
+    handle_change.forget();
     element.append_child(&slider)?;
     div.append_child(&val)?;
     div.append_child(&element)?;
@@ -95,72 +123,450 @@ fn create_frame_speed_slider(
 
 fn create_generate_button(
     document: &Document,
+    mount: &Mount,
     state: &Rc<RefCell<State>>,
-) -> Result<HtmlButtonElement, JsValue> {
+    worker_url: &str,
+) -> Result<Element, JsValue> {
+    let element = Widget::new("div").class("row").build(document)?;
+
     let button = document
         .create_element("button")?
         .dyn_into::<HtmlButtonElement>()?;
     button.set_attribute("class", "btn")?;
     button.set_inner_html("APNG generate");
+    element.append_child(&button)?;
 
+    let optimize = toolbar::create_checkbox(document, &element, "optimize")?;
+    optimize.set_checked(true);
+
+    let colors = resize::create_select(document, &COLORS, COLORS[0])?;
+    colors.set_attribute("title", "colors")?;
+    element.append_child(&colors)?;
+    let dither = toolbar::create_checkbox(document, &element, "dither")?;
+
+    let report = document.create_element("span")?;
+    report.set_attribute("class", "apng-report")?;
+    element.append_child(&report)?;
+
+    // opened by the user's own click, so popup blockers leave it alone
+    let open = document
+        .create_element("a")?
+        .dyn_into::<HtmlAnchorElement>()?;
+    open.set_attribute("class", OPEN_CLASS)?;
+    open.set_attribute("target", "_blank")?;
+    open.set_attribute("rel", "noopener")?;
+    open.set_attribute("style", "display: none;")?;
+    open.set_inner_html("open in new tab");
+    element.append_child(&open)?;
+
+    let controls = JobControls::new(
+        document,
+        &element,
+        std::slice::from_ref(&button),
+        &report,
+        worker_url,
+    )?;
+    let mount = mount.clone();
     let state = state.clone();
     let handle_click = Closure::wrap(Box::new(move || {
-        if state.borrow().get_preview_image_len() == 0 {
-            alert("not added image");
+        let job = Job::from_state(
+            &state.borrow(),
+            optimize.checked(),
+            colors.value().parse::<usize>().ok(),
+            dither.checked(),
+        );
+        let frames = match frame_files(&state.borrow().get_preview_image()) {
+            Ok(frames) => frames,
+            Err(err) => {
+                notify::error("APNG generate", &err);
+                return;
+            }
+        };
+
+        let (state, mount) = (state.clone(), mount.clone());
+        controls.run(
+            "APNG generate",
+            &Task::Generate(job),
+            frames,
+            move |apng, _| {
+                log("success apng encode!!!");
+                notify::report(
+                    "APNG generate",
+                    download_apng(&apng, &state.borrow(), &mount),
+                );
+            },
+        );
+    }) as Box<dyn FnMut()>);
+    button.set_onclick(Some(handle_click.as_ref().unchecked_ref()));
+    handle_click.forget();
+
+    Ok(element)
+}
+
+// the progress bar and cancel button of a row whose buttons run tasks on the worker,
+// one task at a time
+#[derive(Clone)]
+pub struct JobControls {
+    worker: EncodeWorker,
+    buttons: Vec<HtmlButtonElement>,
+    progress: HtmlProgressElement,
+    cancel: HtmlButtonElement,
+    report: Element,
+}
+
+impl JobControls {
+    // appended to `element`, hidden until a task runs. `report` shows how far it got,
+    // `worker_url` is where the encoder worker script is served from
+    pub fn new(
+        document: &Document,
+        element: &Element,
+        buttons: &[HtmlButtonElement],
+        report: &Element,
+        worker_url: &str,
+    ) -> Result<JobControls, JsValue> {
+        let progress = document
+            .create_element("progress")?
+            .dyn_into::<HtmlProgressElement>()?;
+        progress.set_attribute("class", "apng-progress")?;
+        progress.set_attribute("style", "display: none;")?;
+        element.append_child(&progress)?;
+        let cancel = document
+            .create_element("button")?
+            .dyn_into::<HtmlButtonElement>()?;
+        cancel.set_inner_html("cancel");
+        cancel.set_attribute("style", "display: none;")?;
+        element.append_child(&cancel)?;
+
+        let controls = JobControls {
+            worker: EncodeWorker::new(worker_url),
+            buttons: buttons.to_vec(),
+            progress,
+            cancel,
+            report: report.clone(),
+        };
+
+        let handle_cancel = {
+            let controls = controls.clone();
+            Closure::wrap(Box::new(move || {
+                controls.worker.cancel();
+                controls.finish();
+                controls.report.set_inner_html("cancelled");
+            }) as Box<dyn FnMut()>)
+        };
+        controls
+            .cancel
+            .set_onclick(Some(handle_cancel.as_ref().unchecked_ref()));
+        handle_cancel.forget();
+
+        Ok(controls)
+    }
+
+    // `on_done` gets the apng and how the task went, failures are reported under `context`
+    pub fn run(
+        &self,
+        context: &'static str,
+        task: &Task,
+        frames: Vec<Vec<u8>>,
+        mut on_done: impl FnMut(Vec<u8>, Outcome) + 'static,
+    ) {
+        if self.worker.is_busy() {
             return;
         }
+        let on_event = {
+            let controls = self.clone();
+            move |event: JobEvent| match event {
+                JobEvent::Progress { stage, done, total } => {
+                    controls.progress.set_max(total.max(1) as f64);
+                    controls.progress.set_value(done as f64);
+                    controls
+                        .report
+                        .set_inner_html(&format!("{} {}/{}", stage, done, total));
+                }
+                JobEvent::Done { apng, outcome } => {
+                    controls.finish();
+                    controls.report.set_inner_html(&outcome.report);
+                    on_done(apng, outcome);
+                }
+                JobEvent::Failed(err) => {
+                    controls.finish();
+                    controls.report.set_inner_html("");
+                    notify::error(context, &err);
+                }
+            }
+        };
+
+        match self.worker.start(task, frames, on_event) {
+            Ok(()) => {
+                self.buttons.iter().for_each(|b| b.set_disabled(true));
+                // indeterminate until the first frame is done
+                let _ = self.progress.remove_attribute("value");
+                let _ = self.progress.set_attribute("style", "margin-left: 8px;");
+                let _ = self.cancel.set_attribute("style", "");
+            }
+            Err(err) => notify::error(context, &err.into()),
+        }
+    }
 
-        let preview_images = state.borrow().get_preview_image();
+    // back to idle once a task is over, however it ended
+    fn finish(&self) {
+        self.buttons.iter().for_each(|b| b.set_disabled(false));
+        let _ = self.progress.set_attribute("style", "display: none;");
+        let _ = self.cancel.set_attribute("style", "display: none;");
+    }
+}
 
-        let mut png_images: Vec<PNGImage> = Vec::new();
-        for data in preview_images {
-            let v = data.to_string().replace("data:image/png;base64,", "");
+// what the worker needs besides the frames themselves
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub delays: Vec<f64>,
+    // pixel art is written against the document palette
+    pub palette: Option<Vec<Color>>,
+    pub optimize: bool,
+    // `None` keeps 32 bit color
+    pub max_colors: Option<usize>,
+    pub dither: bool,
+}
 
-            let buffer = base64::decode(&v).unwrap();
-            let img =
-                image::load_from_memory_with_format(&buffer, image::ImageFormat::PNG).unwrap();
-            png_images.push(apng::load_dynamic_image(img).unwrap());
+impl Job {
+    pub fn from_state(
+        state: &State,
+        optimize: bool,
+        max_colors: Option<usize>,
+        dither: bool,
+    ) -> Job {
+        Job {
+            delays: state.get_frame_delays(),
+            palette: if state.is_pixel_art() {
+                Some(state.get_palette())
+            } else {
+                None
+            },
+            optimize,
+            max_colors,
+            dither,
         }
+    }
+}
 
-        let mut buf = Vec::new();
-        {
-            let mut buf_writer = BufWriter::new(&mut buf);
+// the png file of every stored frame, cheap enough to do before handing them to the worker
+pub fn frame_files(preview_images: &[String]) -> Result<Vec<Vec<u8>>, Error> {
+    if preview_images.is_empty() {
+        return Err(Error::NoFrames);
+    }
+    preview_images
+        .iter()
+        .enumerate()
+        .map(|(i, data)| codec::data_url_bytes(data).map_err(|e| Error::frame(i, e)))
+        .collect()
+}
 
-            let config = apng::create_config(&png_images, None).unwrap();
-            let mut encoder = Encoder::new(&mut buf_writer, config).unwrap();
+// the apng and a line on its size. `progress` is told the stage and how many of its frames are done
+pub fn generate(
+    mut images: Vec<RgbaImage>,
+    job: &Job,
+    progress: &mut dyn FnMut(&str, usize, usize),
+) -> Result<(Vec<u8>, String), Error> {
+    let mut palette = job.palette.clone();
+    // the plain encoding of the untouched frames is only made to tell how much was saved
+    let full = if job.optimize {
+        let measure = &mut |done, total| progress("measuring", done, total);
+        encode(
+            images.clone(),
+            &job.delays,
+            palette.as_deref(),
+            false,
+            0,
+            measure,
+        )?
+        .0
+    } else {
+        vec![]
+    };
 
-            // calculate frame speed
-            let frame_speed = state.borrow().get_frame_speed();
-            let d_num = frame_speed * (100 as f64);
-            let d_den = 100;
+    // pixel art already has the document palette
+    if let (None, Some(max_colors)) = (&palette, job.max_colors) {
+        progress("reducing colors", 0, images.len());
+        let reduced = quantize::median_cut(&images, max_colors);
+        let total = images.len();
+        images = images
+            .iter()
+            .enumerate()
+            .map(|(i, img)| {
+                let remapped = quantize::remap(img, &reduced, job.dither);
+                progress("reducing colors", i + 1, total);
+                remapped
+            })
+            .collect();
+        palette = Some(reduced);
+    }
 
-            let frame = Frame {
-                delay_num: Some(d_num as u16),
-                delay_den: Some(d_den),
-                ..Default::default()
-            };
+    let (buf, merged) = encode(
+        images,
+        &job.delays,
+        palette.as_deref(),
+        job.optimize,
+        0,
+        &mut |done, total| progress("encoding", done, total),
+    )?;
+    let text = if job.optimize {
+        size_report(buf.len(), full.len(), merged)
+    } else {
+        format_size(buf.len())
+    };
 
-            match encoder.encode_all(png_images, Some(&frame)) {
-                Ok(_n) => log("success apng encode!!!"),
-                Err(err) => console_log!("{}", err),
-            }
+    Ok((buf, text))
+}
+
+// saved as a named download, the "open in new tab" link then points at the latest one
+pub fn download_apng(buf: &[u8], state: &State, mount: &Mount) -> Result<(), Error> {
+    let document = window()
+        .unwrap()
+        .document()
+        .expect("Could not find `document`");
+    let export = apng_export(buf, format!("{}.png", save::export_stem(state)), state);
+    let blob = save::create_blob(buf, export.mime)?;
+    save::download_blob(&document, &blob, &export.file_name)?;
+    state.get_hooks().exported(&export);
+
+    let link = mount
+        .generate
+        .query_selector(&format!("a.{}", OPEN_CLASS))?
+        .and_then(|link| link.dyn_into::<HtmlAnchorElement>().ok());
+    if let Some(link) = link {
+        // the link keeps its url alive until the next export replaces it
+        let previous = link.href();
+        if previous.starts_with("blob:") {
+            Url::revoke_object_url(&previous)?;
         }
+        link.set_href(&Url::create_object_url_with_blob(&blob)?);
+        link.set_attribute("style", "margin-left: 8px;")?;
+    }
 
-        let b = js_sys::Uint8Array::new(&unsafe { js_sys::Uint8Array::view(&buf) }.into());
-        let array = js_sys::Array::new();
-        array.push(&b.buffer());
-        let blob = Blob::new_with_u8_array_sequence_and_options(
-            &array,
-            BlobPropertyBag::new().type_("image/png"),
-        )
-        .unwrap();
-        let url = Url::create_object_url_with_blob(&blob).unwrap();
-        let window = window().unwrap();
-        window.open_with_url(&url).unwrap();
-    }) as Box<dyn FnMut()>);
+    Ok(())
+}
 
-    button.set_onclick(Some(handle_click.as_ref().unchecked_ref()));
-    handle_click.forget();
+// described by its own chunks, as budgets and presets may have scaled or thinned it
+pub fn apng_export<'a>(buf: &'a [u8], file_name: String, state: &State) -> Export<'a> {
+    let export = Export::of_document(state, "apng", file_name, "image/png", buf);
+    match decoder::animation_info(buf) {
+        Ok(info) => Export {
+            width: info.width,
+            height: info.height,
+            frames: info.frames,
+            duration: info.duration,
+            ..export
+        },
+        Err(_) => export,
+    }
+}
+
+// every stored frame as pixels, a broken one is reported by its number
+pub fn decode_frames(preview_images: &[String]) -> Result<Vec<RgbaImage>, Error> {
+    preview_images
+        .iter()
+        .enumerate()
+        .map(|(i, data)| codec::decode_data_url(data).map_err(|e| Error::frame(i, e)))
+        .collect()
+}
+
+// the apng and how many duplicate frames were merged away, `plays` 0 loops forever.
+// pixel art frames are written palettized against the document palette
+pub fn encode(
+    images: Vec<RgbaImage>,
+    delays: &[f64],
+    palette: Option<&[Color]>,
+    optimize: bool,
+    plays: u32,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<(Vec<u8>, usize), Error> {
+    let (width, height) = images.last().map_or((0, 0), |img| img.dimensions());
+    let (frames, merged) = if optimize {
+        let optimized = optimize::optimize(images, delays, palette, progress);
+        (optimized.frames, optimized.merged)
+    } else {
+        let total = images.len();
+        let frames = images
+            .into_iter()
+            .zip(delays.iter())
+            .enumerate()
+            .map(|(i, (img, delay))| {
+                let pixels = match palette {
+                    Some(palette) => palette::index_image(&img, palette),
+                    None => img.into_raw(),
+                };
+                let (d_num, d_den) = encoder::frame_delay(*delay);
+                progress(i + 1, total);
+                FrameData::full(width, height, pixels, d_num, d_den)
+            })
+            .collect();
+        (frames, 0)
+    };
+
+    // a single pixel art frame is written as a plain png
+    let buf = if frames.len() == 1 && palette.is_some() {
+        encoder::encode_png(width, height, palette, &frames[0].data)?
+    } else {
+        encoder::encode_apng(width, height, palette, &frames, plays)?
+    };
+
+    Ok((buf, merged))
+}
 
-    Ok(button)
+fn size_report(size: usize, full: usize, merged: usize) -> String {
+    let saved = if full > 0 {
+        100.0 - size as f64 / full as f64 * 100.0
+    } else {
+        0.0
+    };
+    let mut report = format!(
+        "{} (saved {:.0}% of {})",
+        format_size(size),
+        saved.max(0.0),
+        format_size(full)
+    );
+    if merged > 0 {
+        report.push_str(&format!(", {} duplicate frames merged", merged));
+    }
+    report
+}
+
+pub fn format_size(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", bytes as f64 / 1024.0 / 1024.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn reports_every_frame_of_every_stage() {
+        let images: Vec<_> = (0..3)
+            .map(|i| RgbaImage::from_pixel(4, 4, Rgba([i * 80, 0, 0, 255])))
+            .collect();
+        let job = Job {
+            delays: vec![0.1; 3],
+            palette: None,
+            optimize: true,
+            max_colors: Some(16),
+            dither: false,
+        };
+        let mut seen: Vec<(String, usize, usize)> = vec![];
+        let (apng, _) = generate(images, &job, &mut |stage, done, total| {
+            seen.push((stage.to_string(), done, total))
+        })
+        .unwrap();
+
+        for stage in ["measuring", "reducing colors", "encoding"].iter() {
+            let last = seen.iter().rev().find(|(s, _, _)| s == stage).unwrap();
+            assert_eq!(last.1, last.2);
+        }
+        assert_eq!(crate::decoder::decode_animation(&apng).unwrap().len(), 3);
+    }
 }
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+
+// distance in pixels within which a point is pulled onto a grid line or guide
+pub static SNAP_DISTANCE: f64 = 6.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Orientation {
+    // a line across the canvas at a fixed y
+    Horizontal,
+    // a line down the canvas at a fixed x
+    Vertical,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Guide {
+    pub orientation: Orientation,
+    pub position: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Grid {
+    pub visible: bool,
+    pub spacing: u32,
+    pub snap: bool,
+}
+
+impl Default for Grid {
+    fn default() -> Grid {
+        Grid {
+            visible: false,
+            spacing: 16,
+            snap: false,
+        }
+    }
+}
+
+impl Grid {
+    // grid lines are only snapped to while they are shown
+    fn nearest_line(&self, v: f64) -> Option<f64> {
+        if !self.visible || self.spacing == 0 {
+            return None;
+        }
+        let spacing = self.spacing as f64;
+        Some((v / spacing).round() * spacing)
+    }
+
+    // snap a single coordinate to the grid only
+    pub fn snap_value(&self, v: f64) -> f64 {
+        if !self.snap {
+            return v;
+        }
+        self.nearest_line(v)
+            .filter(|line| (line - v).abs() <= SNAP_DISTANCE)
+            .unwrap_or(v)
+    }
+}
+
+// move a point onto the closest guide or grid line on each axis, guides win over the grid.
+// guides always pull, the grid only while its snapping is on
+pub fn snap(grid: &Grid, guides: &[Guide], p: (f64, f64)) -> (f64, f64) {
+    let snap_axis = |v: f64, orientation: Orientation| -> f64 {
+        let guide = guides
+            .iter()
+            .filter(|g| g.orientation == orientation)
+            .map(|g| g.position)
+            .filter(|pos| (pos - v).abs() <= SNAP_DISTANCE)
+            .min_by(|a, b| (a - v).abs().partial_cmp(&(b - v).abs()).unwrap());
+
+        guide.unwrap_or_else(|| grid.snap_value(v))
+    };
+
+    (
+        snap_axis(p.0, Orientation::Vertical),
+        snap_axis(p.1, Orientation::Horizontal),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(snap: bool) -> Grid {
+        Grid {
+            visible: true,
+            spacing: 10,
+            snap,
+        }
+    }
+
+    fn guide(orientation: Orientation, position: f64) -> Guide {
+        Guide {
+            orientation,
+            position,
+        }
+    }
+
+    #[test]
+    fn grid_snaps_only_when_near_and_on() {
+        assert_eq!(grid(true).snap_value(23.0), 20.0);
+        let wide = Grid {
+            spacing: 20,
+            ..grid(true)
+        };
+        assert_eq!(wide.snap_value(30.0), 30.0);
+        assert_eq!(grid(false).snap_value(23.0), 23.0);
+
+        let hidden = Grid {
+            visible: false,
+            ..grid(true)
+        };
+        assert_eq!(hidden.snap_value(23.0), 23.0);
+    }
+
+    #[test]
+    fn guides_snap_without_grid_snapping() {
+        let guides = [
+            guide(Orientation::Vertical, 33.0),
+            guide(Orientation::Horizontal, 50.0),
+        ];
+        assert_eq!(snap(&grid(false), &guides, (30.0, 47.0)), (33.0, 50.0));
+        // out of reach of any guide, and the grid is off
+        assert_eq!(snap(&grid(false), &guides, (12.0, 13.0)), (12.0, 13.0));
+    }
+
+    #[test]
+    fn guides_win_over_the_grid() {
+        let guides = [guide(Orientation::Vertical, 24.0)];
+        assert_eq!(snap(&grid(true), &guides, (21.0, 38.0)), (24.0, 40.0));
+        // the closest guide is picked
+        let guides = [
+            guide(Orientation::Vertical, 16.0),
+            guide(Orientation::Vertical, 19.0),
+        ];
+        assert_eq!(snap(&grid(false), &guides, (18.0, 0.0)).0, 19.0);
+    }
+}
@@ -0,0 +1,161 @@
+use js_sys::{Array, Function, Object, Reflect, Uint8Array};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::window;
+
+use crate::error::Error;
+use crate::notify;
+use crate::state::State;
+
+// what a host page can listen to through `Editor::on`
+pub static EVENTS: [&str; 3] = ["change", "frames", "export"];
+
+// listeners of the page embedding the editor, and of the toolbar keeping up with the history
+#[derive(Clone, Default)]
+pub struct Hooks {
+    listeners: Rc<RefCell<Vec<(String, Function)>>>,
+}
+
+impl Hooks {
+    pub fn on(&self, event: &str, listener: Function) -> Result<(), Error> {
+        if !EVENTS.contains(&event) {
+            return Err(Error::Input(format!(
+                "unknown event `{}`, the events are {}",
+                event,
+                EVENTS.join(", ")
+            )));
+        }
+        self.listeners
+            .borrow_mut()
+            .push((event.to_string(), listener));
+        Ok(())
+    }
+
+    pub fn off(&self, event: &str, listener: &Function) {
+        self.listeners
+            .borrow_mut()
+            .retain(|(e, f)| !(e == event && f == listener));
+    }
+
+    // the drawing or the frame list changed
+    pub fn changed(&self) {
+        self.emit("change", JsValue::UNDEFINED);
+    }
+
+    // `{ count, current, delays }`, current is `null` while no frame is selected
+    pub fn frames_changed(&self, current: Option<usize>, delays: &[f64]) {
+        let delays: Array = delays.iter().map(|d| JsValue::from_f64(*d)).collect();
+        self.emit(
+            "frames",
+            object(&[
+                ("count", JsValue::from_f64(delays.length() as f64)),
+                (
+                    "current",
+                    current.map_or(JsValue::NULL, |i| JsValue::from_f64(i as f64)),
+                ),
+                ("delays", delays.into()),
+            ]),
+        );
+    }
+
+    // a file was made, for a download or for `Editor::export_apng`
+    pub fn exported(&self, export: &Export) {
+        if !self.has("export") {
+            return;
+        }
+        self.emit(
+            "export",
+            object(&[
+                ("bytes", Uint8Array::from(export.bytes).into()),
+                ("format", JsValue::from_str(export.format)),
+                ("fileName", JsValue::from_str(&export.file_name)),
+                ("mimeType", JsValue::from_str(export.mime)),
+                ("size", JsValue::from_f64(export.bytes.len() as f64)),
+                ("width", JsValue::from_f64(f64::from(export.width))),
+                ("height", JsValue::from_f64(f64::from(export.height))),
+                ("frames", JsValue::from_f64(export.frames as f64)),
+                ("duration", JsValue::from_f64(export.duration)),
+            ]),
+        );
+    }
+
+    fn has(&self, event: &str) -> bool {
+        self.listeners.borrow().iter().any(|(e, _)| e == event)
+    }
+
+    // listeners run once the handler that fired the event has returned, so one that
+    // calls back into the editor finds nothing borrowed
+    fn emit(&self, event: &'static str, payload: JsValue) {
+        let listeners: Vec<Function> = self
+            .listeners
+            .borrow()
+            .iter()
+            .filter(|(e, _)| e == event)
+            .map(|(_, f)| f.clone())
+            .collect();
+        if listeners.is_empty() {
+            return;
+        }
+
+        let call = Closure::once_into_js(move || {
+            for listener in listeners {
+                notify::report(event, listener.call1(&JsValue::NULL, &payload).map(|_| ()));
+            }
+        });
+        notify::report(
+            event,
+            window()
+                .unwrap()
+                .set_timeout_with_callback_and_timeout_and_arguments_0(call.unchecked_ref(), 0)
+                .map(|_| ()),
+        );
+    }
+}
+
+// an exported file and what it holds, handed to the host page as it is downloaded
+pub struct Export<'a> {
+    // apng, png-frames, svg, svg-frames, animated-svg, sprite-sheet, atlas or project
+    pub format: &'static str,
+    pub file_name: String,
+    pub mime: &'static str,
+    pub bytes: &'a [u8],
+    pub width: u32,
+    pub height: u32,
+    pub frames: usize,
+    // seconds
+    pub duration: f64,
+}
+
+impl<'a> Export<'a> {
+    // sized and timed like the document
+    pub fn of_document(
+        state: &State,
+        format: &'static str,
+        file_name: String,
+        mime: &'static str,
+        bytes: &'a [u8],
+    ) -> Export<'a> {
+        let delays = state.get_frame_delays();
+        Export {
+            format,
+            file_name,
+            mime,
+            bytes,
+            width: state.get_width(),
+            height: state.get_height(),
+            frames: delays.len(),
+            duration: delays.iter().sum(),
+        }
+    }
+}
+
+fn object(fields: &[(&str, JsValue)]) -> JsValue {
+    let object = Object::new();
+    for (key, value) in fields {
+        // setting a plain property on a fresh object does not throw
+        let _ = Reflect::set(&object, &JsValue::from_str(key), value);
+    }
+    object.into()
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+
+// drawn for drawasm and built into the wasm, so the toolbar works offline
+pub static ICONS: [(&str, &str); 7] = [
+    ("pen", include_str!("../assets/icons/pen.svg")),
+    ("eraser", include_str!("../assets/icons/eraser.svg")),
+    ("undo", include_str!("../assets/icons/undo.svg")),
+    ("redo", include_str!("../assets/icons/redo.svg")),
+    ("clear", include_str!("../assets/icons/clear.svg")),
+    ("add-frame", include_str!("../assets/icons/add-frame.svg")),
+    (
+        "clear-frames",
+        include_str!("../assets/icons/clear-frames.svg"),
+    ),
+];
+
+// the bundled icons, some of them swapped out by an embedding page
+#[derive(Clone, Default)]
+pub struct Icons {
+    overrides: HashMap<String, String>,
+}
+
+impl Icons {
+    // `icon` is svg markup or a url of any kind, data urls included
+    pub fn set(&mut self, name: &str, icon: String) -> Result<(), Error> {
+        if !ICONS.iter().any(|(n, _)| *n == name) {
+            return Err(Error::Input(format!(
+                "unknown icon `{}`, the icons are {}",
+                name,
+                ICONS.iter().map(|(n, _)| *n).collect::<Vec<_>>().join(", ")
+            )));
+        }
+        self.overrides.insert(name.to_string(), icon);
+        Ok(())
+    }
+
+    // a css `url(...)` for `background-image`
+    pub fn css_url(&self, name: &str) -> String {
+        let icon = match self.overrides.get(name) {
+            Some(icon) => icon.as_str(),
+            None => ICONS
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map_or("", |(_, svg)| *svg),
+        };
+        let url = if icon.trim_start().starts_with('<') {
+            format!("data:image/svg+xml;base64,{}", base64::encode(icon))
+        } else {
+            icon.to_string()
+        };
+        format!("url(\"{}\")", url.replace('"', "%22"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundles_every_icon_as_data_url() {
+        let icons = Icons::default();
+        for (name, svg) in ICONS.iter() {
+            assert!(svg.starts_with("<svg"), "{} is not svg", name);
+            let url = icons.css_url(name);
+            assert!(url.starts_with("url(\"data:image/svg+xml;base64,"));
+            assert!(!url.contains("http"));
+        }
+    }
+
+    #[test]
+    fn overrides_take_urls_or_markup() {
+        let mut icons = Icons::default();
+        icons
+            .set("pen", "https://example.com/pen \"1\".svg".to_string())
+            .unwrap();
+        icons.set("undo", "<svg></svg>".to_string()).unwrap();
+
+        assert_eq!(
+            icons.css_url("pen"),
+            "url(\"https://example.com/pen %221%22.svg\")"
+        );
+        assert_eq!(
+            icons.css_url("undo"),
+            format!(
+                "url(\"data:image/svg+xml;base64,{}\")",
+                base64::encode("<svg></svg>")
+            )
+        );
+        assert!(icons.set("brush", String::new()).is_err());
+    }
+}
@@ -0,0 +1,259 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    window, Blob, ClipboardEvent, Document, DragEvent, FileList, HtmlButtonElement,
+    HtmlImageElement, HtmlInputElement, HtmlSelectElement, Url,
+};
+
+use crate::actions::Actions;
+use crate::editor::Mount;
+use crate::error::Error;
+use crate::notify;
+use crate::overlay::Overlay;
+use crate::resize;
+use crate::save;
+use crate::state::State;
+use crate::widget::Widget;
+
+// what happens to an imported image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportMode {
+    Canvas,
+    Frame,
+    Reference,
+}
+
+impl ImportMode {
+    pub fn all() -> [ImportMode; 3] {
+        [ImportMode::Canvas, ImportMode::Frame, ImportMode::Reference]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ImportMode::Canvas => "onto canvas",
+            ImportMode::Frame => "as new frame",
+            ImportMode::Reference => "as reference",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ImportMode> {
+        ImportMode::all().iter().find(|m| m.name() == name).copied()
+    }
+}
+
+// images come from the file picker, a drop on the canvas or a paste,
+// all of them go where the mode select points
+#[derive(Clone)]
+struct Importer {
+    mode: HtmlSelectElement,
+    overlay: Overlay,
+    actions: Actions,
+    state: Rc<RefCell<State>>,
+}
+
+pub fn init_import(
+    mount: &Mount,
+    overlay: &Overlay,
+    actions: &Actions,
+    state: &Rc<RefCell<State>>,
+) -> Result<(), JsValue> {
+    let window = window().unwrap();
+    let document = window.document().expect("Could not find `document`");
+
+    let generate = &mount.generate;
+
+    let element = Widget::new("div")
+        .class("row")
+        .class("import")
+        .build(&document)?;
+
+    let (label, input) = save::create_file_label(&document, "import image", "image/*")?;
+    element.append_child(&label)?;
+
+    let modes: Vec<&str> = ImportMode::all().iter().map(|m| m.name()).collect();
+    let mode = resize::create_select(&document, &modes, ImportMode::Canvas.name())?;
+    element.append_child(&mode)?;
+
+    let importer = Importer {
+        mode,
+        overlay: overlay.clone(),
+        actions: actions.clone(),
+        state: state.clone(),
+    };
+
+    // file picker
+    {
+        let importer = importer.clone();
+        let input_clone = input.clone();
+        let handle_change = Closure::wrap(Box::new(move || {
+            if let Some(files) = input_clone.files() {
+                notify::report("import image", importer.import_files(&files));
+            }
+            input_clone.set_value("");
+        }) as Box<dyn FnMut()>);
+        input.add_event_listener_with_callback("change", handle_change.as_ref().unchecked_ref())?;
+        handle_change.forget();
+    }
+
+    // drag and drop onto the canvas
+    {
+        let handle_dragover = Closure::wrap(Box::new(move |event: DragEvent| {
+            // without this the browser opens the file instead of dropping it
+            event.prevent_default();
+        }) as Box<dyn FnMut(_)>);
+        let importer = importer.clone();
+        let handle_drop = Closure::wrap(Box::new(move |event: DragEvent| {
+            event.prevent_default();
+            if let Some(files) = event.data_transfer().and_then(|d| d.files()) {
+                notify::report("import image", importer.import_files(&files));
+            }
+        }) as Box<dyn FnMut(_)>);
+
+        let wrapper = overlay.wrapper();
+        wrapper.add_event_listener_with_callback(
+            "dragover",
+            handle_dragover.as_ref().unchecked_ref(),
+        )?;
+        wrapper.add_event_listener_with_callback("drop", handle_drop.as_ref().unchecked_ref())?;
+        handle_dragover.forget();
+        handle_drop.forget();
+    }
+
+    // paste from the clipboard, text pastes are left alone
+    {
+        let importer = importer.clone();
+        let mount = mount.clone();
+        let handle_paste = Closure::wrap(Box::new(move |event: ClipboardEvent| {
+            // another editor on the page is the one being pasted into
+            if !mount.is_active() {
+                return;
+            }
+            let files = match event.clipboard_data().and_then(|d| d.files()) {
+                Some(files) if files.length() > 0 => files,
+                _ => return,
+            };
+            event.prevent_default();
+            notify::report("import image", importer.import_files(&files));
+        }) as Box<dyn FnMut(_)>);
+        window.add_event_listener_with_callback("paste", handle_paste.as_ref().unchecked_ref())?;
+        handle_paste.forget();
+    }
+
+    let opacity = create_opacity_input(&document, state)?;
+    {
+        let overlay = overlay.clone();
+        let state = state.clone();
+        let opacity_clone = opacity.clone();
+        let handle_input = Closure::wrap(Box::new(move || {
+            let value = opacity_clone.value().parse::<f64>().unwrap_or(50.0);
+            state.borrow_mut().set_reference_opacity(value / 100.0);
+            overlay.render(&state);
+        }) as Box<dyn FnMut()>);
+        opacity.add_event_listener_with_callback("input", handle_input.as_ref().unchecked_ref())?;
+        handle_input.forget();
+    }
+    element.append_child(&opacity)?;
+
+    let clear = document
+        .create_element("button")?
+        .dyn_into::<HtmlButtonElement>()?;
+    clear.set_inner_html("clear reference");
+    {
+        let overlay = overlay.clone();
+        let state = state.clone();
+        let handle_click = Closure::wrap(Box::new(move || {
+            state.borrow_mut().set_reference(None);
+            overlay.render(&state);
+        }) as Box<dyn FnMut()>);
+        clear.set_onclick(Some(handle_click.as_ref().unchecked_ref()));
+        handle_click.forget();
+    }
+    element.append_child(&clear)?;
+
+    generate.append_child(&element)?;
+
+    Ok(())
+}
+
+impl Importer {
+    fn mode(&self) -> ImportMode {
+        ImportMode::from_name(&self.mode.value()).unwrap_or(ImportMode::Canvas)
+    }
+
+    // the first image among `files`, anything else is ignored
+    fn import_files(&self, files: &FileList) -> Result<(), JsValue> {
+        let file = (0..files.length())
+            .filter_map(|i| files.get(i))
+            .find(|f| f.type_().starts_with("image/"));
+        match file {
+            Some(file) => self.import(&file),
+            None => Ok(()),
+        }
+    }
+
+    // let the browser decode the image, so every format it can show can be imported
+    fn import(&self, blob: &Blob) -> Result<(), JsValue> {
+        let url = Url::create_object_url_with_blob(blob)?;
+        let image = HtmlImageElement::new()?;
+
+        let handle_load = {
+            let importer = self.clone();
+            let image = image.clone();
+            let url = url.clone();
+            Closure::wrap(Box::new(move || {
+                let _ = Url::revoke_object_url(&url);
+                notify::report("import image", importer.apply(&image));
+            }) as Box<dyn FnMut()>)
+        };
+        let handle_error = {
+            let url = url.clone();
+            Closure::wrap(Box::new(move || {
+                let _ = Url::revoke_object_url(&url);
+                notify::error(
+                    "import image",
+                    &Error::Input("could not read the image".to_string()),
+                );
+            }) as Box<dyn FnMut()>)
+        };
+        image.set_onload(Some(handle_load.as_ref().unchecked_ref()));
+        image.set_onerror(Some(handle_error.as_ref().unchecked_ref()));
+        handle_load.forget();
+        handle_error.forget();
+
+        image.set_src(&url);
+
+        Ok(())
+    }
+
+    fn apply(&self, image: &HtmlImageElement) -> Result<(), JsValue> {
+        match self.mode() {
+            ImportMode::Canvas => self.actions.place_image(image),
+            ImportMode::Frame => self.actions.add_image_frame(image),
+            ImportMode::Reference => {
+                self.state.borrow_mut().set_reference(Some(image.clone()));
+                self.overlay.render(&self.state);
+                Ok(())
+            }
+        }
+    }
+}
+
+// reference opacity in percent
+fn create_opacity_input(
+    document: &Document,
+    state: &Rc<RefCell<State>>,
+) -> Result<HtmlInputElement, JsValue> {
+    let input = document
+        .create_element("input")?
+        .dyn_into::<HtmlInputElement>()?;
+    input.set_attribute("type", "range")?;
+    input.set_attribute("min", "0")?;
+    input.set_attribute("max", "100")?;
+    input.set_attribute("title", "reference opacity")?;
+    input.set_attribute("style", "width: 6em;")?;
+    input.set_value(&((state.borrow().get_reference_opacity() * 100.0).round()).to_string());
+
+    Ok(input)
+}
@@ -0,0 +1,170 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{window, Document, Element, HtmlElement, HtmlInputElement, KeyboardEvent, Storage};
+
+use crate::actions::Actions;
+use crate::editor::Mount;
+use crate::keymap::{Action, Keymap, Shortcut};
+use crate::notify;
+
+static STORAGE_KEY: &str = "drawasm.keymap";
+
+// with several editors on the page only the one last clicked in takes shortcuts
+pub fn init_keyboard(mount: &Mount, actions: &Actions) -> Result<(), JsValue> {
+    let window = window().unwrap();
+    let document = window.document().expect("Could not find `document`");
+
+    let keymap = Rc::new(RefCell::new(load_keymap()));
+
+    let handle_keydown = {
+        let actions = actions.clone();
+        let keymap = keymap.clone();
+        let mount = mount.clone();
+
+        Closure::wrap(Box::new(move |event: KeyboardEvent| {
+            if !mount.is_active() {
+                return;
+            }
+            // typing into a form field is not a shortcut
+            if let Some(target) = event.target() {
+                if let Ok(el) = target.dyn_into::<HtmlElement>() {
+                    let tag = el.tag_name();
+                    if tag == "INPUT" || tag == "SELECT" || tag == "TEXTAREA" {
+                        return;
+                    }
+                }
+            }
+
+            let shortcut = Shortcut::new(
+                &event.key(),
+                event.ctrl_key() || event.meta_key(),
+                event.shift_key(),
+                event.alt_key(),
+            );
+            let action = match keymap.borrow().lookup(&shortcut) {
+                Some(action) => action,
+                None => return,
+            };
+            event.prevent_default();
+            notify::report(action.name(), run(&actions, action));
+        }) as Box<dyn FnMut(_)>)
+    };
+    window.add_event_listener_with_callback("keydown", handle_keydown.as_ref().unchecked_ref())?;
+    handle_keydown.forget();
+
+    let panel = create_keymap_panel(&document, &keymap)?;
+    mount.generate.append_child(&panel)?;
+
+    Ok(())
+}
+
+fn run(actions: &Actions, action: Action) -> Result<(), JsValue> {
+    match action {
+        Action::Undo => actions.undo(),
+        Action::Redo => actions.redo(),
+        Action::Pen => actions.pen(),
+        Action::Eraser => actions.eraser(),
+        Action::SizeDown => actions.step_pen_thin(-1),
+        Action::SizeUp => actions.step_pen_thin(1),
+        Action::NewFrame => actions.add_frame(),
+        Action::PrevFrame => actions.step_frame(-1),
+        Action::NextFrame => actions.step_frame(1),
+    }
+}
+
+// editable list of shortcuts, changes are saved right away
+fn create_keymap_panel(
+    document: &Document,
+    keymap: &Rc<RefCell<Keymap>>,
+) -> Result<Element, JsValue> {
+    let details = document.create_element("details")?;
+    details.set_attribute("class", "keymap")?;
+    details.set_attribute("style", "font-size: 11px;")?;
+    let summary = document.create_element("summary")?;
+    summary.set_inner_html("shortcuts");
+    details.append_child(&summary)?;
+
+    // every input, so one that loses its shortcut to another action is emptied too
+    let inputs: Rc<RefCell<Vec<(Action, HtmlInputElement)>>> = Rc::new(RefCell::new(vec![]));
+    for action in Action::all().iter() {
+        let action = *action;
+        let row = document.create_element("label")?;
+        row.set_attribute("style", "display: flex; justify-content: space-between;")?;
+        let name = document.create_element("span")?;
+        name.set_inner_html(action.name());
+        row.append_child(&name)?;
+
+        let input = document
+            .create_element("input")?
+            .dyn_into::<HtmlInputElement>()?;
+        input.set_attribute("style", "width: 8em;")?;
+        let current = keymap.borrow().get(action).map(|s| s.to_string());
+        input.set_value(&current.unwrap_or_default());
+
+        let keymap = keymap.clone();
+        let inputs_clone = inputs.clone();
+        let input_clone = input.clone();
+        let handle_change = Closure::wrap(Box::new(move || {
+            let value = input_clone.value();
+            // an emptied input leaves the action without a shortcut
+            if value.trim().is_empty() {
+                keymap.borrow_mut().unset(action);
+                save_keymap(&keymap.borrow());
+            } else if let Some(shortcut) = Shortcut::parse(&value) {
+                let text = shortcut.to_string();
+                let taken_from = keymap.borrow_mut().set(action, shortcut);
+                if let Some(other) = taken_from {
+                    notify::warn(
+                        "shortcuts",
+                        &format!(
+                            "{} was moved from {} to {}, {} has no shortcut now",
+                            text,
+                            other.name(),
+                            action.name(),
+                            other.name()
+                        ),
+                    );
+                }
+                save_keymap(&keymap.borrow());
+            }
+            // show what is bound now, for a rejected input and a taken shortcut alike
+            for (action, input) in inputs_clone.borrow().iter() {
+                let current = keymap.borrow().get(*action).map(|s| s.to_string());
+                input.set_value(&current.unwrap_or_default());
+            }
+        }) as Box<dyn FnMut()>);
+        input.add_event_listener_with_callback("change", handle_change.as_ref().unchecked_ref())?;
+        handle_change.forget();
+
+        row.append_child(&input)?;
+        details.append_child(&row)?;
+        inputs.borrow_mut().push((action, input));
+    }
+
+    Ok(details)
+}
+
+fn local_storage() -> Option<Storage> {
+    window()?.local_storage().ok()?
+}
+
+fn load_keymap() -> Keymap {
+    local_storage()
+        .and_then(|s| s.get_item(STORAGE_KEY).ok()?)
+        .map(|s| Keymap::parse(&s))
+        .unwrap_or_default()
+}
+
+// a full or blocked storage only costs the shortcuts after a reload
+fn save_keymap(keymap: &Keymap) {
+    if let Some(storage) = local_storage() {
+        if storage.set_item(STORAGE_KEY, &keymap.serialize()).is_err() {
+            notify::warn(
+                "keyboard shortcuts",
+                "shortcuts could not be saved, they only last until the page is reloaded",
+            );
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Undo,
+    Redo,
+    Pen,
+    Eraser,
+    SizeDown,
+    SizeUp,
+    NewFrame,
+    PrevFrame,
+    NextFrame,
+}
+
+impl Action {
+    pub fn all() -> [Action; 9] {
+        [
+            Action::Undo,
+            Action::Redo,
+            Action::Pen,
+            Action::Eraser,
+            Action::SizeDown,
+            Action::SizeUp,
+            Action::NewFrame,
+            Action::PrevFrame,
+            Action::NextFrame,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Pen => "pen",
+            Action::Eraser => "eraser",
+            Action::SizeDown => "size-down",
+            Action::SizeUp => "size-up",
+            Action::NewFrame => "new-frame",
+            Action::PrevFrame => "prev-frame",
+            Action::NextFrame => "next-frame",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::all().iter().find(|a| a.name() == name).copied()
+    }
+}
+
+// a key with its modifiers, written like `ctrl+shift+z`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shortcut {
+    pub key: String,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Shortcut {
+    pub fn new(key: &str, ctrl: bool, shift: bool, alt: bool) -> Shortcut {
+        let key = key.to_lowercase();
+        // a symbol like `+` already says whether shift was needed for it on the
+        // keyboard at hand, so shift is not part of the shortcut
+        let mut chars = key.chars();
+        let symbol = match (chars.next(), chars.next()) {
+            (Some(c), None) => !c.is_alphabetic() && !c.is_whitespace(),
+            _ => false,
+        };
+        Shortcut {
+            key,
+            ctrl,
+            shift: shift && !symbol,
+            alt,
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Shortcut> {
+        let (mut ctrl, mut shift, mut alt) = (false, false, false);
+        let s = s.trim().to_lowercase();
+        // a trailing `+` is the key itself, as in `+` or `ctrl++`
+        let (modifiers, key) = match s.strip_suffix('+') {
+            Some("") => ("", "+"),
+            Some(rest) => (rest.strip_suffix('+')?, "+"),
+            None => match s.rfind('+') {
+                Some(i) => (&s[..i], &s[i + 1..]),
+                None => ("", s.as_str()),
+            },
+        };
+
+        for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+            match modifier {
+                "ctrl" | "cmd" | "meta" => ctrl = true,
+                "shift" => shift = true,
+                "alt" => alt = true,
+                _ => return None,
+            }
+        }
+        if key.is_empty() {
+            return None;
+        }
+
+        Some(Shortcut::new(key, ctrl, shift, alt))
+    }
+}
+
+impl fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "shift+")?;
+        }
+        if self.alt {
+            write!(f, "alt+")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+// one shortcut per action
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap {
+    bindings: Vec<(Action, Shortcut)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        let bindings = [
+            (Action::Undo, "ctrl+z"),
+            (Action::Redo, "ctrl+shift+z"),
+            (Action::Pen, "b"),
+            (Action::Eraser, "e"),
+            (Action::SizeDown, "["),
+            (Action::SizeUp, "]"),
+            (Action::NewFrame, "n"),
+            (Action::PrevFrame, "arrowleft"),
+            (Action::NextFrame, "arrowright"),
+        ]
+        .iter()
+        .map(|(action, s)| (*action, Shortcut::parse(s).unwrap()))
+        .collect();
+
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    pub fn lookup(&self, shortcut: &Shortcut) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, s)| s == shortcut)
+            .map(|(action, _)| *action)
+    }
+
+    pub fn get(&self, action: Action) -> Option<&Shortcut> {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, s)| s)
+    }
+
+    // a shortcut can only trigger one action, the action it is taken from is returned
+    pub fn set(&mut self, action: Action, shortcut: Shortcut) -> Option<Action> {
+        let taken_from = self.lookup(&shortcut).filter(|a| *a != action);
+        self.bindings
+            .retain(|(a, s)| *a != action && *s != shortcut);
+        self.bindings.push((action, shortcut));
+        taken_from
+    }
+
+    // the action keeps no shortcut
+    pub fn unset(&mut self, action: Action) {
+        self.bindings.retain(|(a, _)| *a != action);
+    }
+
+    // `action=shortcut` per line, the format kept in local storage.
+    // an unbound action is written as `action=`
+    pub fn serialize(&self) -> String {
+        Action::all()
+            .iter()
+            .map(|a| {
+                let shortcut = self.get(*a).map(|s| s.to_string());
+                format!("{}={}", a.name(), shortcut.unwrap_or_default())
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // unknown or broken lines are skipped so the defaults stay in place for them
+    pub fn parse(s: &str) -> Keymap {
+        let mut keymap = Keymap::default();
+        for line in s.lines() {
+            let mut parts = line.splitn(2, '=');
+            let action = match parts.next().and_then(|a| Action::from_name(a.trim())) {
+                Some(action) => action,
+                None => continue,
+            };
+            match parts.next().map(str::trim) {
+                Some("") => keymap.unset(action),
+                Some(s) => {
+                    if let Some(shortcut) = Shortcut::parse(s) {
+                        keymap.set(action, shortcut);
+                    }
+                }
+                None => {}
+            }
+        }
+
+        keymap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_modifiers_and_plus() {
+        assert_eq!(
+            Shortcut::parse("Ctrl+Shift+Z"),
+            Some(Shortcut::new("z", true, true, false))
+        );
+        assert_eq!(
+            Shortcut::parse("cmd+alt+["),
+            Some(Shortcut::new("[", true, false, true))
+        );
+        assert_eq!(
+            Shortcut::parse("+"),
+            Some(Shortcut::new("+", false, false, false))
+        );
+        assert_eq!(
+            Shortcut::parse("ctrl++"),
+            Some(Shortcut::new("+", true, false, false))
+        );
+        // shift is how `+` is typed, not part of the shortcut
+        assert_eq!(Shortcut::parse("ctrl+shift++"), Shortcut::parse("ctrl++"));
+        assert_eq!(
+            Shortcut::new("+", true, true, false),
+            Shortcut::parse("ctrl++").unwrap()
+        );
+        assert_eq!(Shortcut::parse("ctrl+"), None);
+        assert_eq!(Shortcut::parse("hyper+x"), None);
+        assert_eq!(Shortcut::parse(""), None);
+    }
+
+    #[test]
+    fn round_trips_through_storage() {
+        let mut keymap = Keymap::default();
+        keymap.set(Action::SizeUp, Shortcut::parse("ctrl++").unwrap());
+        keymap.set(Action::Pen, Shortcut::parse("shift+p").unwrap());
+
+        let stored = keymap.serialize();
+        assert!(stored.contains("size-up=ctrl++"));
+        let parsed = Keymap::parse(&stored);
+        for action in Action::all().iter() {
+            assert_eq!(parsed.get(*action), keymap.get(*action));
+        }
+        // broken lines keep their defaults
+        assert_eq!(
+            Keymap::parse("pen=hyper+x\nfoo=x\nundo=ctrl+").serialize(),
+            Keymap::default().serialize()
+        );
+    }
+
+    #[test]
+    fn unbound_actions_stay_unbound() {
+        let mut keymap = Keymap::default();
+        keymap.unset(Action::Eraser);
+        let stored = keymap.serialize();
+
+        assert!(stored.contains("eraser=\n"));
+        assert_eq!(Keymap::parse(&stored).get(Action::Eraser), None);
+        assert_eq!(
+            Keymap::parse(&stored).get(Action::Pen),
+            Shortcut::parse("b").as_ref()
+        );
+    }
+
+    #[test]
+    fn set_moves_a_taken_shortcut() {
+        let mut keymap = Keymap::default();
+        let e = Shortcut::parse("e").unwrap();
+
+        assert_eq!(keymap.set(Action::Pen, e.clone()), Some(Action::Eraser));
+        assert_eq!(keymap.lookup(&e), Some(Action::Pen));
+        assert_eq!(keymap.get(Action::Eraser), None);
+        assert_eq!(keymap.set(Action::Pen, e), None);
+    }
+}
@@ -1,15 +1,45 @@
-use std::cell::RefCell;
-use std::cmp::{max, min};
-use std::rc::Rc;
 use wasm_bindgen::prelude::*;
-use wasm_bindgen::JsCast;
-use web_sys::{window, Element, HtmlCanvasElement, HtmlElement};
-
+use web_sys::window;
+
+mod actions;
+mod atlas;
+mod autosave;
+mod budget;
+mod codec;
+mod decoder;
 mod draw;
+mod editor;
+mod encoder;
+mod error;
 mod generate;
+mod guides;
+mod hooks;
+mod icons;
+mod import;
+mod keyboard;
+mod keymap;
+mod notify;
+mod optimize;
+mod overlay;
+mod palette;
+mod presets;
+mod project;
+mod quantize;
+mod resize;
+mod ruler;
+mod save;
+mod sequence;
+mod spritesheet;
 mod state;
+mod stroke;
+mod svg;
+mod symmetry;
 mod toolbar;
+mod transform;
 mod utils;
+mod widget;
+mod worker;
+mod zip;
 
 #[cfg(feature = "wee_alloc")]
 #[global_allocator]
@@ -20,8 +50,6 @@ pub fn initialize() {
     utils::set_panic_hook();
 }
 
-static TOOLBAR_HEIGHT: u32 = 50;
-
 #[wasm_bindgen]
 pub fn run() -> Result<(), JsValue> {
     let document = window()
@@ -29,53 +57,8 @@ pub fn run() -> Result<(), JsValue> {
         .document()
         .expect("Could not find `document`");
 
-    let body = document.body().expect("Could not find `body` element");
-    let toolbar = document.get_element_by_id("toolbar").unwrap();
-    let preview = document.get_element_by_id("preview").unwrap();
-    let canvas = document.get_element_by_id("draw").unwrap();
-
-    // set canvas, preview dimention
-    let (w, h) = get_body_dimensions(&body);
-    let (pre_w, _) = get_el_dimensions(&preview);
-    let canvas: HtmlCanvasElement = canvas
-        .dyn_into::<HtmlCanvasElement>()
-        .map_err(|_| ())
-        .unwrap();
-
-    let canvas_w = w - (pre_w + 5);
-    let canvas_h = h - (TOOLBAR_HEIGHT + 100 + 5);
-
-    canvas.set_width(canvas_w);
-    canvas.set_height(canvas_h);
-
-    let preview = document.get_element_by_id("preview").unwrap();
-    let (pre_w, pre_h) = get_el_dimensions(&preview);
-    preview.set_attribute(
-        "style",
-        format!("width: {}px; height: {}px;", pre_w, pre_h).as_str(),
-    )?;
-
-    toolbar.set_attribute("style", format!("width: {}px;", canvas_w + 5).as_str())?;
-
-    let state: Rc<RefCell<state::State>> =
-        Rc::new(RefCell::new(state::State::new(canvas_w, canvas_h)));
-
-    draw::canvas_draw_start(&canvas, &state)?;
-    toolbar::init_toolbar(&toolbar, &canvas, &preview, &state)?;
-    generate::init_generate(&state)?;
+    let mount = editor::Mount::from_document(&document)?;
+    editor::start(&document, &mount, editor::Options::standalone())?;
 
     Ok(())
 }
-
-fn get_el_dimensions(el: &Element) -> (u32, u32) {
-    let width = min(max(el.client_width() as u32, 250), 1500);
-    let height = min(max(el.client_height() as u32, 450), 1800);
-
-    (width, height)
-}
-fn get_body_dimensions(body: &HtmlElement) -> (u32, u32) {
-    let width = min(max(body.client_width() as u32, 600), 3000);
-    let height = min(max(body.client_height() as u32, 400), 2000);
-
-    (width, height)
-}
@@ -0,0 +1,82 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::window;
+
+use crate::error::Error;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console, js_name = error)]
+    fn console_error(msg: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = warn)]
+    fn console_warn(msg: &str);
+}
+
+static AREA_ID: &str = "notifications";
+static SHOW_MS: i32 = 5000;
+
+// toasts stacked in a corner instead of blocking alerts, each one goes away by itself
+pub fn error(context: &str, err: &Error) {
+    console_error(&format!("{}: {}", context, err));
+    show(&err.to_string(), "toast-error");
+}
+
+// something done differently than asked, shown without failing anything
+pub fn warn(context: &str, message: &str) {
+    console_warn(&format!("{}: {}", context, message));
+    show(message, "toast-warning");
+}
+
+// log and show the error of a handler that has nowhere to return it to
+pub fn report<E: Into<Error>>(context: &str, result: Result<(), E>) {
+    if let Err(err) = result {
+        error(context, &err.into());
+    }
+}
+
+fn show(message: &str, kind: &str) {
+    // a notification that fails to show must not take the handler down with it
+    if let Err(err) = try_show(message, kind) {
+        console_error(&format!("notification: {}", Error::from(err)));
+    }
+}
+
+fn try_show(message: &str, kind: &str) -> Result<(), JsValue> {
+    let window = window().unwrap();
+    let document = window.document().expect("Could not find `document`");
+
+    let area = match document.get_element_by_id(AREA_ID) {
+        Some(area) => area,
+        None => {
+            let area = document.create_element("div")?;
+            area.set_id(AREA_ID);
+            document
+                .body()
+                .expect("Could not find `body` element")
+                .append_child(&area)?;
+            area
+        }
+    };
+
+    let toast = document.create_element("div")?;
+    toast.set_attribute("class", &format!("toast {}", kind))?;
+    toast.set_attribute("role", "status")?;
+    toast.set_text_content(Some(message));
+    area.append_child(&toast)?;
+
+    // click to dismiss early
+    {
+        let toast_clone = toast.clone();
+        let handle_click = Closure::wrap(Box::new(move || {
+            toast_clone.remove();
+        }) as Box<dyn FnMut()>);
+        toast.add_event_listener_with_callback("click", handle_click.as_ref().unchecked_ref())?;
+        handle_click.forget();
+    }
+
+    let dismiss = Closure::once_into_js(move || toast.remove());
+    window
+        .set_timeout_with_callback_and_timeout_and_arguments_0(dismiss.unchecked_ref(), SHOW_MS)?;
+
+    Ok(())
+}
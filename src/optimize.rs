@@ -0,0 +1,274 @@
+use apng::{BlendOp, DisposeOp};
+use image::{Rgba, RgbaImage};
+
+use crate::encoder::{self, FrameData};
+use crate::palette::{self, Color};
+
+static CLEAR: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Rect {
+    fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+}
+
+pub struct Optimized {
+    pub frames: Vec<FrameData>,
+    // identical consecutive frames folded into the one before them
+    pub merged: usize,
+}
+
+// apng frames that only carry what changed since the previous frame.
+// all images have to share one size, the first frame always covers the whole canvas.
+// `progress` hears of every finished frame out of the frames left after merging
+pub fn optimize(
+    images: Vec<RgbaImage>,
+    delays: &[f64],
+    palette: Option<&[Color]>,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Optimized {
+    let total = images.len();
+    let images = match palette {
+        Some(palette) => images.into_iter().map(|i| snap(i, palette)).collect(),
+        None => images,
+    };
+    let unique = dedupe(images, delays);
+    let merged = total - unique.len();
+
+    // blending over needs a way to write "keep what is there"
+    let can_blend = palette.is_none_or(|p| p.iter().any(|c| c[3] == 0));
+
+    let mut frames: Vec<FrameData> = vec![];
+    // what the output buffer holds right before the previous frame was drawn
+    let mut canvas = match unique.first() {
+        Some((img, _)) => RgbaImage::from_pixel(img.width(), img.height(), CLEAR),
+        None => return Optimized { frames, merged },
+    };
+    let mut region = Rect {
+        x: 0,
+        y: 0,
+        width: canvas.width(),
+        height: canvas.height(),
+    };
+
+    for (i, (img, delay)) in unique.iter().enumerate() {
+        if i > 0 {
+            let prev = &unique[i - 1].0;
+
+            // pick the disposal of the previous frame that leaves the least to redraw
+            let mut candidates = vec![(DisposeOp::ApngDisposeOpNone, prev.clone())];
+            let mut cleared = prev.clone();
+            fill(&mut cleared, region, CLEAR);
+            candidates.push((DisposeOp::ApngDisposeOpBackground, cleared));
+            // on the first frame "previous" is treated as "background" by decoders
+            if i > 1 {
+                candidates.push((DisposeOp::ApngDisposeOpPrevious, canvas.clone()));
+            }
+            let (dispose_op, next) = candidates
+                .into_iter()
+                .min_by_key(|(_, c)| diff_rect(c, img).map_or(0, |r| r.area()))
+                .unwrap();
+
+            frames[i - 1].dispose_op = dispose_op;
+            canvas = next;
+
+            // an unchanged canvas still needs a frame to hold the delay
+            region = diff_rect(&canvas, img).unwrap_or(Rect {
+                x: 0,
+                y: 0,
+                width: 1,
+                height: 1,
+            });
+        }
+
+        let over = i > 0 && can_blend && covers(&canvas, img, region);
+        let cropped = crop(&canvas, img, region, over);
+        let data = match palette {
+            Some(palette) => palette::index_image(&cropped, palette),
+            None => cropped.into_raw(),
+        };
+        let (delay_num, delay_den) = encoder::frame_delay(*delay);
+        frames.push(FrameData {
+            x: region.x,
+            y: region.y,
+            width: region.width,
+            height: region.height,
+            data,
+            delay_num,
+            delay_den,
+            dispose_op: DisposeOp::ApngDisposeOpNone,
+            blend_op: if over {
+                BlendOp::ApngBlendOpOver
+            } else {
+                BlendOp::ApngBlendOpSource
+            },
+        });
+        progress(i + 1, unique.len());
+    }
+
+    Optimized { frames, merged }
+}
+
+// exact palette colors, so comparing pixels matches comparing the indices written out
+fn snap(mut img: RgbaImage, palette: &[Color]) -> RgbaImage {
+    for pixel in img.pixels_mut() {
+        *pixel = Rgba(palette[palette::nearest(palette, pixel.0) as usize]);
+    }
+    img
+}
+
+// consecutive identical frames become one frame showing for their summed delay
+fn dedupe(images: Vec<RgbaImage>, delays: &[f64]) -> Vec<(RgbaImage, f64)> {
+    let mut unique: Vec<(RgbaImage, f64)> = vec![];
+    for (img, delay) in images.into_iter().zip(delays.iter()) {
+        match unique.last_mut() {
+            Some((last, sum)) if **last == *img => *sum += delay,
+            _ => unique.push((img, *delay)),
+        }
+    }
+    unique
+}
+
+// bounding box of every pixel that differs, None when the images are the same
+fn diff_rect(a: &RgbaImage, b: &RgbaImage) -> Option<Rect> {
+    let (mut min_x, mut min_y) = (u32::MAX, u32::MAX);
+    let (mut max_x, mut max_y) = (0, 0);
+    for (x, y, pixel) in b.enumerate_pixels() {
+        if a.get_pixel(x, y) != pixel {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+
+    if min_x == u32::MAX {
+        return None;
+    }
+    Some(Rect {
+        x: min_x,
+        y: min_y,
+        width: max_x - min_x + 1,
+        height: max_y - min_y + 1,
+    })
+}
+
+// blending over only reproduces the target when every changed pixel is opaque
+fn covers(canvas: &RgbaImage, img: &RgbaImage, region: Rect) -> bool {
+    pixels(region).all(|(x, y)| {
+        let pixel = img.get_pixel(x, y);
+        canvas.get_pixel(x, y) == pixel || pixel[3] == 255
+    })
+}
+
+// the region of `img`, with unchanged pixels left transparent when blending over
+fn crop(canvas: &RgbaImage, img: &RgbaImage, region: Rect, over: bool) -> RgbaImage {
+    RgbaImage::from_fn(region.width, region.height, |x, y| {
+        let (x, y) = (region.x + x, region.y + y);
+        let pixel = *img.get_pixel(x, y);
+        if over && *canvas.get_pixel(x, y) == pixel {
+            CLEAR
+        } else {
+            pixel
+        }
+    })
+}
+
+fn fill(img: &mut RgbaImage, region: Rect, color: Rgba<u8>) {
+    for (x, y) in pixels(region) {
+        img.put_pixel(x, y, color);
+    }
+}
+
+fn pixels(region: Rect) -> impl Iterator<Item = (u32, u32)> {
+    (region.y..region.y + region.height)
+        .flat_map(move |y| (region.x..region.x + region.width).map(move |x| (x, y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::decode_animation;
+
+    fn frame(w: u32, h: u32, dots: &[(u32, u32, Color)]) -> RgbaImage {
+        let mut img = RgbaImage::from_pixel(w, h, Rgba([255, 255, 255, 255]));
+        for (x, y, c) in dots {
+            img.put_pixel(*x, *y, Rgba(*c));
+        }
+        img
+    }
+
+    #[test]
+    fn crops_to_changed_pixels() {
+        let red = [255, 0, 0, 255];
+        let images = vec![frame(8, 8, &[]), frame(8, 8, &[(2, 3, red), (4, 5, red)])];
+        let optimized = optimize(images, &[0.1, 0.1], None, &mut |_, _| {});
+
+        let second = &optimized.frames[1];
+        assert_eq!(
+            (second.x, second.y, second.width, second.height),
+            (2, 3, 3, 3)
+        );
+        assert_eq!(second.blend_op as u8, BlendOp::ApngBlendOpOver as u8);
+        assert_eq!(second.data.len(), 3 * 3 * 4);
+    }
+
+    #[test]
+    fn merges_identical_frames() {
+        let red = [255, 0, 0, 255];
+        let images = vec![
+            frame(4, 4, &[]),
+            frame(4, 4, &[]),
+            frame(4, 4, &[(1, 1, red)]),
+        ];
+        let optimized = optimize(images, &[0.1, 0.2, 0.1], None, &mut |_, _| {});
+
+        assert_eq!(optimized.merged, 1);
+        assert_eq!(optimized.frames.len(), 2);
+        assert_eq!(optimized.frames[0].delay_num, 30);
+    }
+
+    #[test]
+    fn decodes_to_the_original_frames() {
+        let (red, clear) = ([255, 0, 0, 255], [0, 0, 0, 0]);
+        let images = vec![
+            frame(6, 6, &[(0, 0, red)]),
+            frame(6, 6, &[(0, 0, red), (5, 5, clear)]),
+            frame(6, 6, &[(0, 0, red)]),
+            frame(6, 6, &[(3, 2, red), (3, 3, red)]),
+        ];
+        let optimized = optimize(images.clone(), &[0.1; 4], None, &mut |_, _| {});
+        let bytes = encoder::encode_apng(6, 6, None, &optimized.frames, 0).unwrap();
+        let decoded = decode_animation(&bytes).unwrap();
+
+        assert_eq!(decoded.len(), images.len());
+        for (decoded, image) in decoded.iter().zip(images.iter()) {
+            assert_eq!(decoded.image.to_vec(), image.to_vec());
+        }
+    }
+
+    #[test]
+    fn indexed_frames_decode_to_palette_colors() {
+        let palette = &palette::DEFAULT_PALETTE[..];
+        let (black, red) = (palette[1], palette[9]);
+        let images = vec![
+            frame(5, 5, &[(1, 1, black)]),
+            frame(5, 5, &[(1, 1, red), (2, 2, [0, 0, 0, 0])]),
+        ];
+        let optimized = optimize(images.clone(), &[0.1; 2], Some(palette), &mut |_, _| {});
+        let bytes = encoder::encode_apng(5, 5, Some(palette), &optimized.frames, 0).unwrap();
+        let decoded = decode_animation(&bytes).unwrap();
+
+        // white is not in the palette and snaps to its closest entry
+        let expected = snap(images[1].clone(), palette);
+        assert_eq!(decoded[1].image.to_vec(), expected.to_vec());
+    }
+}
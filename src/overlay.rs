@@ -0,0 +1,341 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, Document, Element, HtmlCanvasElement};
+
+use crate::guides::Orientation;
+use crate::notify;
+use crate::state::State;
+use crate::transform;
+use crate::utils::get_context;
+
+pub static RULER_SIZE: u32 = 14;
+static CANVAS_MARGIN: u32 = 3; // `#draw` margin in index.css
+
+// a canvas stacked on top of the drawing canvas for guides,
+// it never receives mouse events and is never exported
+#[derive(Clone)]
+pub struct Overlay {
+    wrapper: Element,
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+    reference: HtmlCanvasElement,
+    top_ruler: HtmlCanvasElement,
+    left_ruler: HtmlCanvasElement,
+}
+
+impl Overlay {
+    pub fn new(document: &Document, canvas: &HtmlCanvasElement) -> Result<Overlay, JsValue> {
+        // wrap the drawing canvas so the overlay and rulers can be positioned around it
+        let wrapper = document.create_element("div")?;
+        wrapper.set_attribute("class", "canvas-wrapper")?;
+        wrapper.set_attribute(
+            "style",
+            format!(
+                "position: relative; align-self: flex-start; padding: {}px 0 0 {}px;",
+                RULER_SIZE, RULER_SIZE
+            )
+            .as_str(),
+        )?;
+        let parent = canvas
+            .parent_node()
+            .ok_or_else(|| JsValue::from_str("drawing canvas is not in the document"))?;
+        parent.insert_before(&wrapper, Some(canvas))?;
+        wrapper.append_child(canvas)?;
+
+        let offset = RULER_SIZE + CANVAS_MARGIN;
+        // the only layer under the drawing canvas, for tracing
+        let reference = create_layer(
+            document,
+            &wrapper,
+            "reference",
+            format!(
+                "top: {}px; left: {}px; pointer-events: none;",
+                offset, offset
+            )
+            .as_str(),
+        )?;
+        wrapper.insert_before(&reference, Some(canvas))?;
+        let overlay = create_layer(
+            document,
+            &wrapper,
+            "overlay",
+            format!(
+                "top: {}px; left: {}px; pointer-events: none;",
+                offset, offset
+            )
+            .as_str(),
+        )?;
+        let top_ruler = create_layer(
+            document,
+            &wrapper,
+            "ruler ruler-top",
+            format!("top: 0; left: {}px; cursor: s-resize;", offset).as_str(),
+        )?;
+        let left_ruler = create_layer(
+            document,
+            &wrapper,
+            "ruler ruler-left",
+            format!("top: {}px; left: 0; cursor: e-resize;", offset).as_str(),
+        )?;
+
+        let context = get_context(&overlay)?;
+
+        Ok(Overlay {
+            wrapper,
+            canvas: overlay,
+            reference,
+            context,
+            top_ruler,
+            left_ruler,
+        })
+    }
+
+    // the element around the drawing canvas and its layers
+    pub fn wrapper(&self) -> &Element {
+        &self.wrapper
+    }
+
+    pub fn top_ruler(&self) -> &HtmlCanvasElement {
+        &self.top_ruler
+    }
+
+    pub fn left_ruler(&self) -> &HtmlCanvasElement {
+        &self.left_ruler
+    }
+
+    pub fn render(&self, state: &Rc<RefCell<State>>) {
+        let state = state.borrow();
+        let zoom = state.get_zoom();
+        let (w, h) = (state.get_width() * zoom, state.get_height() * zoom);
+
+        // keep the overlay and rulers the same size as the (zoomed) document
+        resize_layer(&self.canvas, w, h);
+        resize_layer(&self.reference, w, h);
+        resize_layer(&self.top_ruler, w, RULER_SIZE);
+        resize_layer(&self.left_ruler, RULER_SIZE, h);
+        self.context.clear_rect(0.0, 0.0, w as f64, h as f64);
+
+        self.render_grid(&state);
+        self.render_guides(&state);
+        // a layer that fails to draw leaves the others and the drawing untouched
+        notify::report("overlay", self.render_symmetry(&state));
+        notify::report("overlay", self.render_rulers(&state));
+        notify::report("overlay", self.render_reference(&state));
+    }
+
+    // the drawing canvas turns transparent while there is a reference to show through it
+    fn render_reference(&self, state: &State) -> Result<(), JsValue> {
+        let context = get_context(&self.reference)?;
+        let (w, h) = (
+            self.reference.width() as f64,
+            self.reference.height() as f64,
+        );
+        context.clear_rect(0.0, 0.0, w, h);
+
+        let image = match state.get_reference() {
+            Some(image) => image,
+            None => {
+                self.wrapper.class_list().remove_1("tracing")?;
+                return Ok(());
+            }
+        };
+        self.wrapper.class_list().add_1("tracing")?;
+
+        context.set_fill_style_str("#ffffff");
+        context.fill_rect(0.0, 0.0, w, h);
+        let zoom = state.get_zoom() as f64;
+        let (x, y, fit_w, fit_h) = transform::contain(
+            image.natural_width() as f64,
+            image.natural_height() as f64,
+            state.get_width() as f64,
+            state.get_height() as f64,
+        );
+        context.save();
+        context.set_global_alpha(state.get_reference_opacity());
+        context.draw_image_with_html_image_element_and_dw_and_dh(
+            &image,
+            x * zoom,
+            y * zoom,
+            fit_w * zoom,
+            fit_h * zoom,
+        )?;
+        context.restore();
+
+        Ok(())
+    }
+
+    fn render_grid(&self, state: &State) {
+        let grid = state.get_grid();
+        if !grid.visible || grid.spacing == 0 {
+            return;
+        }
+        let zoom = state.get_zoom() as f64;
+        let (w, h) = (
+            state.get_width() as f64 * zoom,
+            state.get_height() as f64 * zoom,
+        );
+        let spacing = grid.spacing as f64 * zoom;
+
+        self.context.save();
+        self.context.set_stroke_style_str("rgba(0, 0, 0, 0.15)");
+        self.context.set_line_width(1.0);
+        self.context.begin_path();
+        let mut x = spacing;
+        while x < w {
+            self.context.move_to(x.floor() + 0.5, 0.0);
+            self.context.line_to(x.floor() + 0.5, h);
+            x += spacing;
+        }
+        let mut y = spacing;
+        while y < h {
+            self.context.move_to(0.0, y.floor() + 0.5);
+            self.context.line_to(w, y.floor() + 0.5);
+            y += spacing;
+        }
+        self.context.stroke();
+        self.context.restore();
+    }
+
+    fn render_guides(&self, state: &State) {
+        let zoom = state.get_zoom() as f64;
+        let (w, h) = (
+            state.get_width() as f64 * zoom,
+            state.get_height() as f64 * zoom,
+        );
+
+        self.context.save();
+        self.context.set_stroke_style_str("rgba(0, 188, 212, 0.9)");
+        self.context.set_line_width(1.0);
+        self.context.begin_path();
+        for guide in state.get_guides() {
+            let pos = (guide.position * zoom).floor() + 0.5;
+            match guide.orientation {
+                Orientation::Horizontal => {
+                    self.context.move_to(0.0, pos);
+                    self.context.line_to(w, pos);
+                }
+                Orientation::Vertical => {
+                    self.context.move_to(pos, 0.0);
+                    self.context.line_to(pos, h);
+                }
+            }
+        }
+        self.context.stroke();
+        self.context.restore();
+    }
+
+    fn render_symmetry(&self, state: &State) -> Result<(), JsValue> {
+        let zoom = state.get_zoom() as f64;
+        let (cx, cy) = state.get_symmetry_center();
+        let center = (cx * zoom, cy * zoom);
+        let radius = (state.get_width() as f64 * zoom).hypot(state.get_height() as f64 * zoom);
+        let axes = state.get_symmetry().axes(center, radius);
+        if axes.is_empty() {
+            return Ok(());
+        }
+
+        self.context.save();
+        self.context.set_stroke_style_str("rgba(11, 68, 239, 0.6)");
+        self.context.set_line_width(1.0);
+        self.context
+            .set_line_dash(&js_sys::Array::of2(&JsValue::from(4), &JsValue::from(4)))?;
+        self.context.begin_path();
+        for (x0, y0, x1, y1) in axes {
+            self.context.move_to(x0, y0);
+            self.context.line_to(x1, y1);
+        }
+        self.context.stroke();
+        self.context.restore();
+        Ok(())
+    }
+
+    // tick marks in document pixels and a handle for every guide that can be picked up from the ruler
+    fn render_rulers(&self, state: &State) -> Result<(), JsValue> {
+        let size = RULER_SIZE as f64;
+        let guides = state.get_guides();
+        let zoom = state.get_zoom();
+        let step = match zoom {
+            1 => 10,
+            2..=7 => 5,
+            _ => 1,
+        };
+
+        for (ruler, length, orientation) in [
+            (&self.top_ruler, state.get_width(), Orientation::Vertical),
+            (
+                &self.left_ruler,
+                state.get_height(),
+                Orientation::Horizontal,
+            ),
+        ]
+        .iter()
+        {
+            let context = get_context(ruler)?;
+            let horizontal = *orientation == Orientation::Vertical;
+            let (w, h) = if horizontal {
+                ((*length * zoom) as f64, size)
+            } else {
+                (size, (*length * zoom) as f64)
+            };
+
+            context.set_fill_style_str("#f5f5f7");
+            context.fill_rect(0.0, 0.0, w, h);
+            context.set_stroke_style_str("#9b9b9b");
+            context.set_line_width(1.0);
+            context.begin_path();
+            for i in (0..*length).step_by(step) {
+                let pos = (i * zoom) as f64 + 0.5;
+                let tick = if i % (step as u32 * 5) == 0 {
+                    size
+                } else {
+                    size / 3.0
+                };
+                if horizontal {
+                    context.move_to(pos, size);
+                    context.line_to(pos, size - tick);
+                } else {
+                    context.move_to(size, pos);
+                    context.line_to(size - tick, pos);
+                }
+            }
+            context.stroke();
+
+            context.set_fill_style_str("rgba(0, 188, 212, 0.9)");
+            for guide in guides.iter().filter(|g| g.orientation == *orientation) {
+                let pos = guide.position * zoom as f64;
+                if horizontal {
+                    context.fill_rect(pos - 2.0, 0.0, 4.0, size);
+                } else {
+                    context.fill_rect(0.0, pos - 2.0, size, 4.0);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn create_layer(
+    document: &Document,
+    wrapper: &Element,
+    class: &str,
+    style: &str,
+) -> Result<HtmlCanvasElement, JsValue> {
+    let layer = document
+        .create_element("canvas")?
+        .dyn_into::<HtmlCanvasElement>()?;
+    layer.set_attribute("class", class)?;
+    layer.set_attribute("style", format!("position: absolute; {}", style).as_str())?;
+    wrapper.append_child(&layer)?;
+
+    Ok(layer)
+}
+
+fn resize_layer(layer: &HtmlCanvasElement, w: u32, h: u32) {
+    if layer.width() != w || layer.height() != h {
+        layer.set_width(w);
+        layer.set_height(h);
+    }
+}
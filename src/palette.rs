@@ -0,0 +1,72 @@
+use image::RgbaImage;
+
+pub type Color = [u8; 4];
+
+// index 0 is always transparent, the rest is the PICO-8 palette
+// a png palette has room for this many entries, indices are a single byte
+pub static MAX_COLORS: usize = 256;
+
+pub static DEFAULT_PALETTE: [Color; 17] = [
+    [0, 0, 0, 0],
+    [0, 0, 0, 255],
+    [29, 43, 83, 255],
+    [126, 37, 83, 255],
+    [0, 135, 81, 255],
+    [171, 82, 54, 255],
+    [95, 87, 79, 255],
+    [194, 195, 199, 255],
+    [255, 241, 232, 255],
+    [255, 0, 77, 255],
+    [255, 163, 0, 255],
+    [255, 236, 39, 255],
+    [0, 228, 54, 255],
+    [41, 173, 255, 255],
+    [131, 118, 156, 255],
+    [255, 119, 168, 255],
+    [255, 204, 170, 255],
+];
+
+pub fn to_hex(c: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", c[0], c[1], c[2])
+}
+
+// `#rrggbb` as an opaque color
+pub fn from_hex(s: &str) -> Option<Color> {
+    let s = s.strip_prefix('#')?;
+    if s.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(s.get(i..i + 2)?, 16).ok();
+
+    Some([channel(0)?, channel(2)?, channel(4)?, 255])
+}
+
+// index of the closest palette entry, fully transparent pixels map to a transparent entry
+pub fn nearest(palette: &[Color], c: Color) -> u8 {
+    if c[3] == 0 {
+        if let Some(i) = palette.iter().position(|p| p[3] == 0) {
+            return i as u8;
+        }
+    }
+
+    let distance = |p: &Color| -> u32 {
+        (0..4)
+            .map(|i| {
+                let d = p[i] as i32 - c[i] as i32;
+                (d * d) as u32
+            })
+            .sum()
+    };
+
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, p)| distance(p))
+        .map(|(i, _)| i as u8)
+        .unwrap_or(0)
+}
+
+// one palette index per pixel, row by row
+pub fn index_image(img: &RgbaImage, palette: &[Color]) -> Vec<u8> {
+    img.pixels().map(|p| nearest(palette, p.0)).collect()
+}
@@ -0,0 +1,567 @@
+use image::{FilterType, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{window, Document, Element, HtmlButtonElement};
+
+use crate::budget::{self, Budget};
+use crate::decoder::{self, AnimationInfo};
+use crate::editor::Mount;
+use crate::error::Error;
+use crate::generate::{self, JobControls};
+use crate::notify;
+use crate::palette::Color;
+use crate::resize;
+use crate::state::State;
+use crate::transform;
+use crate::widget::Widget;
+use crate::worker::{Outcome, Task};
+
+// what a sticker platform accepts for an animated upload
+pub struct Preset {
+    pub name: &'static str,
+    pub max_width: u32,
+    pub max_height: u32,
+    // at least one side has to be this long
+    pub min_side: u32,
+    pub min_frames: usize,
+    pub max_frames: Option<usize>,
+    // over every loop, or a single one when looping forever
+    pub max_seconds: Option<f64>,
+    // allowed loop counts, None when looping forever is fine
+    pub plays: Option<(u32, u32)>,
+    pub max_bytes: usize,
+}
+
+pub static PRESETS: [Preset; 4] = [
+    Preset {
+        name: "LINE animated sticker",
+        max_width: 320,
+        max_height: 270,
+        min_side: 270,
+        min_frames: 5,
+        max_frames: Some(20),
+        max_seconds: Some(4.0),
+        plays: Some((1, 4)),
+        max_bytes: 300 * 1024,
+    },
+    Preset {
+        name: "Telegram sticker",
+        max_width: 512,
+        max_height: 512,
+        min_side: 512,
+        min_frames: 1,
+        max_frames: Some(90),
+        max_seconds: Some(3.0),
+        plays: None,
+        max_bytes: 256 * 1024,
+    },
+    Preset {
+        name: "Discord sticker",
+        max_width: 320,
+        max_height: 320,
+        min_side: 320,
+        min_frames: 1,
+        max_frames: None,
+        max_seconds: Some(5.0),
+        plays: None,
+        max_bytes: 512 * 1024,
+    },
+    Preset {
+        name: "Discord emoji",
+        max_width: 128,
+        max_height: 128,
+        min_side: 0,
+        min_frames: 1,
+        max_frames: None,
+        max_seconds: None,
+        plays: None,
+        max_bytes: 256 * 1024,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|p| p.name == name)
+}
+
+// the parts of an export a preset has rules for
+pub struct Animation {
+    pub width: u32,
+    pub height: u32,
+    pub frames: usize,
+    // seconds, a single loop
+    pub duration: f64,
+    // 0 loops forever
+    pub plays: u32,
+}
+
+impl Animation {
+    // read back from an encoded apng, so the rules see what is in the file
+    pub fn of_apng(apng: &[u8]) -> Result<Animation, Error> {
+        let AnimationInfo {
+            width,
+            height,
+            frames,
+            duration,
+            plays,
+        } = decoder::animation_info(apng)?;
+        Ok(Animation {
+            width,
+            height,
+            frames,
+            duration,
+            plays,
+        })
+    }
+
+    fn seconds(&self) -> f64 {
+        self.duration * self.plays.max(1) as f64
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Violation {
+    pub message: String,
+    pub fixable: bool,
+}
+
+impl Violation {
+    fn new(message: String, fixable: bool) -> Violation {
+        Violation { message, fixable }
+    }
+}
+
+// every rule the animation breaks, `bytes` is checked when the encoded size is known
+pub fn validate(preset: &Preset, animation: &Animation, bytes: Option<usize>) -> Vec<Violation> {
+    let mut violations = vec![];
+    let (w, h) = (animation.width, animation.height);
+
+    if w > preset.max_width || h > preset.max_height {
+        violations.push(Violation::new(
+            format!(
+                "{}x{} is larger than {}x{}",
+                w, h, preset.max_width, preset.max_height
+            ),
+            true,
+        ));
+    } else if w.max(h) < preset.min_side {
+        violations.push(Violation::new(
+            format!("one side has to be at least {}px", preset.min_side),
+            true,
+        ));
+    }
+
+    let frames = animation.frames;
+    if frames < preset.min_frames {
+        // a single still frame can't be stretched into an animation
+        violations.push(Violation::new(
+            format!("{} frames, needs at least {}", frames, preset.min_frames),
+            frames > 1,
+        ));
+    }
+    if let Some(max) = preset.max_frames.filter(|max| frames > *max) {
+        violations.push(Violation::new(
+            format!("{} frames, at most {}", frames, max),
+            true,
+        ));
+    }
+
+    if let Some((min, max)) = preset.plays {
+        if animation.plays == 0 {
+            violations.push(Violation::new(
+                format!("loops forever, has to loop {} to {} times", min, max),
+                true,
+            ));
+        } else if animation.plays < min || animation.plays > max {
+            violations.push(Violation::new(
+                format!(
+                    "loops {} times, has to loop {} to {} times",
+                    animation.plays, min, max
+                ),
+                true,
+            ));
+        }
+    }
+
+    if let Some(max) = preset.max_seconds {
+        let seconds = animation.seconds();
+        if seconds > max + 1e-9 {
+            violations.push(Violation::new(
+                format!("plays for {:.2}s, at most {}s", seconds, max),
+                true,
+            ));
+        }
+    }
+
+    if let Some(bytes) = bytes.filter(|b| *b > preset.max_bytes) {
+        violations.push(Violation::new(
+            format!(
+                "{}, at most {}",
+                generate::format_size(bytes),
+                generate::format_size(preset.max_bytes)
+            ),
+            true,
+        ));
+    }
+
+    violations
+}
+
+// frames, delays and loop count changed to follow the preset, file size aside
+pub fn fix(
+    preset: &Preset,
+    images: Vec<RgbaImage>,
+    delays: &[f64],
+    plays: u32,
+    pixel_art: bool,
+) -> (Vec<RgbaImage>, Vec<f64>, u32) {
+    let (mut images, mut delays) = (images, delays.to_vec());
+
+    // fit inside the limits, growing only when one side has to be longer
+    if let Some((w, h)) = images.first().map(|img| img.dimensions()) {
+        let too_large = w > preset.max_width || h > preset.max_height;
+        if too_large || w.max(h) < preset.min_side {
+            let scale =
+                (preset.max_width as f64 / w as f64).min(preset.max_height as f64 / h as f64);
+            let size = (
+                ((w as f64 * scale).round() as u32).clamp(1, preset.max_width),
+                ((h as f64 * scale).round() as u32).clamp(1, preset.max_height),
+            );
+            let filter = if pixel_art {
+                FilterType::Nearest
+            } else {
+                FilterType::Lanczos3
+            };
+            images = images
+                .iter()
+                .map(|img| transform::scale_image(img, size.0, size.1, filter))
+                .collect();
+        }
+    }
+
+    if let Some(max) = preset.max_frames.filter(|max| images.len() > *max) {
+        let (thinned, summed) = budget::thin(&images, &delays, images.len().div_ceil(max));
+        images = thinned;
+        delays = summed;
+    }
+    if images.len() > 1 && images.len() < preset.min_frames {
+        // the whole sequence played again, repeating single frames would just be merged
+        let repeat = preset.min_frames.div_ceil(images.len());
+        images = images
+            .iter()
+            .cycle()
+            .take(images.len() * repeat)
+            .cloned()
+            .collect();
+        delays = delays.repeat(repeat);
+    }
+
+    // the fewest loops, so the delays have the most room
+    let plays = match preset.plays {
+        Some((min, _)) if plays == 0 => min,
+        Some((min, max)) => plays.clamp(min, max),
+        None => plays,
+    };
+
+    if let Some(max) = preset.max_seconds {
+        // delays are stored in 1/100s, round them here so the sum is the one encoded
+        delays
+            .iter_mut()
+            .for_each(|d| *d = (*d * 100.0).round() / 100.0);
+        let seconds = delays.iter().sum::<f64>() * plays.max(1) as f64;
+        if seconds > max + 1e-9 {
+            let factor = max / seconds;
+            delays
+                .iter_mut()
+                .for_each(|d| *d = ((*d * factor * 100.0 + 1e-6).floor() / 100.0).max(0.01));
+        }
+    }
+
+    (images, delays, plays)
+}
+
+pub fn init_presets(
+    mount: &Mount,
+    state: &Rc<RefCell<State>>,
+    worker_url: &str,
+) -> Result<(), JsValue> {
+    let document = window()
+        .unwrap()
+        .document()
+        .expect("Could not find `document`");
+
+    let generate = &mount.generate;
+
+    let element = Widget::new("div")
+        .class("column")
+        .class("presets")
+        .build(&document)?;
+
+    let row = Widget::new("div").class("row").build(&document)?;
+    let names: Vec<&str> = PRESETS.iter().map(|p| p.name).collect();
+    let select = resize::create_select(&document, &names, names[0])?;
+    row.append_child(&select)?;
+
+    let violations = document.create_element("ul")?;
+    violations.set_attribute("class", "preset-violations")?;
+    violations.set_attribute("style", "margin: 4px 0;")?;
+
+    let check = create_button(&document, "check")?;
+    row.append_child(&check)?;
+    let export = create_button(&document, "fix & export")?;
+    row.append_child(&export)?;
+    let report = document.create_element("span")?;
+    report.set_attribute("class", "preset-report")?;
+    row.append_child(&report)?;
+
+    let controls = JobControls::new(
+        &document,
+        &row,
+        &[check.clone(), export.clone()],
+        &report,
+        worker_url,
+    )?;
+    for (button, fix) in [(&check, false), (&export, true)] {
+        let document = document.clone();
+        let (select, violations) = (select.clone(), violations.clone());
+        let (controls, mount, state) = (controls.clone(), mount.clone(), state.clone());
+        let context = if fix {
+            "fix & export preset"
+        } else {
+            "check preset"
+        };
+        let handle_click = Closure::wrap(Box::new(move || {
+            let preset = find(&select.value()).unwrap_or(&PRESETS[0]);
+            let (document, violations) = (document.clone(), violations.clone());
+            let (mount, state_clone) = (mount.clone(), state.clone());
+            let on_done = move |apng: Vec<u8>, outcome: Outcome| {
+                notify::report(
+                    context,
+                    show_violations(&document, &violations, &outcome.violations),
+                );
+                if fix && outcome.accepted {
+                    notify::report(
+                        context,
+                        generate::download_apng(&apng, &state_clone.borrow(), &mount),
+                    );
+                }
+            };
+            notify::report(
+                context,
+                start(&controls, context, preset, fix, &state, on_done),
+            );
+        }) as Box<dyn FnMut()>);
+        button.set_onclick(Some(handle_click.as_ref().unchecked_ref()));
+        handle_click.forget();
+    }
+
+    element.append_child(&row)?;
+    element.append_child(&violations)?;
+    generate.append_child(&element)?;
+
+    Ok(())
+}
+
+fn create_button(document: &Document, text: &str) -> Result<HtmlButtonElement, JsValue> {
+    let button = document
+        .create_element("button")?
+        .dyn_into::<HtmlButtonElement>()?;
+    button.set_inner_html(text);
+
+    Ok(button)
+}
+
+// the check, or the fix and export, of the project against `preset` on the worker
+fn start(
+    controls: &JobControls,
+    context: &'static str,
+    preset: &Preset,
+    fix: bool,
+    state: &Rc<RefCell<State>>,
+    on_done: impl FnMut(Vec<u8>, Outcome) + 'static,
+) -> Result<(), Error> {
+    let state = state.borrow();
+    let frames = generate::frame_files(&state.get_preview_image())?;
+    let task = Task::Preset {
+        delays: state.get_frame_delays(),
+        palette: if state.is_pixel_art() {
+            Some(state.get_palette())
+        } else {
+            None
+        },
+        preset: preset.name.to_string(),
+        fix,
+    };
+    controls.run(context, &task, frames, on_done);
+
+    Ok(())
+}
+
+// what `Task::Preset` runs on the worker: the project as it is, exported looping forever,
+// or with every fixable rule fixed first. what the file still breaks is reported
+pub fn run(
+    preset: &Preset,
+    images: Vec<RgbaImage>,
+    delays: &[f64],
+    palette: Option<&[Color]>,
+    fixing: bool,
+    progress: &mut dyn FnMut(&str, usize, usize),
+) -> Result<(Vec<u8>, Outcome), Error> {
+    let apng = if fixing {
+        let (images, delays, plays) = fix(preset, images, delays, 0, palette.is_some());
+        let budget = Budget {
+            bytes: preset.max_bytes,
+            width: Some(preset.max_width),
+            height: Some(preset.max_height),
+            frames: preset.max_frames,
+            min_side: Some(preset.min_side),
+        };
+        budget::fit(images, &delays, palette, &budget, plays, progress)?.apng
+    } else {
+        let encoding = &mut |done, total| progress("encoding", done, total);
+        generate::encode(images, delays, palette, true, 0, encoding)?.0
+    };
+
+    // fitting may scale and drop frames again, so the file itself is checked
+    let violations = validate(preset, &Animation::of_apng(&apng)?, Some(apng.len()));
+    let outcome = Outcome {
+        report: generate::format_size(apng.len()),
+        accepted: violations.is_empty(),
+        violations,
+    };
+    Ok((apng, outcome))
+}
+
+fn show_violations(
+    document: &Document,
+    list: &Element,
+    violations: &[Violation],
+) -> Result<(), JsValue> {
+    list.set_inner_html("");
+    if violations.is_empty() {
+        let item = document.create_element("li")?;
+        item.set_inner_html("ok");
+        list.append_child(&item)?;
+    }
+    for violation in violations {
+        let item = document.create_element("li")?;
+        let note = if violation.fixable {
+            "fixable"
+        } else {
+            "fix by hand"
+        };
+        item.set_inner_html(&format!("{} ({})", violation.message, note));
+        list.append_child(&item)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder;
+    use image::Rgba;
+
+    fn frames(count: usize, w: u32, h: u32) -> Vec<RgbaImage> {
+        (0..count)
+            .map(|i| RgbaImage::from_pixel(w, h, Rgba([(i * 10) as u8, 0, 0, 255])))
+            .collect()
+    }
+
+    #[test]
+    fn lists_each_broken_rule() {
+        let line = find("LINE animated sticker").unwrap();
+        let animation = Animation {
+            width: 640,
+            height: 480,
+            frames: 3,
+            duration: 6.0,
+            plays: 0,
+        };
+        let violations = validate(line, &animation, Some(400 * 1024));
+        let messages: Vec<&str> = violations.iter().map(|v| v.message.as_str()).collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                "640x480 is larger than 320x270",
+                "3 frames, needs at least 5",
+                "loops forever, has to loop 1 to 4 times",
+                "plays for 6.00s, at most 4s",
+                "400.0 KB, at most 300.0 KB",
+            ]
+        );
+        assert!(violations.iter().all(|v| v.fixable));
+    }
+
+    #[test]
+    fn fixed_animation_passes() {
+        let line = find("LINE animated sticker").unwrap();
+        let (images, delays, plays) = fix(line, frames(3, 640, 480), &[2.0; 3], 0, false);
+        assert_eq!(images.len(), 6);
+        assert_eq!(plays, 1);
+        let (apng, _) =
+            generate::encode(images, &delays, None, true, plays, &mut |_, _| {}).unwrap();
+        let animation = Animation::of_apng(&apng).unwrap();
+
+        assert_eq!((animation.width, animation.height), (320, 240));
+        assert_eq!(animation.plays, 1);
+        assert!(validate(line, &animation, None).is_empty());
+    }
+
+    #[test]
+    fn rounded_delays_stay_within_the_limit() {
+        // 24 frames of 1/8s add up to 3s, but each one would be stored as 0.13s
+        let telegram = find("Telegram sticker").unwrap();
+        let (_, delays, plays) = fix(telegram, frames(24, 512, 512), &[0.125; 24], 0, false);
+        let stored: u32 = delays
+            .iter()
+            .map(|d| u32::from(encoder::frame_delay(*d).0))
+            .sum();
+
+        assert_eq!(plays, 0);
+        assert!(stored <= 300);
+    }
+
+    #[test]
+    fn run_checks_the_encoded_file() {
+        let emoji = find("Discord emoji").unwrap();
+        let (_, outcome) = run(
+            emoji,
+            frames(2, 64, 64),
+            &[0.1; 2],
+            None,
+            false,
+            &mut |_, _, _| {},
+        )
+        .unwrap();
+        assert!(outcome.accepted);
+
+        let line = find("LINE animated sticker").unwrap();
+        let (_, outcome) = run(
+            line,
+            frames(2, 64, 64),
+            &[0.1; 2],
+            None,
+            false,
+            &mut |_, _, _| {},
+        )
+        .unwrap();
+        assert!(!outcome.accepted);
+        assert!(outcome
+            .violations
+            .iter()
+            .any(|v| v.message == "2 frames, needs at least 5"));
+    }
+
+    #[test]
+    fn small_images_grow_to_the_required_side() {
+        let telegram = find("Telegram sticker").unwrap();
+        let (images, _, _) = fix(telegram, frames(2, 100, 50), &[0.5; 2], 0, true);
+
+        assert_eq!(images[0].dimensions(), (512, 256));
+    }
+}
@@ -0,0 +1,371 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::codec;
+use crate::decoder;
+use crate::error::Error;
+use crate::guides::{Grid, Guide};
+use crate::palette::{self, Color};
+use crate::state::{self, State};
+use crate::stroke::Stroke;
+use crate::symmetry::Symmetry;
+use crate::transform;
+
+// bump when the schema changes and add the migration to `Project::from_json`
+pub static PROJECT_VERSION: u32 = 1;
+
+// everything needed to reopen a drawing, saved as json
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+    pub version: u32,
+    #[serde(default = "default_name")]
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub frames: Vec<ProjectFrame>,
+    pub palette: Vec<Color>,
+    pub settings: Settings,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProjectFrame {
+    // png data url, the same format the frames are kept in while editing
+    pub image: String,
+    // seconds, `None` follows `Settings::frame_speed`
+    pub delay: Option<f64>,
+    #[serde(default)]
+    pub strokes: Vec<Stroke>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub frame_speed: f64,
+    pub pen_thin: f64,
+    pub color: String,
+    pub pixel_art: bool,
+    pub symmetry: Symmetry,
+    pub grid: Grid,
+    pub guides: Vec<Guide>,
+}
+
+// projects saved before they had a name
+fn default_name() -> String {
+    state::DEFAULT_NAME.to_string()
+}
+
+// `<name>-<timestamp>`, with the name cut down to what is safe in a file name
+pub fn export_stem(name: &str, timestamp: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let name = name.trim_matches('-');
+    let name = if name.is_empty() {
+        state::DEFAULT_NAME
+    } else {
+        name
+    };
+    format!("{}-{}", name, timestamp)
+}
+
+#[derive(Debug)]
+pub enum ProjectError {
+    Json(serde_json::Error),
+    UnsupportedVersion(u32),
+    // width and height of the document or of a frame
+    Size(u32, u32),
+    // number of palette entries
+    Palette(usize),
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProjectError::Json(err) => write!(f, "broken project file: {}", err),
+            ProjectError::UnsupportedVersion(v) => {
+                write!(f, "project version {} is newer than this app", v)
+            }
+            ProjectError::Size(w, h) => write!(
+                f,
+                "{}x{} is not a usable size, sides go from 1 to {} pixels",
+                w,
+                h,
+                transform::MAX_SIDE
+            ),
+            ProjectError::Palette(n) => write!(
+                f,
+                "a palette of {} colors is not usable, it holds 1 to {}",
+                n,
+                palette::MAX_COLORS
+            ),
+        }
+    }
+}
+
+impl From<serde_json::Error> for ProjectError {
+    fn from(err: serde_json::Error) -> ProjectError {
+        ProjectError::Json(err)
+    }
+}
+
+impl Project {
+    pub fn from_state(state: &State) -> Project {
+        let frames = state
+            .get_preview_image()
+            .into_iter()
+            .zip(state.get_frame_strokes())
+            .enumerate()
+            .map(|(i, (image, strokes))| ProjectFrame {
+                image,
+                delay: state.get_frame_delay(i),
+                strokes,
+            })
+            .collect();
+
+        Project {
+            version: PROJECT_VERSION,
+            name: state.get_name(),
+            width: state.get_width(),
+            height: state.get_height(),
+            frames,
+            palette: state.get_palette(),
+            settings: Settings {
+                frame_speed: state.get_frame_speed(),
+                pen_thin: state.get_pen_thin(),
+                color: state.get_color(),
+                pixel_art: state.is_pixel_art(),
+                symmetry: state.get_symmetry(),
+                grid: state.get_grid(),
+                guides: state.get_guides(),
+            },
+        }
+    }
+
+    // everything `apply` would choke on, so a broken file is refused before the
+    // current work is replaced
+    pub fn check(&self) -> Result<(), Error> {
+        check_size(self.width, self.height)?;
+        if !(1..=palette::MAX_COLORS).contains(&self.palette.len()) {
+            return Err(ProjectError::Palette(self.palette.len()).into());
+        }
+        for (i, frame) in self.frames.iter().enumerate() {
+            let check = || -> Result<(), Error> {
+                let bytes = codec::data_url_bytes(&frame.image)?;
+                // read the header first, the size decides whether decoding is affordable
+                let info = decoder::animation_info(&bytes)?;
+                check_size(info.width, info.height)?;
+                codec::decode_png(&bytes)?;
+                Ok(())
+            };
+            check().map_err(|err| Error::frame(i, err))?;
+        }
+        Ok(())
+    }
+
+    // replace the document in `state`, the caller resizes the canvas afterwards
+    pub fn apply(self, state: &mut State) {
+        let (viewport_w, _) = state.get_viewport();
+        let zoom = if self.settings.pixel_art {
+            (viewport_w / self.width.max(1)).max(1)
+        } else {
+            1
+        };
+        state.set_name(self.name);
+        state.set_pixel_art(self.settings.pixel_art, zoom);
+        state.set_size(self.width, self.height);
+
+        state.delete_all_images();
+        for (i, frame) in self.frames.into_iter().enumerate() {
+            state.add_preview_image_with_delay(frame.image, frame.delay);
+            state.set_frame_strokes(i, frame.strokes);
+        }
+
+        state.set_palette(self.palette);
+        state.set_frame_speed(self.settings.frame_speed);
+        state.set_pen_thin(self.settings.pen_thin);
+        state.set_color(self.settings.color);
+        state.set_symmetry(self.settings.symmetry);
+        state.set_grid(self.settings.grid);
+        state.set_guides(self.settings.guides);
+    }
+
+    pub fn to_json(&self) -> Result<String, ProjectError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_json(s: &str) -> Result<Project, ProjectError> {
+        // read the version first so older layouts can be migrated before parsing
+        #[derive(Deserialize)]
+        struct Versioned {
+            version: u32,
+        }
+        let versioned: Versioned = serde_json::from_str(s)?;
+        if versioned.version > PROJECT_VERSION {
+            return Err(ProjectError::UnsupportedVersion(versioned.version));
+        }
+
+        Ok(serde_json::from_str(s)?)
+    }
+}
+
+fn check_size(width: u32, height: u32) -> Result<(), Error> {
+    let side = 1..=transform::MAX_SIDE;
+    if side.contains(&width) && side.contains(&height) {
+        Ok(())
+    } else {
+        Err(ProjectError::Size(width, height).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guides::Orientation;
+
+    fn sample_state() -> State {
+        let mut state = State::new(320, 240);
+        state.add_preview_image("data:image/png;base64,AAAA".to_string());
+        state.add_preview_image_with_delay("data:image/png;base64,BBBB".to_string(), Some(0.5));
+        let mut stroke = Stroke::new((1.0, 2.0), "#ff004d", 4.0, false, false);
+        stroke.points.push((10.0, 12.0));
+        state.set_frame_strokes(1, vec![stroke]);
+        state.set_frame_speed(0.2);
+        state.set_pen_thin(8.0);
+        state.set_color("#ff004d".to_string());
+        state.set_symmetry(Symmetry::Radial(6));
+        state.set_grid(Grid {
+            visible: true,
+            spacing: 8,
+            snap: true,
+        });
+        state.add_guide(Guide {
+            orientation: Orientation::Vertical,
+            position: 42.0,
+        });
+        state
+    }
+
+    #[test]
+    fn round_trip_json() {
+        let project = Project::from_state(&sample_state());
+        let json = project.to_json().unwrap();
+        let loaded = Project::from_json(&json).unwrap();
+
+        assert_eq!(project, loaded);
+        assert_eq!(loaded.version, PROJECT_VERSION);
+        assert_eq!(loaded.frames[0].delay, None);
+        assert_eq!(loaded.frames[1].delay, Some(0.5));
+    }
+
+    #[test]
+    fn round_trip_state() {
+        let project = Project::from_state(&sample_state());
+        let json = project.to_json().unwrap();
+
+        let mut state = State::new(640, 480);
+        Project::from_json(&json).unwrap().apply(&mut state);
+
+        assert_eq!(Project::from_state(&state), project);
+        assert_eq!(state.get_frame_delays(), vec![0.2, 0.5]);
+    }
+
+    #[test]
+    fn pixel_art_zoom_follows_viewport() {
+        let mut project = Project::from_state(&sample_state());
+        project.width = 32;
+        project.height = 24;
+        project.settings.pixel_art = true;
+
+        let mut state = State::new(640, 480);
+        project.apply(&mut state);
+
+        assert!(state.is_pixel_art());
+        assert_eq!(state.get_zoom(), 20);
+        assert_eq!((state.get_width(), state.get_height()), (32, 24));
+    }
+
+    #[test]
+    fn rejects_newer_version() {
+        let mut project = Project::from_state(&sample_state());
+        project.version = PROJECT_VERSION + 1;
+        let json = serde_json::to_string(&project).unwrap();
+
+        match Project::from_json(&json) {
+            Err(ProjectError::UnsupportedVersion(v)) => assert_eq!(v, PROJECT_VERSION + 1),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn older_projects_get_the_default_name() {
+        let mut json: serde_json::Value =
+            serde_json::from_str(&Project::from_state(&sample_state()).to_json().unwrap()).unwrap();
+        json.as_object_mut().unwrap().remove("name");
+        let project = Project::from_json(&json.to_string()).unwrap();
+
+        assert_eq!(project.name, state::DEFAULT_NAME);
+    }
+
+    #[test]
+    fn export_names_are_file_safe() {
+        assert_eq!(
+            export_stem("my cat/walk", "20261019-153000"),
+            "my-cat-walk-20261019-153000"
+        );
+        assert_eq!(
+            export_stem(" ?? ", "20261019-153000"),
+            "drawasm-20261019-153000"
+        );
+    }
+
+    #[test]
+    fn check_refuses_broken_frames_and_sizes() {
+        let frame = |width, height| ProjectFrame {
+            image: codec::encode_data_url(&image::RgbaImage::new(width, height)).unwrap(),
+            delay: None,
+            strokes: vec![],
+        };
+        let mut project = Project::from_state(&sample_state());
+        project.frames = vec![frame(320, 240), frame(16, 16)];
+        assert!(project.check().is_ok());
+
+        project.width = 0;
+        assert!(matches!(
+            project.check(),
+            Err(Error::Project(ProjectError::Size(0, 240)))
+        ));
+        project.width = transform::MAX_SIDE + 1;
+        assert!(project.check().is_err());
+
+        project.width = 320;
+        let colors = project.palette.clone();
+        project.palette = vec![];
+        assert!(matches!(
+            project.check(),
+            Err(Error::Project(ProjectError::Palette(0)))
+        ));
+        project.palette = vec![[0, 0, 0, 255]; palette::MAX_COLORS + 1];
+        assert!(project.check().is_err());
+        project.palette = colors;
+
+        project.frames[1].image = "data:image/png;base64,AAAA".to_string();
+        assert!(matches!(project.check(), Err(Error::Frame(1, _))));
+        project.frames[1] = frame(transform::MAX_SIDE + 1, 1);
+        assert!(matches!(project.check(), Err(Error::Frame(1, _))));
+    }
+
+    #[test]
+    fn rejects_broken_json() {
+        assert!(matches!(
+            Project::from_json("{\"version\": 1}"),
+            Err(ProjectError::Json(_))
+        ));
+    }
+}
@@ -0,0 +1,191 @@
+use image::{Rgba, RgbaImage};
+use std::collections::HashMap;
+
+use crate::palette::{self, Color};
+
+// one shared palette for every frame, built by median cut over the colors of all of them.
+// fully transparent pixels get an entry of their own and count against `max_colors`
+pub fn median_cut(images: &[RgbaImage], max_colors: usize) -> Vec<Color> {
+    let mut histogram: HashMap<Color, u32> = HashMap::new();
+    let mut transparent = false;
+    for pixel in images.iter().flat_map(|img| img.pixels()) {
+        if pixel[3] == 0 {
+            transparent = true;
+        } else {
+            *histogram.entry(pixel.0).or_insert(0) += 1;
+        }
+    }
+
+    let mut palette = vec![];
+    if transparent {
+        palette.push([0, 0, 0, 0]);
+    }
+    let max_colors = max_colors.clamp(2, 256) - palette.len();
+
+    let mut boxes = vec![histogram.into_iter().collect::<Vec<_>>()];
+    boxes.retain(|b| !b.is_empty());
+    while boxes.len() < max_colors {
+        // split the box spanning the widest channel range
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .max_by_key(|(_, b)| widest_channel(b).1);
+        let index = match widest {
+            Some((i, _)) => i,
+            None => break,
+        };
+
+        let mut colors = boxes.swap_remove(index);
+        let (channel, _) = widest_channel(&colors);
+        colors.sort_by_key(|(c, _)| c[channel]);
+
+        // at the weighted median, keeping at least one color on each side
+        let total: u64 = colors.iter().map(|(_, n)| *n as u64).sum();
+        let mut seen = 0;
+        let mut split = 1;
+        for (i, (_, n)) in colors.iter().enumerate() {
+            seen += *n as u64;
+            if seen * 2 >= total {
+                split = (i + 1).clamp(1, colors.len() - 1);
+                break;
+            }
+        }
+        let rest = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(rest);
+    }
+
+    palette.extend(boxes.iter().map(|b| average(b)));
+    palette
+}
+
+fn widest_channel(colors: &[(Color, u32)]) -> (usize, u8) {
+    (0..4)
+        .map(|channel| {
+            let values = colors.iter().map(|(c, _)| c[channel]);
+            let range = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+            (channel, range)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap()
+}
+
+fn average(colors: &[(Color, u32)]) -> Color {
+    let total: u64 = colors.iter().map(|(_, n)| *n as u64).sum();
+    let mut color = [0; 4];
+    for (channel, value) in color.iter_mut().enumerate() {
+        let sum: u64 = colors
+            .iter()
+            .map(|(c, n)| c[channel] as u64 * *n as u64)
+            .sum();
+        *value = ((sum + total / 2) / total) as u8;
+    }
+    color
+}
+
+// every pixel replaced by its palette color, with floyd-steinberg error diffusion when dithering
+pub fn remap(img: &RgbaImage, palette: &[Color], dither: bool) -> RgbaImage {
+    let mut cache: HashMap<Color, Color> = HashMap::new();
+    let mut closest = |c: Color| -> Color {
+        *cache
+            .entry(c)
+            .or_insert_with(|| palette[palette::nearest(palette, c) as usize])
+    };
+
+    if !dither {
+        let mut out = img.clone();
+        for pixel in out.pixels_mut() {
+            *pixel = Rgba(closest(pixel.0));
+        }
+        return out;
+    }
+
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let mut error = vec![[0.0_f32; 4]; w * 2];
+    let mut out = RgbaImage::new(img.width(), img.height());
+    for y in 0..h {
+        // two rows of carried error, the current and the next one
+        let (current, next) = error.split_at_mut(w);
+        for x in 0..w {
+            let pixel = img.get_pixel(x as u32, y as u32).0;
+            // transparency stays exact, diffusing into it would speckle the background
+            if pixel[3] == 0 {
+                out.put_pixel(x as u32, y as u32, Rgba(closest(pixel)));
+                continue;
+            }
+
+            let mut wanted = [0; 4];
+            for i in 0..4 {
+                wanted[i] = (pixel[i] as f32 + current[x][i]).round().clamp(0.0, 255.0) as u8;
+            }
+            let got = closest(wanted);
+            out.put_pixel(x as u32, y as u32, Rgba(got));
+
+            for i in 0..4 {
+                let e = wanted[i] as f32 - got[i] as f32;
+                if x + 1 < w {
+                    current[x + 1][i] += e * 7.0 / 16.0;
+                    next[x + 1][i] += e / 16.0;
+                }
+                if x > 0 {
+                    next[x - 1][i] += e * 3.0 / 16.0;
+                }
+                next[x][i] += e * 5.0 / 16.0;
+            }
+        }
+        current.copy_from_slice(next);
+        next.iter_mut().for_each(|e| *e = [0.0; 4]);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(w: u32, h: u32) -> RgbaImage {
+        RgbaImage::from_fn(w, h, |x, y| {
+            Rgba([
+                (x * 255 / (w - 1)) as u8,
+                (y * 255 / (h - 1)) as u8,
+                128,
+                255,
+            ])
+        })
+    }
+
+    #[test]
+    fn palette_stays_within_max_colors() {
+        let palette = median_cut(&[gradient(32, 32)], 16);
+
+        assert_eq!(palette.len(), 16);
+        assert!(palette.iter().all(|c| c[3] == 255));
+    }
+
+    #[test]
+    fn keeps_few_colors_as_they_are() {
+        let mut img = RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 255]));
+        img.put_pixel(0, 0, Rgba([200, 100, 0, 255]));
+        img.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+        let mut palette = median_cut(&[img], 256);
+        palette.sort();
+
+        assert_eq!(
+            palette,
+            vec![[0, 0, 0, 0], [10, 20, 30, 255], [200, 100, 0, 255]]
+        );
+    }
+
+    #[test]
+    fn remapped_pixels_come_from_the_palette() {
+        let img = gradient(16, 16);
+        let palette = median_cut(std::slice::from_ref(&img), 8);
+
+        for dither in [false, true] {
+            let out = remap(&img, &palette, dither);
+            assert!(out.pixels().all(|p| palette.contains(&p.0)));
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use image::RgbaImage;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    window, Document, Element, HtmlButtonElement, HtmlCanvasElement, HtmlInputElement,
+    HtmlSelectElement,
+};
+
+use crate::codec;
+use crate::editor::Mount;
+use crate::error::Error;
+use crate::generate;
+use crate::notify;
+use crate::overlay::Overlay;
+use crate::state::State;
+use crate::toolbar;
+use crate::transform::{self, Anchor};
+use crate::utils;
+use crate::widget::Widget;
+
+pub fn init_resize(
+    mount: &Mount,
+    overlay: &Overlay,
+    state: &Rc<RefCell<State>>,
+) -> Result<(), JsValue> {
+    let document = window()
+        .unwrap()
+        .document()
+        .expect("Could not find `document`");
+
+    let generate = &mount.generate;
+    let (canvas, preview) = (&mount.canvas, &mount.preview);
+
+    let element = Widget::new("div")
+        .class("row")
+        .class("resize")
+        .build(&document)?;
+
+    let width = create_size_input(&document, state.borrow().get_width())?;
+    element.append_child(&width)?;
+    let height = create_size_input(&document, state.borrow().get_height())?;
+    element.append_child(&height)?;
+
+    let anchors: Vec<&str> = Anchor::all().iter().map(|a| a.name()).collect();
+    let anchor = create_select(&document, &anchors, "center")?;
+    element.append_child(&anchor)?;
+
+    let filters: Vec<&str> = transform::FILTERS.iter().map(|(n, _)| *n).collect();
+    let filter = create_select(&document, &filters, "lanczos")?;
+    element.append_child(&filter)?;
+
+    // resize canvas
+    {
+        let (width, height, anchor) = (width.clone(), height.clone(), anchor.clone());
+        let button = create_button(
+            &document,
+            "resize canvas",
+            canvas,
+            overlay,
+            preview,
+            state,
+            move |_| {
+                let (w, h) = (parse_size(&width)?, parse_size(&height)?);
+                let anchor = match Anchor::from_name(&anchor.value()) {
+                    Some(anchor) => anchor,
+                    None => return Ok(None),
+                };
+                Ok(Some(Box::new(move |img: &RgbaImage| {
+                    transform::resize_canvas(img, w, h, anchor)
+                })))
+            },
+        )?;
+        element.append_child(&button)?;
+    }
+
+    // scale image
+    {
+        let (width, height, filter) = (width.clone(), height.clone(), filter.clone());
+        let button = create_button(
+            &document,
+            "scale image",
+            canvas,
+            overlay,
+            preview,
+            state,
+            move |_| {
+                let (w, h) = (parse_size(&width)?, parse_size(&height)?);
+                let filter = match transform::filter_from_name(&filter.value()) {
+                    Some(filter) => filter,
+                    None => return Ok(None),
+                };
+                Ok(Some(Box::new(move |img: &RgbaImage| {
+                    transform::scale_image(img, w, h, filter)
+                })))
+            },
+        )?;
+        element.append_child(&button)?;
+    }
+
+    // auto crop
+    {
+        let button = create_button(
+            &document,
+            "auto crop",
+            canvas,
+            overlay,
+            preview,
+            state,
+            |images| {
+                Ok(transform::content_bounds(images).map(|rect| {
+                    Box::new(move |img: &RgbaImage| transform::crop(img, rect)) as FrameOp
+                }))
+            },
+        )?;
+        element.append_child(&button)?;
+    }
+
+    generate.append_child(&element)?;
+
+    Ok(())
+}
+
+pub type FrameOp = Box<dyn Fn(&RgbaImage) -> RgbaImage>;
+
+// button that applies one operation to every frame and to the canvas itself,
+// `make_op` sees all frames and returns None when there is nothing to do
+fn create_button<F>(
+    document: &Document,
+    label: &str,
+    canvas: &HtmlCanvasElement,
+    overlay: &Overlay,
+    preview: &Element,
+    state: &Rc<RefCell<State>>,
+    make_op: F,
+) -> Result<HtmlButtonElement, JsValue>
+where
+    F: Fn(&[RgbaImage]) -> Result<Option<FrameOp>, Error> + 'static,
+{
+    let button = document
+        .create_element("button")?
+        .dyn_into::<HtmlButtonElement>()?;
+    button.set_inner_html(label);
+
+    let context = label.to_string();
+    let document = document.clone();
+    let canvas = canvas.clone();
+    let overlay = overlay.clone();
+    let preview = preview.clone();
+    let state = state.clone();
+
+    let handle_click = Closure::wrap(Box::new(move || {
+        let images = generate::decode_frames(&state.borrow().get_preview_image());
+        let result = images
+            .and_then(|images| make_op(&images))
+            .map_err(JsValue::from)
+            .and_then(|op| match op {
+                Some(op) => apply_to_document(&document, &canvas, &overlay, &preview, &state, &op),
+                None => Ok(()),
+            });
+        notify::report(&context, result);
+    }) as Box<dyn FnMut()>);
+
+    button.set_onclick(Some(handle_click.as_ref().unchecked_ref()));
+    handle_click.forget();
+
+    Ok(button)
+}
+
+// transform every frame and the canvas, then refresh everything that depends on the size
+pub fn apply_to_document(
+    document: &Document,
+    canvas: &HtmlCanvasElement,
+    overlay: &Overlay,
+    preview: &Element,
+    state: &Rc<RefCell<State>>,
+    op: &FrameOp,
+) -> Result<(), JsValue> {
+    let mut urls = vec![];
+    for data in state.borrow().get_preview_image().iter() {
+        let img = codec::decode_data_url(data)?;
+        urls.push(codec::encode_data_url(&op(&img))?);
+    }
+    state.borrow_mut().set_preview_image(urls);
+
+    apply_to_canvas(canvas, state, op)?;
+    overlay.render(state);
+    toolbar::render_preview(document, preview, state)?;
+
+    let hooks = state.borrow().get_hooks();
+    hooks.changed();
+    Ok(())
+}
+
+fn apply_to_canvas(
+    canvas: &HtmlCanvasElement,
+    state: &Rc<RefCell<State>>,
+    op: &FrameOp,
+) -> Result<(), JsValue> {
+    let context = utils::get_context(canvas)?;
+
+    let image_data = context.get_image_data(
+        0.0,
+        0.0,
+        state.borrow().get_width() as f64,
+        state.borrow().get_height() as f64,
+    )?;
+    let current = codec::image_data_to_rgba(&image_data)
+        .ok_or_else(|| JsValue::from_str("canvas pixels do not match its size"))?;
+    let changed = op(&current);
+
+    // resizing the canvas element clears it, so the transformed picture is put back after
+    state
+        .borrow_mut()
+        .set_size(changed.width(), changed.height());
+    fit_canvas(canvas, state)?;
+    context.put_image_data(&codec::rgba_to_image_data(&changed)?, 0.0, 0.0)?;
+
+    Ok(())
+}
+
+// size the canvas element to the document, this clears the canvas
+pub fn fit_canvas(canvas: &HtmlCanvasElement, state: &Rc<RefCell<State>>) -> Result<(), JsValue> {
+    let state = state.borrow();
+    let (w, h, zoom) = (state.get_width(), state.get_height(), state.get_zoom());
+
+    canvas.set_width(w);
+    canvas.set_height(h);
+    canvas.set_attribute(
+        "style",
+        format!(
+            "width: {}px; height: {}px; image-rendering: {};",
+            w * zoom,
+            h * zoom,
+            if state.is_pixel_art() {
+                "pixelated"
+            } else {
+                "auto"
+            }
+        )
+        .as_str(),
+    )
+}
+
+fn create_size_input(document: &Document, value: u32) -> Result<HtmlInputElement, JsValue> {
+    let input = document
+        .create_element("input")?
+        .dyn_into::<HtmlInputElement>()?;
+    input.set_attribute("type", "number")?;
+    input.set_attribute("min", "1")?;
+    input.set_attribute("max", &transform::MAX_SIDE.to_string())?;
+    input.set_attribute("style", "width: 5em;")?;
+    input.set_value(format!("{}", value).as_str());
+
+    Ok(input)
+}
+
+pub fn create_select(
+    document: &Document,
+    options: &[&str],
+    selected: &str,
+) -> Result<HtmlSelectElement, JsValue> {
+    let select = document
+        .create_element("select")?
+        .dyn_into::<HtmlSelectElement>()?;
+
+    for name in options {
+        let option = document.create_element("option")?;
+        option.set_attribute("value", name)?;
+        option.set_inner_html(name);
+        select.append_child(&option)?;
+    }
+    select.set_value(selected);
+
+    Ok(select)
+}
+
+fn parse_size(input: &HtmlInputElement) -> Result<u32, Error> {
+    match input.value().parse() {
+        Ok(v) if v > 0 && v <= transform::MAX_SIDE => Ok(v),
+        _ => Err(Error::Input(format!(
+            "sizes go from 1 to {} pixels",
+            transform::MAX_SIDE
+        ))),
+    }
+}
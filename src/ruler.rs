@@ -0,0 +1,149 @@
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{window, HtmlCanvasElement, MouseEvent};
+
+use crate::guides::{Guide, Orientation};
+use crate::overlay::Overlay;
+use crate::state::State;
+
+// how close (in pixels) a click on the ruler has to be to grab an existing guide
+static PICK_DISTANCE: f64 = 4.0;
+
+// dragging from the top ruler pulls out a horizontal guide and from the left ruler a vertical one,
+// existing guides are grabbed by their handle on the ruler and dropped off the canvas to delete
+pub fn init_rulers(
+    canvas: &HtmlCanvasElement,
+    overlay: &Overlay,
+    state: &Rc<RefCell<State>>,
+) -> Result<(), JsValue> {
+    let dragging: Rc<Cell<Option<usize>>> = Rc::new(Cell::new(None));
+
+    for (ruler, new_orientation, picked_orientation) in [
+        (
+            overlay.top_ruler(),
+            Orientation::Horizontal,
+            Orientation::Vertical,
+        ),
+        (
+            overlay.left_ruler(),
+            Orientation::Vertical,
+            Orientation::Horizontal,
+        ),
+    ]
+    .iter()
+    {
+        let (new_orientation, picked_orientation) = (*new_orientation, *picked_orientation);
+        let state = state.clone();
+        let dragging = dragging.clone();
+
+        let mouse_down = Closure::wrap(Box::new(move |event: MouseEvent| {
+            event.prevent_default();
+            let zoom = state.borrow().get_zoom() as f64;
+            let along = match picked_orientation {
+                Orientation::Vertical => event.offset_x() as f64 / zoom,
+                Orientation::Horizontal => event.offset_y() as f64 / zoom,
+            };
+
+            let picked = state.borrow().get_guides().iter().position(|g| {
+                g.orientation == picked_orientation && (g.position - along).abs() <= PICK_DISTANCE
+            });
+
+            let index = match picked {
+                Some(index) => index,
+                None => state.borrow_mut().add_guide(Guide {
+                    orientation: new_orientation,
+                    position: 0.0,
+                }),
+            };
+            dragging.set(Some(index));
+        }) as Box<dyn FnMut(_)>);
+
+        ruler.add_event_listener_with_callback("mousedown", mouse_down.as_ref().unchecked_ref())?;
+        mouse_down.forget();
+    }
+
+    let window = window().unwrap();
+
+    // mousemove
+    {
+        let canvas = canvas.clone();
+        let overlay = overlay.clone();
+        let state = state.clone();
+        let dragging = dragging.clone();
+
+        let mouse_move = Closure::wrap(Box::new(move |event: MouseEvent| {
+            let index = match dragging.get() {
+                Some(index) => index,
+                None => return,
+            };
+            // the guides were replaced under the drag, e.g. by opening a project
+            let position = match guide_position(&canvas, &state, index, &event) {
+                Some(position) => position,
+                None => {
+                    dragging.set(None);
+                    return;
+                }
+            };
+            let position = state.borrow().get_grid().snap_value(position);
+            state.borrow_mut().move_guide(index, position);
+            overlay.render(&state);
+        }) as Box<dyn FnMut(_)>);
+
+        window
+            .add_event_listener_with_callback("mousemove", mouse_move.as_ref().unchecked_ref())?;
+        mouse_move.forget();
+    }
+
+    // mouseup
+    {
+        let canvas = canvas.clone();
+        let overlay = overlay.clone();
+        let state = state.clone();
+
+        let mouse_up = Closure::wrap(Box::new(move |event: MouseEvent| {
+            let index = match dragging.take() {
+                Some(index) => index,
+                None => return,
+            };
+
+            let guide = state.borrow().get_guides().get(index).copied();
+            let (position, guide) = match (guide_position(&canvas, &state, index, &event), guide) {
+                (Some(position), Some(guide)) => (position, guide),
+                _ => return,
+            };
+            let limit = match guide.orientation {
+                Orientation::Horizontal => state.borrow().get_height(),
+                Orientation::Vertical => state.borrow().get_width(),
+            };
+            if position < 0.0 || position > limit as f64 {
+                state.borrow_mut().remove_guide(index);
+            }
+            overlay.render(&state);
+        }) as Box<dyn FnMut(_)>);
+
+        window.add_event_listener_with_callback("mouseup", mouse_up.as_ref().unchecked_ref())?;
+        mouse_up.forget();
+    }
+
+    Ok(())
+}
+
+// mouse position along the axis the guide moves on, in document pixels.
+// `None` once the guide is gone
+fn guide_position(
+    canvas: &HtmlCanvasElement,
+    state: &Rc<RefCell<State>>,
+    index: usize,
+    event: &MouseEvent,
+) -> Option<f64> {
+    let rect = canvas.get_bounding_client_rect();
+    let zoom = state.borrow().get_zoom() as f64;
+    let orientation = state.borrow().get_guides().get(index)?.orientation;
+    Some(match orientation {
+        Orientation::Horizontal => (event.client_y() as f64 - rect.top()) / zoom,
+        Orientation::Vertical => (event.client_x() as f64 - rect.left()) / zoom,
+    })
+}
@@ -0,0 +1,360 @@
+use image::RgbaImage;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    window, Blob, BlobPropertyBag, Document, Element, FileReader, HtmlAnchorElement,
+    HtmlButtonElement, HtmlCanvasElement, HtmlInputElement, Url,
+};
+
+use crate::actions::Actions;
+use crate::codec;
+use crate::decoder;
+use crate::editor::Mount;
+use crate::error::Error;
+use crate::hooks::Export;
+use crate::notify;
+use crate::overlay::Overlay;
+use crate::project::{self, Project, ProjectFrame};
+use crate::resize;
+use crate::state::{State, DEFAULT_NAME};
+use crate::widget::Widget;
+
+static NAME_CLASS: &str = "project-name";
+// browsers that start a download asynchronously need the object url a while longer
+static REVOKE_MS: i32 = 40_000;
+
+pub fn init_save(
+    mount: &Mount,
+    overlay: &Overlay,
+    actions: &Actions,
+    state: &Rc<RefCell<State>>,
+) -> Result<(), JsValue> {
+    let document = window()
+        .unwrap()
+        .document()
+        .expect("Could not find `document`");
+
+    let generate = &mount.generate;
+    let canvas = &mount.canvas;
+
+    let element = Widget::new("div")
+        .class("row")
+        .class("project")
+        .build(&document)?;
+
+    let name = create_name_input(&document, state)?;
+    element.append_child(&name)?;
+
+    let save = create_save_button(&document, state)?;
+    element.append_child(&save)?;
+
+    let open = create_open_input(&document, canvas, overlay, actions, state)?;
+    element.append_child(&open)?;
+
+    let import = create_import_input(&document, canvas, overlay, actions, state)?;
+    element.append_child(&import)?;
+
+    generate.append_child(&element)?;
+
+    Ok(())
+}
+
+fn create_name_input(
+    document: &Document,
+    state: &Rc<RefCell<State>>,
+) -> Result<HtmlInputElement, JsValue> {
+    let input = document
+        .create_element("input")?
+        .dyn_into::<HtmlInputElement>()?;
+    input.set_attribute("type", "text")?;
+    input.set_attribute("class", NAME_CLASS)?;
+    input.set_attribute("placeholder", DEFAULT_NAME)?;
+    input.set_attribute("title", "project name, exports are named after it")?;
+    input.set_attribute("style", "width: 8em;")?;
+    input.set_value(&state.borrow().get_name());
+
+    let state = state.clone();
+    let input_clone = input.clone();
+    let handle_input = Closure::wrap(Box::new(move || {
+        state.borrow_mut().set_name(input_clone.value());
+    }) as Box<dyn FnMut()>);
+    input.add_event_listener_with_callback("input", handle_input.as_ref().unchecked_ref())?;
+    handle_input.forget();
+
+    Ok(input)
+}
+
+fn create_save_button(
+    document: &Document,
+    state: &Rc<RefCell<State>>,
+) -> Result<HtmlButtonElement, JsValue> {
+    let button = document
+        .create_element("button")?
+        .dyn_into::<HtmlButtonElement>()?;
+    button.set_inner_html("save project");
+
+    let document = document.clone();
+    let state = state.clone();
+    let handle_click = Closure::wrap(Box::new(move || {
+        notify::report("save project", save_project(&document, &state));
+    }) as Box<dyn FnMut()>);
+
+    button.set_onclick(Some(handle_click.as_ref().unchecked_ref()));
+    handle_click.forget();
+
+    Ok(button)
+}
+
+fn save_project(document: &Document, state: &Rc<RefCell<State>>) -> Result<(), Error> {
+    let state = state.borrow();
+    let json = Project::from_state(&state).to_json()?;
+    Ok(deliver(
+        document,
+        &state,
+        &Export::of_document(
+            &state,
+            "project",
+            format!("{}-project.json", export_stem(&state)),
+            "application/json",
+            json.as_bytes(),
+        ),
+    )?)
+}
+
+fn create_open_input(
+    document: &Document,
+    canvas: &HtmlCanvasElement,
+    overlay: &Overlay,
+    actions: &Actions,
+    state: &Rc<RefCell<State>>,
+) -> Result<Element, JsValue> {
+    let (label, input) = create_file_label(document, "open project", ".json,application/json")?;
+
+    // one reader is reused for every file that gets picked
+    let reader = FileReader::new()?;
+    {
+        let reader_clone = reader.clone();
+        let canvas = canvas.clone();
+        let overlay = overlay.clone();
+        let actions = actions.clone();
+        let state = state.clone();
+
+        let handle_load = Closure::wrap(Box::new(move || {
+            let text = reader_clone
+                .result()
+                .ok()
+                .and_then(|r| r.as_string())
+                .unwrap_or_default();
+            let result = Project::from_json(&text)
+                .map_err(Error::from)
+                .and_then(|project| {
+                    Ok(load_project(project, &canvas, &overlay, &actions, &state)?)
+                });
+            notify::report("open project", result);
+        }) as Box<dyn FnMut()>);
+        reader.set_onload(Some(handle_load.as_ref().unchecked_ref()));
+        handle_load.forget();
+    }
+
+    let input_clone = input.clone();
+    let handle_change = Closure::wrap(Box::new(move || {
+        if let Some(file) = input_clone.files().and_then(|files| files.get(0)) {
+            notify::report("open project", reader.read_as_text(&file));
+        }
+        // allow picking the same file again
+        input_clone.set_value("");
+    }) as Box<dyn FnMut()>);
+    input.add_event_listener_with_callback("change", handle_change.as_ref().unchecked_ref())?;
+    handle_change.forget();
+
+    Ok(label)
+}
+
+// apng or gif, replaces the document with its frames
+fn create_import_input(
+    document: &Document,
+    canvas: &HtmlCanvasElement,
+    overlay: &Overlay,
+    actions: &Actions,
+    state: &Rc<RefCell<State>>,
+) -> Result<Element, JsValue> {
+    let (label, input) = create_file_label(document, "import animation", ".png,.apng,.gif")?;
+
+    let reader = FileReader::new()?;
+    {
+        let reader_clone = reader.clone();
+        let canvas = canvas.clone();
+        let overlay = overlay.clone();
+        let actions = actions.clone();
+        let state = state.clone();
+
+        let handle_load = Closure::wrap(Box::new(move || {
+            let result = reader_clone.result().map_err(Error::from).and_then(|r| {
+                let bytes = js_sys::Uint8Array::new(&r).to_vec();
+                let frames = decoder::decode_animation(&bytes)?
+                    .into_iter()
+                    .map(|frame| (frame.image, frame.delay))
+                    .collect();
+                Ok(import_frames(frames, &canvas, &overlay, &actions, &state)?)
+            });
+            notify::report("import animation", result);
+        }) as Box<dyn FnMut()>);
+        reader.set_onload(Some(handle_load.as_ref().unchecked_ref()));
+        handle_load.forget();
+    }
+
+    let input_clone = input.clone();
+    let handle_change = Closure::wrap(Box::new(move || {
+        if let Some(file) = input_clone.files().and_then(|files| files.get(0)) {
+            notify::report("import animation", reader.read_as_array_buffer(&file));
+        }
+        input_clone.set_value("");
+    }) as Box<dyn FnMut()>);
+    input.add_event_listener_with_callback("change", handle_change.as_ref().unchecked_ref())?;
+    handle_change.forget();
+
+    Ok(label)
+}
+
+// a hidden file input wrapped in a clickable label
+pub fn create_file_label(
+    document: &Document,
+    text: &str,
+    accept: &str,
+) -> Result<(Element, HtmlInputElement), JsValue> {
+    let label = Widget::new("label").class("file-label").build(document)?;
+    let span = document.create_element("span")?;
+    span.set_inner_html(text);
+    label.append_child(&span)?;
+
+    let input = document
+        .create_element("input")?
+        .dyn_into::<HtmlInputElement>()?;
+    input.set_attribute("type", "file")?;
+    input.set_attribute("accept", accept)?;
+    input.set_attribute("style", "display: none;")?;
+    label.append_child(&input)?;
+
+    Ok((label, input))
+}
+
+// the document takes the size of the first frame, the current settings are kept.
+// a `None` delay follows the frame speed
+pub fn import_frames(
+    frames: Vec<(RgbaImage, Option<f64>)>,
+    canvas: &HtmlCanvasElement,
+    overlay: &Overlay,
+    actions: &Actions,
+    state: &Rc<RefCell<State>>,
+) -> Result<(), JsValue> {
+    let mut project = Project::from_state(&state.borrow());
+    if let Some((first, _)) = frames.first() {
+        project.width = first.width();
+        project.height = first.height();
+    }
+    project.frames = frames
+        .iter()
+        .map(|(image, delay)| {
+            Ok(ProjectFrame {
+                image: codec::encode_data_url(image)?,
+                delay: *delay,
+                strokes: vec![],
+            })
+        })
+        .collect::<Result<Vec<ProjectFrame>, Error>>()?;
+
+    load_project(project, canvas, overlay, actions, state)
+}
+
+pub fn load_project(
+    project: Project,
+    canvas: &HtmlCanvasElement,
+    overlay: &Overlay,
+    actions: &Actions,
+    state: &Rc<RefCell<State>>,
+) -> Result<(), JsValue> {
+    project.check()?;
+    project.apply(&mut state.borrow_mut());
+    let name = actions
+        .mount()
+        .generate
+        .query_selector(&format!("input.{}", NAME_CLASS))?
+        .and_then(|input| input.dyn_into::<HtmlInputElement>().ok());
+    if let Some(name) = name {
+        name.set_value(&state.borrow().get_name());
+    }
+    resize::fit_canvas(canvas, state)?;
+    overlay.render(state);
+
+    if state.borrow().get_preview_image_len() > 0 {
+        actions.select_frame(0)?;
+        let hooks = state.borrow().get_hooks();
+        hooks.changed();
+        Ok(())
+    } else {
+        actions.clear_frames()
+    }
+}
+
+// what every export is named, project name and the local time it was made,
+// e.g. `drawasm-20261019-153000`
+pub fn export_stem(state: &State) -> String {
+    let now = js_sys::Date::new_0();
+    let timestamp = format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        now.get_full_year(),
+        now.get_month() + 1,
+        now.get_date(),
+        now.get_hours(),
+        now.get_minutes(),
+        now.get_seconds()
+    );
+    project::export_stem(&state.get_name(), &timestamp)
+}
+
+// download an export and hand it to the export listeners of the host page
+pub fn deliver(document: &Document, state: &State, export: &Export) -> Result<(), JsValue> {
+    download_bytes(document, export.bytes, export.mime, &export.file_name)?;
+    state.get_hooks().exported(export);
+    Ok(())
+}
+
+pub fn download_bytes(
+    document: &Document,
+    bytes: &[u8],
+    mime: &str,
+    file_name: &str,
+) -> Result<(), JsValue> {
+    download_blob(document, &create_blob(bytes, mime)?, file_name)
+}
+
+pub fn create_blob(bytes: &[u8], mime: &str) -> Result<Blob, JsValue> {
+    let array = js_sys::Array::new();
+    array.push(&js_sys::Uint8Array::from(bytes));
+    let blob_property = BlobPropertyBag::new();
+    blob_property.set_type(mime);
+    Blob::new_with_u8_array_sequence_and_options(&array, &blob_property)
+}
+
+// a named download through a throwaway anchor, never a popup
+pub fn download_blob(document: &Document, blob: &Blob, file_name: &str) -> Result<(), JsValue> {
+    let url = Url::create_object_url_with_blob(blob)?;
+
+    let anchor = document
+        .create_element("a")?
+        .dyn_into::<HtmlAnchorElement>()?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    let revoke = Closure::once_into_js(move || {
+        let _ = Url::revoke_object_url(&url);
+    });
+    window()
+        .unwrap()
+        .set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), REVOKE_MS)?;
+
+    Ok(())
+}
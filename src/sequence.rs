@@ -0,0 +1,159 @@
+use image::{FilterType, Pixel, Rgba, RgbaImage};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{window, Document, HtmlButtonElement, HtmlInputElement};
+
+use crate::codec;
+use crate::editor::Mount;
+use crate::encoder;
+use crate::error::Error;
+use crate::hooks::Export;
+use crate::notify;
+use crate::palette::{self, Color};
+use crate::resize;
+use crate::save;
+use crate::state::State;
+use crate::toolbar;
+use crate::transform;
+use crate::widget::Widget;
+use crate::zip;
+
+static SCALES: [&str; 5] = ["0.5", "1", "2", "4", "8"];
+
+// every frame as its own png, zipped into one download
+pub fn init_sequence(mount: &Mount, state: &Rc<RefCell<State>>) -> Result<(), JsValue> {
+    let document = window()
+        .unwrap()
+        .document()
+        .expect("Could not find `document`");
+
+    let generate = &mount.generate;
+
+    let element = Widget::new("div")
+        .class("row")
+        .class("sequence")
+        .build(&document)?;
+
+    let scale = resize::create_select(&document, &SCALES, "1")?;
+    scale.set_attribute("title", "scale")?;
+    element.append_child(&scale)?;
+
+    let fill = toolbar::create_checkbox(&document, &element, "background")?;
+    let color = document
+        .create_element("input")?
+        .dyn_into::<HtmlInputElement>()?;
+    color.set_attribute("type", "color")?;
+    color.set_value("#ffffff");
+    element.append_child(&color)?;
+
+    let button = document
+        .create_element("button")?
+        .dyn_into::<HtmlButtonElement>()?;
+    button.set_inner_html("export png sequence");
+    {
+        let document = document.clone();
+        let state = state.clone();
+        let scale = scale.clone();
+        let handle_click = Closure::wrap(Box::new(move || {
+            let scale: f64 = scale.value().parse().unwrap_or(1.0);
+            let background = if fill.checked() {
+                palette::from_hex(&color.value())
+            } else {
+                None
+            };
+            notify::report(
+                "export png sequence",
+                export(&document, &state, scale, background),
+            );
+        }) as Box<dyn FnMut()>);
+        button.set_onclick(Some(handle_click.as_ref().unchecked_ref()));
+        handle_click.forget();
+    }
+    element.append_child(&button)?;
+
+    generate.append_child(&element)?;
+
+    Ok(())
+}
+
+fn export(
+    document: &Document,
+    state: &Rc<RefCell<State>>,
+    scale: f64,
+    background: Option<Color>,
+) -> Result<(), Error> {
+    if state.borrow().get_preview_image_len() == 0 {
+        return Err(Error::NoFrames);
+    }
+    // pixel art stays crisp, drawings are resampled smoothly
+    let filter = if state.borrow().is_pixel_art() {
+        FilterType::Nearest
+    } else {
+        FilterType::Lanczos3
+    };
+
+    let mut files = vec![];
+    for (i, data) in state.borrow().get_preview_image().iter().enumerate() {
+        let img = codec::decode_data_url(data).map_err(|e| Error::frame(i, e))?;
+        let img = render_frame(&img, scale, background, filter)?;
+        let png = encoder::encode_png(img.width(), img.height(), None, &img)?;
+        files.push((frame_name(i), png));
+    }
+    let archive = zip::write_zip(&files)?;
+
+    let state = state.borrow();
+    Ok(save::deliver(
+        document,
+        &state,
+        &Export::of_document(
+            &state,
+            "png-frames",
+            format!("{}-frames.zip", save::export_stem(&state)),
+            "application/zip",
+            &archive,
+        ),
+    )?)
+}
+
+// numbered from 1 the way video editors expect an image sequence
+fn frame_name(index: usize) -> String {
+    format!("frame_{:04}.png", index + 1)
+}
+
+fn render_frame(
+    img: &RgbaImage,
+    scale: f64,
+    background: Option<Color>,
+    filter: FilterType,
+) -> Result<RgbaImage, Error> {
+    let (w, h) = (
+        ((img.width() as f64 * scale).round() as u32).max(1),
+        ((img.height() as f64 * scale).round() as u32).max(1),
+    );
+    // refused before the scaled frame is allocated
+    if w.max(h) > transform::MAX_SIDE {
+        return Err(Error::Input(format!(
+            "frames scaled to {}x{} are larger than {} pixels a side, pick a smaller scale",
+            w,
+            h,
+            transform::MAX_SIDE
+        )));
+    }
+    let mut img = if (w, h) == img.dimensions() {
+        img.clone()
+    } else {
+        transform::scale_image(img, w, h, filter)
+    };
+
+    if let Some(color) = background {
+        for pixel in img.pixels_mut() {
+            let mut filled = Rgba(color);
+            filled.blend(pixel);
+            *pixel = filled;
+        }
+    }
+
+    Ok(img)
+}
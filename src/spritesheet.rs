@@ -0,0 +1,332 @@
+use image::RgbaImage;
+use std::cell::RefCell;
+use std::io::Cursor;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    window, Document, Element, File, FileReader, HtmlButtonElement, HtmlCanvasElement,
+    HtmlInputElement, HtmlSelectElement,
+};
+
+use crate::actions::Actions;
+use crate::atlas::{self, Atlas, GridSpec, Layout};
+use crate::decoder;
+use crate::editor::Mount;
+use crate::encoder;
+use crate::error::Error;
+use crate::generate;
+use crate::hooks::Export;
+use crate::notify;
+use crate::overlay::Overlay;
+use crate::resize;
+use crate::save;
+use crate::state::State;
+use crate::toolbar;
+use crate::widget::Widget;
+
+static LAYOUTS: [&str; 2] = ["grid", "packed"];
+// in the order of `GridSpec`, left empty they are worked out from the sheet size
+static GRID_FIELDS: [&str; 6] = ["columns", "rows", "cell w", "cell h", "margin", "spacing"];
+// packed frames keep a pixel apart so filtering does not bleed between them
+static PACKED_PADDING: u32 = 1;
+
+pub fn init_spritesheet(
+    mount: &Mount,
+    overlay: &Overlay,
+    actions: &Actions,
+    state: &Rc<RefCell<State>>,
+) -> Result<(), JsValue> {
+    let document = window()
+        .unwrap()
+        .document()
+        .expect("Could not find `document`");
+
+    let generate = &mount.generate;
+    let canvas = &mount.canvas;
+
+    let element = Widget::new("div")
+        .class("row")
+        .class("spritesheet")
+        .build(&document)?;
+
+    let layout = resize::create_select(&document, &LAYOUTS, "grid")?;
+    element.append_child(&layout)?;
+
+    let columns = document
+        .create_element("input")?
+        .dyn_into::<HtmlInputElement>()?;
+    columns.set_attribute("type", "number")?;
+    columns.set_attribute("min", "0")?;
+    columns.set_attribute("title", "columns, 0 for a square grid")?;
+    columns.set_attribute("style", "width: 3em;")?;
+    columns.set_value("0");
+    element.append_child(&columns)?;
+
+    let trim = toolbar::create_checkbox(&document, &element, "trim")?;
+
+    let button = create_export_button(&document, &layout, &columns, &trim, state)?;
+    element.append_child(&button)?;
+
+    generate.append_child(&element)?;
+
+    let import = create_import_element(&document, canvas, overlay, actions, state)?;
+    generate.append_child(&import)?;
+
+    Ok(())
+}
+
+fn create_export_button(
+    document: &Document,
+    layout: &HtmlSelectElement,
+    columns: &HtmlInputElement,
+    trim: &HtmlInputElement,
+    state: &Rc<RefCell<State>>,
+) -> Result<HtmlButtonElement, JsValue> {
+    let button = document
+        .create_element("button")?
+        .dyn_into::<HtmlButtonElement>()?;
+    button.set_inner_html("export sprite sheet");
+
+    let document = document.clone();
+    let (layout, columns, trim) = (layout.clone(), columns.clone(), trim.clone());
+    let state = state.clone();
+    let handle_click = Closure::wrap(Box::new(move || {
+        let (layout, padding) = match layout.value().as_str() {
+            "packed" => (Layout::Packed, PACKED_PADDING),
+            _ => (
+                Layout::Grid {
+                    columns: columns.value().parse().unwrap_or(0),
+                },
+                0,
+            ),
+        };
+        notify::report(
+            "export sprite sheet",
+            export(&document, &state, layout, trim.checked(), padding),
+        );
+    }) as Box<dyn FnMut()>);
+
+    button.set_onclick(Some(handle_click.as_ref().unchecked_ref()));
+    handle_click.forget();
+
+    Ok(button)
+}
+
+// the sheet png and its json descriptor are downloaded side by side
+fn export(
+    document: &Document,
+    state: &Rc<RefCell<State>>,
+    layout: Layout,
+    trim: bool,
+    padding: u32,
+) -> Result<(), Error> {
+    if state.borrow().get_preview_image_len() == 0 {
+        return Err(Error::NoFrames);
+    }
+    let frames = generate::decode_frames(&state.borrow().get_preview_image())?;
+    let delays = state.borrow().get_frame_delays();
+
+    let sheet_name = format!("{}-sheet", save::export_stem(&state.borrow()));
+    let image_name = format!("{}.png", sheet_name);
+    let sheet = atlas::pack(&frames, &delays, layout, trim, padding, &image_name);
+
+    let png = encoder::encode_png(
+        sheet.image.width(),
+        sheet.image.height(),
+        None,
+        &sheet.image,
+    )?;
+    let json = serde_json::to_string_pretty(&sheet.atlas).map_err(Error::Atlas)?;
+
+    let state = state.borrow();
+    save::deliver(
+        document,
+        &state,
+        &Export {
+            width: sheet.image.width(),
+            height: sheet.image.height(),
+            ..Export::of_document(&state, "sprite-sheet", image_name, "image/png", &png)
+        },
+    )?;
+    save::deliver(
+        document,
+        &state,
+        &Export::of_document(
+            &state,
+            "atlas",
+            format!("{}.json", sheet_name),
+            "application/json",
+            json.as_bytes(),
+        ),
+    )?;
+
+    Ok(())
+}
+
+// a sheet image, cut by its json descriptor when one is picked along with it or by the grid inputs
+fn create_import_element(
+    document: &Document,
+    canvas: &HtmlCanvasElement,
+    overlay: &Overlay,
+    actions: &Actions,
+    state: &Rc<RefCell<State>>,
+) -> Result<Element, JsValue> {
+    let element = Widget::new("div").class("row").build(document)?;
+
+    let (label, input) = save::create_file_label(
+        document,
+        "import sprite sheet",
+        "image/*,.json,application/json",
+    )?;
+    input.set_attribute("multiple", "")?;
+    element.append_child(&label)?;
+
+    let mut fields = vec![];
+    for name in GRID_FIELDS.iter() {
+        let field = document
+            .create_element("input")?
+            .dyn_into::<HtmlInputElement>()?;
+        field.set_attribute("type", "number")?;
+        field.set_attribute("min", "0")?;
+        field.set_attribute("placeholder", name)?;
+        field.set_attribute("title", name)?;
+        field.set_attribute("style", "width: 4.5em;")?;
+        element.append_child(&field)?;
+        fields.push(field);
+    }
+
+    let canvas = canvas.clone();
+    let overlay = overlay.clone();
+    let actions = actions.clone();
+    let state = state.clone();
+    let input_clone = input.clone();
+    let handle_change = Closure::wrap(Box::new(move || {
+        let files = match input_clone.files() {
+            Some(files) => files,
+            None => return,
+        };
+        let files: Vec<File> = (0..files.length()).filter_map(|i| files.get(i)).collect();
+        input_clone.set_value("");
+
+        let image = match files.iter().find(|f| f.type_().starts_with("image/")) {
+            Some(image) => image.clone(),
+            None => {
+                notify::error(
+                    "import sprite sheet",
+                    &Error::Input("pick the sprite sheet image".to_string()),
+                );
+                return;
+            }
+        };
+        let descriptor = files
+            .iter()
+            .find(|f| f.name().ends_with(".json") || f.type_() == "application/json")
+            .cloned();
+
+        let value = |i: usize| fields[i].value().parse().unwrap_or(0);
+        let spec = GridSpec {
+            columns: value(0),
+            rows: value(1),
+            cell_w: value(2),
+            cell_h: value(3),
+            margin: value(4),
+            spacing: value(5),
+        };
+        let import = {
+            let (canvas, overlay, actions, state) = (
+                canvas.clone(),
+                overlay.clone(),
+                actions.clone(),
+                state.clone(),
+            );
+            move |atlas: Option<Atlas>| {
+                let read = read_file(&image, false, move |result| {
+                    let bytes = js_sys::Uint8Array::new(&result).to_vec();
+                    let result = slice(&bytes, atlas.as_ref(), spec).and_then(|frames| {
+                        Ok(save::import_frames(
+                            frames, &canvas, &overlay, &actions, &state,
+                        )?)
+                    });
+                    notify::report("import sprite sheet", result);
+                });
+                notify::report("import sprite sheet", read);
+            }
+        };
+
+        match descriptor {
+            Some(descriptor) => {
+                let read = read_file(&descriptor, true, move |result| {
+                    match Atlas::from_json(&result.as_string().unwrap_or_default()) {
+                        Ok(atlas) => import(Some(atlas)),
+                        Err(err) => notify::error("import sprite sheet", &Error::Atlas(err)),
+                    }
+                });
+                notify::report("import sprite sheet", read);
+            }
+            None => import(None),
+        }
+    }) as Box<dyn FnMut()>);
+    input.add_event_listener_with_callback("change", handle_change.as_ref().unchecked_ref())?;
+    handle_change.forget();
+
+    Ok(element)
+}
+
+fn slice(
+    bytes: &[u8],
+    atlas: Option<&Atlas>,
+    spec: GridSpec,
+) -> Result<Vec<(RgbaImage, Option<f64>)>, Error> {
+    // the size is read from the header, before any pixels are allocated
+    let (width, height) = image::io::Reader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .into_dimensions()?;
+    if u64::from(width) * u64::from(height) > decoder::MAX_TOTAL_PIXELS {
+        return Err(Error::Input(format!(
+            "a {}x{} sprite sheet is too large to import",
+            width, height
+        )));
+    }
+    let sheet = image::load_from_memory(bytes)?.to_rgba();
+    let frames = match atlas {
+        Some(atlas) => atlas::slice_atlas(&sheet, atlas).ok_or_else(|| {
+            Error::Input("the sprite sheet frames are too large to import together".to_string())
+        })?,
+        None => atlas::slice_grid(&sheet, spec)
+            .into_iter()
+            .map(|frame| (frame, None))
+            .collect(),
+    };
+    if frames.is_empty() {
+        return Err(Error::Input(
+            "no frames found, set the columns and rows or the cell size".to_string(),
+        ));
+    }
+
+    Ok(frames)
+}
+
+// read `file` as text or as an array buffer and hand the result to `on_load`
+fn read_file(
+    file: &File,
+    as_text: bool,
+    on_load: impl FnOnce(JsValue) + 'static,
+) -> Result<(), JsValue> {
+    let reader = FileReader::new()?;
+    let reader_clone = reader.clone();
+    let on_load = RefCell::new(Some(on_load));
+    let handle_load = Closure::wrap(Box::new(move || {
+        if let (Some(on_load), Ok(result)) = (on_load.borrow_mut().take(), reader_clone.result()) {
+            on_load(result);
+        }
+    }) as Box<dyn FnMut()>);
+    reader.set_onload(Some(handle_load.as_ref().unchecked_ref()));
+    handle_load.forget();
+
+    if as_text {
+        reader.read_as_text(file)
+    } else {
+        reader.read_as_array_buffer(file)
+    }
+}
@@ -1,32 +1,105 @@
+use crate::guides::{self, Grid, Guide};
+use crate::hooks::Hooks;
+use crate::palette::{self, Color};
+use crate::stroke::Stroke;
+use crate::symmetry::Symmetry;
+
+pub static DEFAULT_NAME: &str = "drawasm";
+
+// what a drag on the canvas does
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Tool {
+    Pen,
+    Eraser,
+}
+
+impl Tool {
+    pub fn composite_operation(self) -> &'static str {
+        match self {
+            Tool::Pen => "source-over",
+            Tool::Eraser => "destination-out",
+        }
+    }
+}
+
 pub struct State {
+    // what exported files are named after
+    name: String,
     width: u32,
     height: u32,
+    viewport_w: u32,
+    viewport_h: u32,
+    zoom: u32,
+    pixel_art: bool,
+    palette: Vec<Color>,
     preview_w: u32,
     preview_h: u32,
+    tool: Tool,
     pen_thin: f64,
     color: String,
     preview_image: Vec<String>,
-    undo_image_data: Vec<web_sys::ImageData>,
-    redo_image_data: Vec<web_sys::ImageData>,
+    // per frame delay in seconds, `None` follows `frame_speed`
+    frame_delays: Vec<Option<f64>>,
+    current_frame: Option<usize>,
+    // strokes on the canvas and of every frame, in drawing order
+    strokes: Vec<Stroke>,
+    frame_strokes: Vec<Vec<Stroke>>,
+    // canvas snapshots together with the strokes drawn at that point
+    undo_image_data: Vec<(web_sys::ImageData, Vec<Stroke>)>,
+    redo_image_data: Vec<(web_sys::ImageData, Vec<Stroke>)>,
     frame_speed: f64,
+    symmetry: Symmetry,
+    symmetry_center: Option<(f64, f64)>,
+    grid: Grid,
+    guides: Vec<Guide>,
+    // tracing image shown under the canvas, never part of a frame
+    reference: Option<web_sys::HtmlImageElement>,
+    reference_opacity: f64,
+    hooks: Hooks,
 }
 
 impl State {
     pub fn new(w: u32, h: u32) -> State {
         State {
+            name: DEFAULT_NAME.to_string(),
             width: w,
             height: h,
+            viewport_w: w,
+            viewport_h: h,
+            zoom: 1,
+            pixel_art: false,
+            palette: palette::DEFAULT_PALETTE.to_vec(),
             preview_w: w / 5,
             preview_h: h / 5,
+            tool: Tool::Pen,
             pen_thin: 1.0,                //TODO not hardcode
             color: "#000000".to_string(), //TODO not hardcode
             preview_image: vec![],
+            frame_delays: vec![],
+            current_frame: None,
+            strokes: vec![],
+            frame_strokes: vec![],
             undo_image_data: vec![],
             redo_image_data: vec![],
             frame_speed: 0.33,
+            symmetry: Symmetry::None,
+            symmetry_center: None,
+            grid: Grid::default(),
+            guides: vec![],
+            reference: None,
+            reference_opacity: 0.5,
+            hooks: Hooks::default(),
         }
     }
 
+    pub fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
     pub fn get_color(&self) -> String {
         self.color.clone() // not implement Copy trait
     }
@@ -35,6 +108,14 @@ impl State {
         self.color = color;
     }
 
+    pub fn get_tool(&self) -> Tool {
+        self.tool
+    }
+
+    pub fn set_tool(&mut self, tool: Tool) {
+        self.tool = tool;
+    }
+
     pub fn get_pen_thin(&self) -> f64 {
         self.pen_thin
     }
@@ -59,30 +140,127 @@ impl State {
         self.preview_h
     }
 
+    // size of the drawing area the canvas was laid out with
+    pub fn get_viewport(&self) -> (u32, u32) {
+        (self.viewport_w, self.viewport_h)
+    }
+
+    pub fn get_zoom(&self) -> u32 {
+        self.zoom
+    }
+
+    pub fn is_pixel_art(&self) -> bool {
+        self.pixel_art
+    }
+
+    // pixel art documents are small and shown enlarged by an integer zoom
+    pub fn set_pixel_art(&mut self, pixel_art: bool, zoom: u32) {
+        self.pixel_art = pixel_art;
+        self.zoom = zoom.max(1);
+    }
+
+    pub fn get_palette(&self) -> Vec<Color> {
+        self.palette.clone()
+    }
+
+    pub fn set_palette(&mut self, palette: Vec<Color>) {
+        self.palette = palette;
+    }
+
+    // change the document size, undo/redo snapshots no longer fit so they are dropped
+    // and the recorded strokes no longer line up with the pixels
+    pub fn set_size(&mut self, w: u32, h: u32) {
+        self.width = w;
+        self.height = h;
+        self.preview_w = w * self.zoom / 5;
+        self.preview_h = h * self.zoom / 5;
+        self.undo_image_data = vec![];
+        self.redo_image_data = vec![];
+        self.strokes = vec![];
+        self.frame_strokes = vec![vec![]; self.preview_image.len()];
+        self.symmetry_center = None;
+    }
+
     pub fn add_undo(&mut self, data: web_sys::ImageData) {
-        self.undo_image_data.push(data);
+        self.undo_image_data.push((data, self.strokes.clone()));
+    }
+
+    // step back from `current`, the canvas as it is now, and get the canvas to show
+    pub fn undo(&mut self, current: web_sys::ImageData) -> Option<web_sys::ImageData> {
+        let (data, strokes) = self.undo_image_data.pop()?;
+        let current_strokes = std::mem::replace(&mut self.strokes, strokes);
+        self.redo_image_data.push((current, current_strokes));
+        Some(data)
+    }
+
+    pub fn redo(&mut self, current: web_sys::ImageData) -> Option<web_sys::ImageData> {
+        let (data, strokes) = self.redo_image_data.pop()?;
+        let current_strokes = std::mem::replace(&mut self.strokes, strokes);
+        self.undo_image_data.push((current, current_strokes));
+        Some(data)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_image_data.is_empty()
     }
 
-    pub fn get_undo(&mut self) -> Option<web_sys::ImageData> {
-        self.undo_image_data.pop()
+    pub fn can_redo(&self) -> bool {
+        !self.redo_image_data.is_empty()
     }
 
-    pub fn add_redo(&mut self, data: web_sys::ImageData) {
-        self.redo_image_data.push(data);
+    pub fn get_strokes(&self) -> Vec<Stroke> {
+        self.strokes.clone()
     }
 
-    pub fn get_redo(&mut self) -> Option<web_sys::ImageData> {
-        self.redo_image_data.pop()
+    pub fn set_strokes(&mut self, strokes: Vec<Stroke>) {
+        self.strokes = strokes;
+    }
+
+    // one stroke per symmetric copy
+    pub fn add_strokes(&mut self, strokes: Vec<Stroke>) {
+        self.strokes.extend(strokes);
+    }
+
+    // continue the strokes last added, `points` holds one point per copy
+    pub fn extend_strokes(&mut self, points: &[(f64, f64)]) {
+        let start = self.strokes.len().saturating_sub(points.len());
+        for (stroke, point) in self.strokes[start..].iter_mut().zip(points) {
+            stroke.points.push(*point);
+        }
+    }
+
+    pub fn get_frame_strokes(&self) -> Vec<Vec<Stroke>> {
+        self.frame_strokes.clone()
+    }
+
+    pub fn set_frame_strokes(&mut self, index: usize, strokes: Vec<Stroke>) {
+        if let Some(frame) = self.frame_strokes.get_mut(index) {
+            *frame = strokes;
+        }
     }
 
     pub fn add_preview_image(&mut self, data: String) {
+        self.add_preview_image_with_delay(data, None);
+    }
+
+    pub fn add_preview_image_with_delay(&mut self, data: String, delay: Option<f64>) {
         self.preview_image.push(data);
+        self.frame_delays.push(delay);
+        self.frame_strokes.push(vec![]);
     }
 
     pub fn get_preview_image(&self) -> Vec<String> {
         self.preview_image.clone()
     }
 
+    // replace the frame images, delays and strokes stay with the frame at the same index
+    pub fn set_preview_image(&mut self, images: Vec<String>) {
+        self.current_frame = self.current_frame.filter(|i| *i < images.len());
+        self.frame_delays.resize(images.len(), None);
+        self.frame_strokes.resize(images.len(), vec![]);
+        self.preview_image = images;
+    }
+
     pub fn get_preview_image_len(&self) -> usize {
         self.preview_image.len()
     }
@@ -94,6 +272,30 @@ impl State {
 
     pub fn delete_all_images(&mut self) {
         self.preview_image = vec![];
+        self.frame_delays = vec![];
+        self.frame_strokes = vec![];
+        self.current_frame = None;
+    }
+
+    pub fn get_frame_delay(&self, index: usize) -> Option<f64> {
+        self.frame_delays.get(index).copied().flatten()
+    }
+
+    // delay of every frame in seconds with `frame_speed` filled in
+    pub fn get_frame_delays(&self) -> Vec<f64> {
+        self.frame_delays
+            .iter()
+            .map(|d| d.unwrap_or(self.frame_speed))
+            .collect()
+    }
+
+    // the frame last added or navigated to
+    pub fn get_current_frame(&self) -> Option<usize> {
+        self.current_frame
+    }
+
+    pub fn set_current_frame(&mut self, index: Option<usize>) {
+        self.current_frame = index;
     }
 
     pub fn get_frame_speed(&self) -> f64 {
@@ -103,4 +305,84 @@ impl State {
     pub fn set_frame_speed(&mut self, frame_speed: f64) {
         self.frame_speed = frame_speed;
     }
+
+    pub fn get_symmetry(&self) -> Symmetry {
+        self.symmetry
+    }
+
+    pub fn set_symmetry(&mut self, symmetry: Symmetry) {
+        self.symmetry = symmetry.clamped();
+    }
+
+    // the document center unless the user picked one
+    pub fn get_symmetry_center(&self) -> (f64, f64) {
+        self.symmetry_center
+            .unwrap_or((self.width as f64 / 2.0, self.height as f64 / 2.0))
+    }
+
+    pub fn set_symmetry_center(&mut self, center: Option<(f64, f64)>) {
+        self.symmetry_center = center;
+    }
+
+    pub fn get_grid(&self) -> Grid {
+        self.grid
+    }
+
+    pub fn set_grid(&mut self, grid: Grid) {
+        self.grid = grid;
+    }
+
+    pub fn get_guides(&self) -> Vec<Guide> {
+        self.guides.clone()
+    }
+
+    pub fn set_guides(&mut self, guides: Vec<Guide>) {
+        self.guides = guides;
+    }
+
+    pub fn add_guide(&mut self, guide: Guide) -> usize {
+        self.guides.push(guide);
+        self.guides.len() - 1
+    }
+
+    pub fn move_guide(&mut self, index: usize, position: f64) {
+        if let Some(guide) = self.guides.get_mut(index) {
+            guide.position = position;
+        }
+    }
+
+    pub fn remove_guide(&mut self, index: usize) {
+        if index < self.guides.len() {
+            self.guides.remove(index);
+        }
+    }
+
+    pub fn snap_point(&self, p: (f64, f64)) -> (f64, f64) {
+        guides::snap(&self.grid, &self.guides, p)
+    }
+
+    pub fn get_reference(&self) -> Option<web_sys::HtmlImageElement> {
+        self.reference.clone()
+    }
+
+    pub fn set_reference(&mut self, reference: Option<web_sys::HtmlImageElement>) {
+        self.reference = reference;
+    }
+
+    pub fn get_reference_opacity(&self) -> f64 {
+        self.reference_opacity
+    }
+
+    pub fn set_reference_opacity(&mut self, opacity: f64) {
+        self.reference_opacity = opacity.clamp(0.0, 1.0);
+    }
+
+    // cloned out so no borrow is held while the host runs its callback
+    pub fn get_hooks(&self) -> Hooks {
+        self.hooks.clone()
+    }
+
+    pub fn set_hooks(&mut self, hooks: Hooks) {
+        self.hooks = hooks;
+    }
 }
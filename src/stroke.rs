@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+// one pen or eraser drag, kept next to the pixels so the line art can be exported as vectors
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stroke {
+    // document pixels
+    pub points: Vec<(f64, f64)>,
+    pub color: String,
+    pub width: f64,
+    pub erase: bool,
+    // pixel art strokes are made of square dots instead of a round pen
+    pub square: bool,
+}
+
+impl Stroke {
+    pub fn new(point: (f64, f64), color: &str, width: f64, erase: bool, square: bool) -> Stroke {
+        Stroke {
+            points: vec![point],
+            color: color.to_string(),
+            width,
+            erase,
+            square,
+        }
+    }
+}
@@ -0,0 +1,407 @@
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{window, Document, HtmlButtonElement};
+
+use crate::editor::Mount;
+use crate::error::Error;
+use crate::hooks::Export;
+use crate::notify;
+use crate::save;
+use crate::state::State;
+use crate::stroke::Stroke;
+use crate::widget::Widget;
+use crate::zip;
+
+// the strokes of one frame as a standalone svg
+pub fn frame_svg(width: u32, height: u32, strokes: &[Stroke]) -> String {
+    let (defs, body) = render_strokes(strokes, "m", width, height);
+    format!(
+        "{}<defs>{}</defs>{}</svg>",
+        svg_open(width, height),
+        defs,
+        body
+    )
+}
+
+// every frame in its own group, shown in turn by css keyframes timed with `delays` (seconds)
+pub fn animated_svg(width: u32, height: u32, frames: &[Vec<Stroke>], delays: &[f64]) -> String {
+    let total: f64 = delays.iter().take(frames.len()).sum();
+    let mut style = String::new();
+    let mut defs = String::new();
+    let mut groups = String::new();
+
+    let mut start = 0.0;
+    for (i, strokes) in frames.iter().enumerate() {
+        let delay = delays.get(i).copied().unwrap_or(0.0);
+        let (from, to) = if total > 0.0 {
+            (start / total * 100.0, (start + delay) / total * 100.0)
+        } else {
+            (0.0, 100.0)
+        };
+        start += delay;
+
+        write!(
+            style,
+            ".f{i} {{ visibility: hidden; animation: f{i} {total}s step-end infinite; }} @keyframes f{i} {{ ",
+            i = i,
+            total = total
+        )
+        .unwrap();
+        if from > 0.0 {
+            style.push_str("0% { visibility: hidden; } ");
+        }
+        write!(style, "{:.3}% {{ visibility: visible; }} ", from).unwrap();
+        if to < 100.0 {
+            write!(style, "{:.3}% {{ visibility: hidden; }} ", to).unwrap();
+        }
+        style.push_str("100% { visibility: hidden; } } ");
+
+        let (frame_defs, body) = render_strokes(strokes, &format!("f{}m", i), width, height);
+        defs.push_str(&frame_defs);
+        write!(groups, "<g class=\"f{}\">{}</g>", i, body).unwrap();
+    }
+
+    format!(
+        "{}<style>{}</style><defs>{}</defs>{}</svg>",
+        svg_open(width, height),
+        style,
+        defs,
+        groups
+    )
+}
+
+fn svg_open(width: u32, height: u32) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+        w = width,
+        h = height
+    )
+}
+
+// `(defs, body)`, eraser strokes become masks over everything drawn before them
+fn render_strokes(
+    strokes: &[Stroke],
+    mask_prefix: &str,
+    width: u32,
+    height: u32,
+) -> (String, String) {
+    let mut defs = String::new();
+    let mut body = String::new();
+    let mut erasers = String::new();
+    let mut masks = 0;
+
+    let mut flush = |defs: &mut String, body: &mut String, erasers: &mut String| {
+        if erasers.is_empty() {
+            return;
+        }
+        let id = format!("{}{}", mask_prefix, masks);
+        masks += 1;
+        write!(
+            defs,
+            "<mask id=\"{}\" maskUnits=\"userSpaceOnUse\" x=\"0\" y=\"0\" width=\"{w}\" height=\"{h}\"><rect width=\"{w}\" height=\"{h}\" fill=\"white\"/>{}</mask>",
+            id,
+            erasers,
+            w = width,
+            h = height
+        )
+        .unwrap();
+        *body = format!("<g mask=\"url(#{})\">{}</g>", id, body);
+        erasers.clear();
+    };
+
+    for stroke in strokes {
+        if stroke.erase {
+            erasers.push_str(&path(stroke, "black"));
+        } else {
+            flush(&mut defs, &mut body, &mut erasers);
+            body.push_str(&path(stroke, &escape(&stroke.color)));
+        }
+    }
+    flush(&mut defs, &mut body, &mut erasers);
+
+    (defs, body)
+}
+
+fn path(stroke: &Stroke, color: &str) -> String {
+    let mut d = String::new();
+    for (i, (x, y)) in stroke.points.iter().enumerate() {
+        let command = if i == 0 { 'M' } else { 'L' };
+        write!(d, "{}{:.2} {:.2} ", command, x, y).unwrap();
+    }
+    // a click without a drag is a dot, which needs a (zero length) segment to show its cap
+    if stroke.points.len() == 1 {
+        let (x, y) = stroke.points[0];
+        write!(d, "L{:.2} {:.2}", x, y).unwrap();
+    }
+    let (cap, join) = if stroke.square {
+        ("square", "miter")
+    } else {
+        ("round", "round")
+    };
+
+    format!(
+        "<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" stroke-linecap=\"{}\" stroke-linejoin=\"{}\"/>",
+        d.trim_end(),
+        color,
+        stroke.width,
+        cap,
+        join
+    )
+}
+
+// colors come from project files too, keep them from breaking out of the attribute
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+pub fn init_svg(mount: &Mount, state: &Rc<RefCell<State>>) -> Result<(), JsValue> {
+    let document = window()
+        .unwrap()
+        .document()
+        .expect("Could not find `document`");
+
+    let generate = &mount.generate;
+
+    let element = Widget::new("div")
+        .class("row")
+        .class("svg")
+        .build(&document)?;
+
+    let frames = create_button(&document, "export svg", state, export_frames)?;
+    element.append_child(&frames)?;
+    let animated = create_button(&document, "animated svg", state, export_animated)?;
+    element.append_child(&animated)?;
+
+    generate.append_child(&element)?;
+
+    Ok(())
+}
+
+fn create_button(
+    document: &Document,
+    text: &str,
+    state: &Rc<RefCell<State>>,
+    export: fn(&Document, &State) -> Result<(), Error>,
+) -> Result<HtmlButtonElement, JsValue> {
+    let button = document
+        .create_element("button")?
+        .dyn_into::<HtmlButtonElement>()?;
+    button.set_inner_html(text);
+
+    let document = document.clone();
+    let state = state.clone();
+    let context = text.to_string();
+    let handle_click = Closure::wrap(Box::new(move || {
+        notify::report(&context, export(&document, &state.borrow()));
+    }) as Box<dyn FnMut()>);
+    button.set_onclick(Some(handle_click.as_ref().unchecked_ref()));
+    handle_click.forget();
+
+    Ok(button)
+}
+
+// one svg per frame in a zip, or the canvas alone while there are no frames
+fn export_frames(document: &Document, state: &State) -> Result<(), Error> {
+    let (w, h) = (state.get_width(), state.get_height());
+    let frames = state.get_frame_strokes();
+    if frames.is_empty() {
+        let svg = frame_svg(w, h, &state.get_strokes());
+        save::deliver(
+            document,
+            state,
+            &Export::of_document(
+                state,
+                "svg",
+                format!("{}.svg", save::export_stem(state)),
+                "image/svg+xml",
+                svg.as_bytes(),
+            ),
+        )?;
+        return Ok(());
+    }
+
+    let files = frame_files(w, h, &frames);
+    let skipped = blank_frames(&frames);
+    if files.is_empty() {
+        return Err(Error::Input(
+            "no frame has strokes to export, imported and resized frames are pixels only"
+                .to_string(),
+        ));
+    }
+    if !skipped.is_empty() {
+        notify::warn(
+            "export svg",
+            &format!(
+                "left out frames {}, they have no strokes, imported and resized frames are pixels only",
+                numbers(&skipped)
+            ),
+        );
+    }
+    warn_unadded("export svg", state);
+    let archive = zip::write_zip(&files)?;
+
+    save::deliver(
+        document,
+        state,
+        &Export::of_document(
+            state,
+            "svg-frames",
+            format!("{}-svg.zip", save::export_stem(state)),
+            "application/zip",
+            &archive,
+        ),
+    )?;
+
+    Ok(())
+}
+
+fn export_animated(document: &Document, state: &State) -> Result<(), Error> {
+    let frames = state.get_frame_strokes();
+    let blank = blank_frames(&frames);
+    if !blank.is_empty() {
+        notify::warn(
+            "animated svg",
+            &format!(
+                "frames {} have no strokes and stay empty, imported and resized frames are pixels only",
+                numbers(&blank)
+            ),
+        );
+    }
+    warn_unadded("animated svg", state);
+    let svg = animated_svg(
+        state.get_width(),
+        state.get_height(),
+        &frames,
+        &state.get_frame_delays(),
+    );
+
+    save::deliver(
+        document,
+        state,
+        &Export::of_document(
+            state,
+            "animated-svg",
+            format!("{}-animated.svg", save::export_stem(state)),
+            "image/svg+xml",
+            svg.as_bytes(),
+        ),
+    )?;
+
+    Ok(())
+}
+
+// a file per frame that has strokes, the others would come out blank
+fn frame_files(width: u32, height: u32, frames: &[Vec<Stroke>]) -> Vec<(String, Vec<u8>)> {
+    frames
+        .iter()
+        .enumerate()
+        .filter(|(_, strokes)| !strokes.is_empty())
+        .map(|(i, strokes)| {
+            (
+                format!("frame_{:04}.svg", i + 1),
+                frame_svg(width, height, strokes).into_bytes(),
+            )
+        })
+        .collect()
+}
+
+// numbers of the frames that are pixels only, imported or resized ones
+fn blank_frames(frames: &[Vec<Stroke>]) -> Vec<usize> {
+    frames
+        .iter()
+        .enumerate()
+        .filter(|(_, strokes)| strokes.is_empty())
+        .map(|(i, _)| i + 1)
+        .collect()
+}
+
+// strokes on the canvas that differ from the selected frame were never added as one
+fn warn_unadded(context: &str, state: &State) {
+    let strokes = state.get_strokes();
+    let added = strokes.is_empty()
+        || state
+            .get_current_frame()
+            .is_some_and(|i| state.get_frame_strokes().get(i) == Some(&strokes));
+    if !added {
+        notify::warn(
+            context,
+            "the drawing on the canvas is not a frame yet and was left out, add it as a frame first",
+        );
+    }
+}
+
+fn numbers(frames: &[usize]) -> String {
+    frames
+        .iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stroke(points: &[(f64, f64)], erase: bool) -> Stroke {
+        let mut stroke = Stroke::new(points[0], "#ff004d", 4.0, erase, false);
+        stroke.points.extend_from_slice(&points[1..]);
+        stroke
+    }
+
+    #[test]
+    fn writes_paths_with_pen_settings() {
+        let svg = frame_svg(10, 10, &[stroke(&[(1.0, 2.0), (3.5, 4.0)], false)]);
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"10\""));
+        assert!(svg.contains("d=\"M1.00 2.00 L3.50 4.00\""));
+        assert!(svg.contains("stroke=\"#ff004d\" stroke-width=\"4\" stroke-linecap=\"round\""));
+    }
+
+    #[test]
+    fn eraser_masks_only_earlier_strokes() {
+        let strokes = vec![
+            stroke(&[(0.0, 0.0), (5.0, 5.0)], false),
+            stroke(&[(2.0, 2.0)], true),
+            stroke(&[(5.0, 0.0), (0.0, 5.0)], false),
+        ];
+        let svg = frame_svg(10, 10, &strokes);
+
+        assert!(svg.contains("<mask id=\"m0\""));
+        assert!(svg.contains("stroke=\"black\""));
+        let masked = svg
+            .find("<g mask=\"url(#m0)\"><path d=\"M0.00 0.00")
+            .unwrap();
+        let after = svg.find("<path d=\"M5.00 0.00").unwrap();
+        assert!(masked < after);
+    }
+
+    #[test]
+    fn frames_without_strokes_are_left_out() {
+        let frames = vec![vec![], vec![stroke(&[(1.0, 1.0)], false)], vec![]];
+        let files = frame_files(10, 10, &frames);
+
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["frame_0002.svg"]);
+        assert_eq!(blank_frames(&frames), vec![1, 3]);
+    }
+
+    #[test]
+    fn animation_splits_the_loop_by_delay() {
+        let frames = vec![vec![stroke(&[(0.0, 0.0)], false)], vec![]];
+        let svg = animated_svg(10, 10, &frames, &[0.25, 0.75]);
+
+        assert!(svg.contains("animation: f0 1s step-end infinite"));
+        assert!(svg.contains(
+            "@keyframes f0 { 0.000% { visibility: visible; } 25.000% { visibility: hidden; }"
+        ));
+        assert!(svg.contains(
+            "@keyframes f1 { 0% { visibility: hidden; } 25.000% { visibility: visible; } 100%"
+        ));
+        assert!(svg.contains("<g class=\"f1\"></g>"));
+    }
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+// every drawn segment is repeated once per fold, so radial symmetry stays small
+pub static MAX_FOLDS: u32 = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Symmetry {
+    None,
+    // mirrored left and right of a vertical axis
+    Horizontal,
+    // mirrored above and below a horizontal axis
+    Vertical,
+    // both axes at once
+    Quad,
+    // n-fold rotation around the center
+    Radial(u32),
+}
+
+impl Symmetry {
+    pub fn from_name(name: &str, folds: u32) -> Option<Symmetry> {
+        match name {
+            "none" => Some(Symmetry::None),
+            "horizontal" => Some(Symmetry::Horizontal),
+            "vertical" => Some(Symmetry::Vertical),
+            "quad" => Some(Symmetry::Quad),
+            "radial" => Some(Symmetry::Radial(folds)),
+            _ => None,
+        }
+        .map(Symmetry::clamped)
+    }
+
+    // radial folds within 2..=MAX_FOLDS, whether typed in or read from a project file
+    pub fn clamped(self) -> Symmetry {
+        match self {
+            Symmetry::Radial(n) => Symmetry::Radial(n.clamp(2, MAX_FOLDS)),
+            symmetry => symmetry,
+        }
+    }
+
+    // every position a point is replicated to, the point itself first
+    pub fn points(self, center: (f64, f64), p: (f64, f64)) -> Vec<(f64, f64)> {
+        let (cx, cy) = center;
+        let (x, y) = p;
+
+        match self {
+            Symmetry::None => vec![p],
+            Symmetry::Horizontal => vec![p, (2.0 * cx - x, y)],
+            Symmetry::Vertical => vec![p, (x, 2.0 * cy - y)],
+            Symmetry::Quad => vec![
+                p,
+                (2.0 * cx - x, y),
+                (x, 2.0 * cy - y),
+                (2.0 * cx - x, 2.0 * cy - y),
+            ],
+            Symmetry::Radial(n) => (0..n)
+                .map(|i| {
+                    let angle = 2.0 * PI * i as f64 / n as f64;
+                    let (dx, dy) = (x - cx, y - cy);
+                    (
+                        cx + dx * angle.cos() - dy * angle.sin(),
+                        cy + dx * angle.sin() + dy * angle.cos(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    // guide lines through the center as (x0, y0, x1, y1), reaching `radius` out
+    pub fn axes(self, center: (f64, f64), radius: f64) -> Vec<(f64, f64, f64, f64)> {
+        let (cx, cy) = center;
+        let vertical = (cx, cy - radius, cx, cy + radius);
+        let horizontal = (cx - radius, cy, cx + radius, cy);
+
+        match self {
+            Symmetry::None => vec![],
+            Symmetry::Horizontal => vec![vertical],
+            Symmetry::Vertical => vec![horizontal],
+            Symmetry::Quad => vec![vertical, horizontal],
+            Symmetry::Radial(n) => (0..n)
+                .map(|i| {
+                    let angle = 2.0 * PI * i as f64 / n as f64 - PI / 2.0;
+                    (cx, cy, cx + radius * angle.cos(), cy + radius * angle.sin())
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rounded(points: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+        points
+            .into_iter()
+            .map(|(x, y)| ((x * 1000.0).round() / 1000.0, (y * 1000.0).round() / 1000.0))
+            .collect()
+    }
+
+    #[test]
+    fn mirrors_around_the_center() {
+        let center = (10.0, 20.0);
+        let p = (4.0, 25.0);
+        assert_eq!(Symmetry::None.points(center, p), vec![p]);
+        assert_eq!(
+            Symmetry::Horizontal.points(center, p),
+            vec![p, (16.0, 25.0)]
+        );
+        assert_eq!(Symmetry::Vertical.points(center, p), vec![p, (4.0, 15.0)]);
+        assert_eq!(
+            Symmetry::Quad.points(center, p),
+            vec![p, (16.0, 25.0), (4.0, 15.0), (16.0, 15.0)]
+        );
+    }
+
+    #[test]
+    fn radial_rotates_by_equal_steps() {
+        let points = rounded(Symmetry::Radial(4).points((0.0, 0.0), (1.0, 0.0)));
+        assert_eq!(
+            points,
+            vec![(1.0, 0.0), (0.0, 1.0), (-1.0, 0.0), (0.0, -1.0)]
+        );
+    }
+
+    #[test]
+    fn folds_are_clamped() {
+        assert_eq!(
+            Symmetry::from_name("radial", 100_000),
+            Some(Symmetry::Radial(MAX_FOLDS))
+        );
+        assert_eq!(Symmetry::from_name("radial", 0), Some(Symmetry::Radial(2)));
+        assert_eq!(Symmetry::Radial(7).clamped(), Symmetry::Radial(7));
+        assert_eq!(Symmetry::from_name("spiral", 3), None);
+    }
+}
@@ -1,99 +1,215 @@
+use image::{FilterType, RgbaImage};
 use std::cell::RefCell;
 use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{
-    window, CanvasRenderingContext2d, Document, Element, Event, HtmlCanvasElement,
-    HtmlImageElement, HtmlInputElement,
+    window, Document, Element, Event, HtmlCanvasElement, HtmlImageElement, HtmlInputElement,
+    HtmlSelectElement,
 };
 
-use crate::state::State;
-
+use crate::actions::{self, Actions};
+use crate::draw;
+use crate::editor::Mount;
+use crate::icons::Icons;
+use crate::notify;
+use crate::overlay::Overlay;
+use crate::palette;
+use crate::resize;
+use crate::state::{State, Tool};
+use crate::symmetry::{self, Symmetry};
+use crate::transform;
+use crate::widget::Widget;
+
+// names an embedding page can pick from, in toolbar order
+pub static TOOLS: [&str; 11] = [
+    "color",
+    "palette",
+    "pen",
+    "eraser",
+    "size",
+    "symmetry",
+    "grid",
+    "pixel-art",
+    "undo",
+    "redo",
+    "clear",
+];
+
+// `tools` leaves out every tool not named in it, `None` shows them all.
+// `icons` are the bundled ones unless the embedding page swapped some
 pub fn init_toolbar(
-    toolbar: &Element,
-    canvas: &HtmlCanvasElement,
-    preview: &Element,
+    mount: &Mount,
+    overlay: &Overlay,
+    actions: &Actions,
     state: &Rc<RefCell<State>>,
+    tools: Option<&[String]>,
+    icons: &Icons,
 ) -> Result<(), JsValue> {
     let document = window()
         .unwrap()
         .document()
         .expect("Could not find `document`");
+    let toolbar = &mount.toolbar;
+    let enabled = |tool: &str| tools.is_none_or(|tools| tools.iter().any(|t| t == tool));
 
-    // color picker
+    // color picker, the palette sets it too so it is built either way
     let color_pick = create_color_picker(&document, state)?;
-    toolbar.append_child(&color_pick)?;
+    if enabled("color") {
+        toolbar.append_child(&color_pick)?;
+    }
+
+    // palette
+    if enabled("palette") {
+        let palette = create_palette_element(&document, &color_pick, state)?;
+        toolbar.append_child(&palette)?;
+    }
 
     // pen
-    let pen = create_pen_element(&document, canvas)?;
-    toolbar.append_child(&pen)?;
+    if enabled("pen") {
+        let pen = create_pen_element(&document, icons, actions)?;
+        toolbar.append_child(&pen)?;
+    }
 
     // eraser
-    let eraser = create_eraser_element(&document, canvas)?;
-    toolbar.append_child(&eraser)?;
+    if enabled("eraser") {
+        let eraser = create_eraser_element(&document, icons, actions)?;
+        toolbar.append_child(&eraser)?;
+    }
 
     // pen thin
-    for thin in PEN_THIN.iter() {
-        let pen_thin = create_pen_thin_element(*thin, &document, state)?;
-        toolbar.append_child(&pen_thin)?;
+    if enabled("size") {
+        for thin in actions::PEN_THIN.iter() {
+            let pen_thin = create_pen_thin_element(*thin, &document, actions)?;
+            toolbar.append_child(&pen_thin)?;
+        }
+    }
+
+    // symmetry
+    if enabled("symmetry") {
+        let symmetry = create_symmetry_element(&document, overlay, state)?;
+        toolbar.append_child(&symmetry)?;
+    }
+
+    // grid
+    if enabled("grid") {
+        let grid = create_grid_element(&document, overlay, state)?;
+        toolbar.append_child(&grid)?;
+    }
+
+    // pixel art
+    if enabled("pixel-art") {
+        let pixel_art =
+            create_pixel_art_element(&document, &mount.canvas, overlay, &mount.preview, state)?;
+        toolbar.append_child(&pixel_art)?;
     }
 
     // undo
-    let undo = create_undo_element(&document, canvas, state)?;
-    toolbar.append_child(&undo)?;
+    if enabled("undo") {
+        let undo = create_undo_element(&document, icons, actions)?;
+        toolbar.append_child(&undo)?;
+    }
 
     // redo
-    let redo = create_redo_element(&document, canvas, state)?;
-    toolbar.append_child(&redo)?;
+    if enabled("redo") {
+        let redo = create_redo_element(&document, icons, actions)?;
+        toolbar.append_child(&redo)?;
+    }
 
     // clear
-    let clear = create_clear_element(&document, canvas, state)?;
-    toolbar.append_child(&clear)?;
-
-    let preview_toolbar = document.get_element_by_id("preview-toolbar").unwrap();
+    if enabled("clear") {
+        let clear = create_clear_element(&document, icons, actions)?;
+        toolbar.append_child(&clear)?;
+    }
 
     // add preview
-    let preview_image_list = create_preview_image_element(&document, canvas, preview, state)?;
-    preview_toolbar.append_child(&preview_image_list)?;
+    let preview_image_list = create_preview_image_element(&document, icons, actions)?;
+    mount.preview_toolbar.append_child(&preview_image_list)?;
 
     // clear all preview list
-    let preview_clear = create_preview_clear_element(&document, &preview, state)?;
-    preview_toolbar.append_child(&preview_clear)?;
+    let preview_clear = create_preview_clear_element(&document, icons, actions)?;
+    mount.preview_toolbar.append_child(&preview_clear)?;
+
+    // undo and redo follow the history, which every change to the document goes through
+    let mount_clone = mount.clone();
+    let state_clone = state.clone();
+    let handle_change = Closure::wrap(Box::new(move || {
+        notify::report("toolbar", render_state(&mount_clone, &state_clone.borrow()));
+    }) as Box<dyn FnMut()>);
+    let hooks = state.borrow().get_hooks();
+    hooks.on("change", handle_change.into_js_value().unchecked_into())?;
+    render_state(mount, &state.borrow())?;
 
     Ok(())
 }
 
-static PEN_THIN: [f64; 5] = [1.0, 4.0, 8.0, 10.0, 15.0];
+// mark the active tool and size, grey out undo and redo with nothing to step to,
+// and show the brush as the canvas cursor
+pub fn render_state(mount: &Mount, state: &State) -> Result<(), JsValue> {
+    let buttons = mount.toolbar.query_selector_all("[data-tool]")?;
+    for i in 0..buttons.length() {
+        let button = match buttons.get(i).and_then(|b| b.dyn_into::<Element>().ok()) {
+            Some(button) => button,
+            None => continue,
+        };
+        // `Some` for buttons that pick a tool, they are pressed or not
+        let (pressed, disabled) = match button.get_attribute("data-tool").as_deref() {
+            Some("pen") => (Some(state.get_tool() == Tool::Pen), false),
+            Some("eraser") => (Some(state.get_tool() == Tool::Eraser), false),
+            Some("size") => {
+                let size = button
+                    .get_attribute("data-size")
+                    .and_then(|s| s.parse().ok());
+                (Some(size == Some(state.get_pen_thin())), false)
+            }
+            Some("undo") => (None, !state.can_undo()),
+            Some("redo") => (None, !state.can_redo()),
+            _ => continue,
+        };
+        let classes = button.class_list();
+        classes.toggle_with_force("selected", pressed == Some(true))?;
+        classes.toggle_with_force("disabled", disabled)?;
+        if let Some(pressed) = pressed {
+            button.set_attribute("aria-pressed", &pressed.to_string())?;
+        }
+        button.set_attribute("aria-disabled", &disabled.to_string())?;
+    }
+
+    let size = if state.is_pixel_art() {
+        state.get_pen_thin().round().max(1.0)
+    } else {
+        state.get_pen_thin()
+    };
+    mount.canvas.style().set_property(
+        "cursor",
+        &draw::brush_cursor(size * f64::from(state.get_zoom()), state.is_pixel_art()),
+    )
+}
 
 fn create_pen_thin_element(
     thin: f64,
     document: &Document,
-    state: &Rc<RefCell<State>>,
+    actions: &Actions,
 ) -> Result<Element, JsValue> {
-    let element = document.create_element("div")?;
-
-    element.set_attribute(
-        "style",
-        "height: 50px; width: 50px; display: flex; align-items: center; justify-content: center; font-size: 11px; border: 1px solid #9b9b9b;",
-    )?;
-
-    let inner_element = document.create_element("div")?;
-
-    inner_element.set_attribute(
-        "style",
-        format!(
-            "border-radius: 50%; background-color: black; width: {}px; height: {}px;",
-            thin + 2.0,
-            thin + 2.0
-        )
-        .as_str(),
-    )?;
+    let element = Widget::new("div")
+        .class("tool")
+        .class("size")
+        .attr("role", "button")
+        .data("tool", "size")
+        .data("size", &thin.to_string())
+        .label(&format!("size {}", thin), None)
+        .build(document)?;
+
+    let inner_element = Widget::new("div")
+        .class("size-dot")
+        .var("size", &format!("{}px", thin + 2.0))
+        .build(document)?;
     element.append_child(&inner_element)?;
 
-    let state = state.clone();
+    let actions = actions.clone();
 
     let handle_click = Closure::wrap(Box::new(move || {
-        state.borrow_mut().set_pen_thin(thin);
+        notify::report("pen size", actions.set_pen_thin(thin));
     }) as Box<dyn FnMut()>);
 
     element.add_event_listener_with_callback("click", handle_click.as_ref().unchecked_ref())?;
@@ -103,59 +219,305 @@ fn create_pen_thin_element(
     Ok(element)
 }
 
-fn create_pen_element(document: &Document, canvas: &HtmlCanvasElement) -> Result<Element, JsValue> {
-    let element = document.create_element("div")?;
-    element.set_attribute(
-        "style",
-        "height: 50px; width: 50px; display: flex; align-items: center; justify-content: center; font-size: 11px; border: 1px solid #9b9b9b; background-image:url(https://image.flaticon.com/icons/svg/760/760400.svg); background-size: 100%;",
-    )?;
+// a tool button showing `icon`, with `label` for its tooltip and for screen readers
+fn create_icon_element(
+    document: &Document,
+    icons: &Icons,
+    icon: &str,
+    label: &str,
+    shortcut: Option<&str>,
+) -> Result<Element, JsValue> {
+    Widget::new("div")
+        .class("tool")
+        .class("icon")
+        .attr("role", "button")
+        .data("tool", icon)
+        .var("icon", &icons.css_url(icon))
+        .label(label, shortcut)
+        .build(document)
+}
 
-    let context = canvas
-        .get_context("2d")
-        .expect("Could not get context")
-        .unwrap()
-        .dyn_into::<CanvasRenderingContext2d>()
-        .unwrap();
+fn create_pen_element(
+    document: &Document,
+    icons: &Icons,
+    actions: &Actions,
+) -> Result<Element, JsValue> {
+    let element = create_icon_element(document, icons, "pen", "pen", Some("b"))?;
 
+    let actions = actions.clone();
     let handle_click = Closure::wrap(Box::new(move || {
-        context
-            .set_global_composite_operation("source-over")
-            .unwrap();
+        notify::report("pen", actions.pen());
     }) as Box<dyn FnMut()>);
-
     element.add_event_listener_with_callback("click", handle_click.as_ref().unchecked_ref())?;
-
     handle_click.forget();
 
     Ok(element)
 }
 
 fn create_eraser_element(
+    document: &Document,
+    icons: &Icons,
+    actions: &Actions,
+) -> Result<Element, JsValue> {
+    let element = create_icon_element(document, icons, "eraser", "eraser", Some("e"))?;
+
+    let actions = actions.clone();
+    let handle_click = Closure::wrap(Box::new(move || {
+        notify::report("eraser", actions.eraser());
+    }) as Box<dyn FnMut()>);
+    element.add_event_listener_with_callback("click", handle_click.as_ref().unchecked_ref())?;
+    handle_click.forget();
+
+    Ok(element)
+}
+
+static SYMMETRY_MODES: [&str; 5] = ["none", "horizontal", "vertical", "quad", "radial"];
+
+fn create_symmetry_element(
+    document: &Document,
+    overlay: &Overlay,
+    state: &Rc<RefCell<State>>,
+) -> Result<Element, JsValue> {
+    let element = Widget::new("div")
+        .class("tool")
+        .class("panel")
+        .data("tool", "symmetry")
+        .attr(
+            "title",
+            "symmetry (alt+click on the canvas moves the center)",
+        )
+        .build(document)?;
+
+    let select = document
+        .create_element("select")?
+        .dyn_into::<HtmlSelectElement>()?;
+    for mode in SYMMETRY_MODES.iter() {
+        let option = document.create_element("option")?;
+        option.set_attribute("value", mode)?;
+        option.set_inner_html(mode);
+        select.append_child(&option)?;
+    }
+
+    let folds = document
+        .create_element("input")?
+        .dyn_into::<HtmlInputElement>()?;
+    folds.set_attribute("type", "number")?;
+    folds.set_attribute("min", "2")?;
+    folds.set_attribute("max", &symmetry::MAX_FOLDS.to_string())?;
+    folds.set_attribute("class", "drawasm-number")?;
+    folds.set_value("6");
+
+    let state = state.clone();
+    let overlay = overlay.clone();
+    let (select_clone, folds_clone) = (select.clone(), folds.clone());
+    let handle_change = Closure::wrap(Box::new(move || {
+        let folds: u32 = folds_clone.value().parse().unwrap_or(2);
+        if let Some(symmetry) = Symmetry::from_name(&select_clone.value(), folds) {
+            state.borrow_mut().set_symmetry(symmetry);
+            overlay.render(&state);
+        }
+    }) as Box<dyn FnMut()>);
+    select.add_event_listener_with_callback("change", handle_change.as_ref().unchecked_ref())?;
+    folds.add_event_listener_with_callback("change", handle_change.as_ref().unchecked_ref())?;
+    handle_change.forget();
+
+    element.append_child(&select)?;
+    element.append_child(&folds)?;
+
+    Ok(element)
+}
+
+fn create_grid_element(
+    document: &Document,
+    overlay: &Overlay,
+    state: &Rc<RefCell<State>>,
+) -> Result<Element, JsValue> {
+    let element = Widget::new("div")
+        .class("tool")
+        .class("panel")
+        .data("tool", "grid")
+        .build(document)?;
+
+    let visible = create_checkbox(document, &element, "grid")?;
+    let snap = create_checkbox(document, &element, "snap")?;
+
+    let spacing = document
+        .create_element("input")?
+        .dyn_into::<HtmlInputElement>()?;
+    spacing.set_attribute("type", "number")?;
+    spacing.set_attribute("min", "2")?;
+    spacing.set_attribute("title", "grid spacing")?;
+    spacing.set_attribute("class", "drawasm-number")?;
+    spacing.set_value(format!("{}", state.borrow().get_grid().spacing).as_str());
+    element.append_child(&spacing)?;
+
+    let state = state.clone();
+    let overlay = overlay.clone();
+    let (visible_clone, snap_clone, spacing_clone) =
+        (visible.clone(), snap.clone(), spacing.clone());
+    let handle_change = Closure::wrap(Box::new(move || {
+        let mut grid = state.borrow().get_grid();
+        grid.visible = visible_clone.checked();
+        grid.snap = snap_clone.checked();
+        if let Ok(v) = spacing_clone.value().parse::<u32>() {
+            grid.spacing = v.max(2);
+        }
+        state.borrow_mut().set_grid(grid);
+        overlay.render(&state);
+    }) as Box<dyn FnMut()>);
+    for input in [&visible, &snap, &spacing].iter() {
+        input.add_event_listener_with_callback("change", handle_change.as_ref().unchecked_ref())?;
+    }
+    handle_change.forget();
+
+    Ok(element)
+}
+
+pub fn create_checkbox(
+    document: &Document,
+    parent: &Element,
+    label: &str,
+) -> Result<HtmlInputElement, JsValue> {
+    let wrapper = document.create_element("label")?;
+    let input = document
+        .create_element("input")?
+        .dyn_into::<HtmlInputElement>()?;
+    input.set_attribute("type", "checkbox")?;
+    wrapper.append_child(&input)?;
+    let text = document.create_element("span")?;
+    text.set_inner_html(label);
+    wrapper.append_child(&text)?;
+    parent.append_child(&wrapper)?;
+
+    Ok(input)
+}
+
+static PIXEL_ART_SIZES: [&str; 5] = ["off", "16", "32", "64", "128"];
+
+fn create_pixel_art_element(
     document: &Document,
     canvas: &HtmlCanvasElement,
+    overlay: &Overlay,
+    preview: &Element,
+    state: &Rc<RefCell<State>>,
 ) -> Result<Element, JsValue> {
-    let element = document.create_element("div")?;
-    element.set_attribute(
-        "style",
-        "height: 50px; width: 50px; display: flex; align-items: center; justify-content: center; font-size: 11px; border: 1px solid #9b9b9b; background-image:url(https://image.flaticon.com/icons/svg/200/200404.svg); background-size: 100%;",
-    )?;
+    let element = Widget::new("div")
+        .class("tool")
+        .class("panel")
+        .data("tool", "pixel-art")
+        .attr("title", "pixel art document width")
+        .build(document)?;
+    let label = Widget::new("span").text("pixel art").build(document)?;
+    element.append_child(&label)?;
+
+    let select = document
+        .create_element("select")?
+        .dyn_into::<HtmlSelectElement>()?;
+    for size in PIXEL_ART_SIZES.iter() {
+        let option = document.create_element("option")?;
+        option.set_attribute("value", size)?;
+        option.set_inner_html(size);
+        select.append_child(&option)?;
+    }
+    element.append_child(&select)?;
 
-    let context = canvas
-        .get_context("2d")
-        .expect("Could not get context")
-        .unwrap()
-        .dyn_into::<CanvasRenderingContext2d>()
-        .unwrap();
+    let document = document.clone();
+    let canvas = canvas.clone();
+    let overlay = overlay.clone();
+    let preview = preview.clone();
+    let state = state.clone();
+    let select_clone = select.clone();
+    let mut previous = select.value();
+
+    let handle_change = Closure::wrap(Box::new(move || {
+        // resampling every frame with nearest can not be taken back, undo history included
+        let has_work = {
+            let state = state.borrow();
+            state.get_preview_image_len() > 0 || !state.get_strokes().is_empty() || state.can_undo()
+        };
+        if has_work {
+            let message = "Switching pixel art resamples the canvas and every frame and clears the undo history, the original pixels can not be restored. Continue?";
+            match window().unwrap().confirm_with_message(message) {
+                Ok(true) => {}
+                Ok(false) => {
+                    select_clone.set_value(&previous);
+                    return;
+                }
+                Err(err) => {
+                    select_clone.set_value(&previous);
+                    notify::error("pixel art", &err.into());
+                    return;
+                }
+            }
+        }
+        previous = select_clone.value();
 
-    let handle_click = Closure::wrap(Box::new(move || {
-        context
-            .set_global_composite_operation("destination-out")
-            .unwrap();
+        let (vw, vh) = state.borrow().get_viewport();
+
+        // the document keeps the aspect ratio of the drawing area
+        let (w, h) = match select_clone.value().parse::<u32>() {
+            Ok(w) => {
+                let h = ((w as f64 * vh as f64 / vw as f64).round() as u32).max(1);
+                state.borrow_mut().set_pixel_art(true, vw / w);
+                (w, h)
+            }
+            Err(_) => {
+                state.borrow_mut().set_pixel_art(false, 1);
+                (vw, vh)
+            }
+        };
+
+        let op: resize::FrameOp =
+            Box::new(move |img: &RgbaImage| transform::scale_image(img, w, h, FilterType::Nearest));
+        notify::report(
+            "pixel art",
+            resize::apply_to_document(&document, &canvas, &overlay, &preview, &state, &op),
+        );
     }) as Box<dyn FnMut()>);
+    select.add_event_listener_with_callback("change", handle_change.as_ref().unchecked_ref())?;
+    handle_change.forget();
 
-    element.add_event_listener_with_callback("click", handle_click.as_ref().unchecked_ref())?;
+    Ok(element)
+}
 
-    handle_click.forget();
+// swatches for the indexed palette, the transparent entry is left to the eraser
+fn create_palette_element(
+    document: &Document,
+    color_pick: &Element,
+    state: &Rc<RefCell<State>>,
+) -> Result<Element, JsValue> {
+    let element = Widget::new("div")
+        .class("tool")
+        .class("palette")
+        .data("tool", "palette")
+        .build(document)?;
+
+    let input = color_pick
+        .query_selector("input")?
+        .unwrap()
+        .dyn_into::<HtmlInputElement>()?;
+
+    for color in state.borrow().get_palette().iter().filter(|c| c[3] != 0) {
+        let hex = palette::to_hex(*color);
+        let swatch = Widget::new("div")
+            .class("swatch")
+            .attr("role", "button")
+            .data("color", &hex)
+            .var("swatch", &hex)
+            .label(&hex, None)
+            .build(document)?;
+
+        let state = state.clone();
+        let input = input.clone();
+        let handle_click = Closure::wrap(Box::new(move || {
+            input.set_value(&hex);
+            state.borrow_mut().set_color(hex.clone());
+        }) as Box<dyn FnMut()>);
+        swatch.add_event_listener_with_callback("click", handle_click.as_ref().unchecked_ref())?;
+        handle_click.forget();
+
+        element.append_child(&swatch)?;
+    }
 
     Ok(element)
 }
@@ -164,11 +526,10 @@ fn create_color_picker(
     document: &Document,
     state: &Rc<RefCell<State>>,
 ) -> Result<Element, JsValue> {
-    let element = document.create_element("div")?;
-    element.set_attribute(
-        "style",
-        "height: 50px; width: 50px; display: flex; align-items: center; justify-content: center; font-size: 11px; border: 1px solid #9b9b9b;",
-    )?;
+    let element = Widget::new("div")
+        .class("tool")
+        .data("tool", "color")
+        .build(document)?;
 
     let input = document
         .create_element("input")?
@@ -176,6 +537,7 @@ fn create_color_picker(
 
     input.set_attribute("type", "color")?;
     input.set_attribute("value", "#000000")?;
+    input.set_attribute("aria-label", "color")?;
 
     let state = state.clone();
     let picked_color = Closure::wrap(Box::new(move |e: Event| {
@@ -192,40 +554,14 @@ fn create_color_picker(
 
 fn create_undo_element(
     document: &Document,
-    canvas: &HtmlCanvasElement,
-    state: &Rc<RefCell<State>>,
+    icons: &Icons,
+    actions: &Actions,
 ) -> Result<Element, JsValue> {
-    let element = document.create_element("div")?;
-    element.set_attribute(
-        "style",
-        "height: 50px; width: 50px; display: flex; align-items: center; justify-content: center; font-size: 11px; border: 1px solid #9b9b9b; background-image:url(https://image.flaticon.com/icons/svg/1/1453.svg); background-size: 100%;",
-    )?;
-
-    let context = canvas
-        .get_context("2d")
-        .expect("Could not get context")
-        .unwrap()
-        .dyn_into::<CanvasRenderingContext2d>()
-        .unwrap();
-    let state = state.clone();
+    let element = create_icon_element(document, icons, "undo", "undo", Some("ctrl+z"))?;
 
+    let actions = actions.clone();
     let handle_click = Closure::wrap(Box::new(move || {
-        let undo = state.borrow_mut().get_undo();
-        match undo {
-            Some(u) => {
-                let image_data = context
-                    .get_image_data(
-                        0.0,
-                        0.0,
-                        state.borrow().get_width() as f64,
-                        state.borrow().get_height() as f64,
-                    )
-                    .unwrap();
-                state.borrow_mut().add_redo(image_data);
-                context.put_image_data(&u, 0.0, 0.0).unwrap();
-            }
-            None => {}
-        }
+        notify::report("undo", actions.undo());
     }) as Box<dyn FnMut()>);
     element.add_event_listener_with_callback("click", handle_click.as_ref().unchecked_ref())?;
     handle_click.forget();
@@ -235,40 +571,14 @@ fn create_undo_element(
 
 fn create_redo_element(
     document: &Document,
-    canvas: &HtmlCanvasElement,
-    state: &Rc<RefCell<State>>,
+    icons: &Icons,
+    actions: &Actions,
 ) -> Result<Element, JsValue> {
-    let element = document.create_element("div")?;
-    element.set_attribute(
-        "style",
-        "height: 50px; width: 50px; display: flex; align-items: center; justify-content: center; font-size: 11px; border: 1px solid #9b9b9b; background-image:url(https://image.flaticon.com/icons/svg/74/74474.svg); background-size: 100%;",
-    )?;
-
-    let context = canvas
-        .get_context("2d")
-        .expect("Could not get context")
-        .unwrap()
-        .dyn_into::<CanvasRenderingContext2d>()
-        .unwrap();
-    let state = state.clone();
+    let element = create_icon_element(document, icons, "redo", "redo", Some("ctrl+shift+z"))?;
 
+    let actions = actions.clone();
     let handle_click = Closure::wrap(Box::new(move || {
-        let redo = state.borrow_mut().get_redo();
-        match redo {
-            Some(r) => {
-                let image_data = context
-                    .get_image_data(
-                        0.0,
-                        0.0,
-                        state.borrow().get_width() as f64,
-                        state.borrow().get_height() as f64,
-                    )
-                    .unwrap();
-                state.borrow_mut().add_undo(image_data);
-                context.put_image_data(&r, 0.0, 0.0).unwrap();
-            }
-            None => {}
-        }
+        notify::report("redo", actions.redo());
     }) as Box<dyn FnMut()>);
     element.add_event_listener_with_callback("click", handle_click.as_ref().unchecked_ref())?;
     handle_click.forget();
@@ -278,39 +588,14 @@ fn create_redo_element(
 
 fn create_clear_element(
     document: &Document,
-    canvas: &HtmlCanvasElement,
-    state: &Rc<RefCell<State>>,
+    icons: &Icons,
+    actions: &Actions,
 ) -> Result<Element, JsValue> {
-    let element = document.create_element("div")?;
-    element.set_attribute(
-        "style",
-        "height: 50px; width: 50px; display: flex; align-items: center; justify-content: center; font-size: 11px; border: 1px solid #9b9b9b; background-image:url(https://image.flaticon.com/icons/svg/35/35480.svg); background-size: 100%;",
-    )?;
-
-    let context = canvas
-        .get_context("2d")
-        .expect("Could not get context")
-        .unwrap()
-        .dyn_into::<CanvasRenderingContext2d>()
-        .unwrap();
-    let state = state.clone();
+    let element = create_icon_element(document, icons, "clear", "clear", None)?;
 
+    let actions = actions.clone();
     let handle_click = Closure::wrap(Box::new(move || {
-        let image_data = context
-            .get_image_data(
-                0.0,
-                0.0,
-                state.borrow().get_width() as f64,
-                state.borrow().get_height() as f64,
-            )
-            .unwrap();
-        state.borrow_mut().add_undo(image_data);
-        context.clear_rect(
-            0.0,
-            0.0,
-            state.borrow().get_width() as f64,
-            state.borrow().get_height() as f64,
-        );
+        notify::report("clear", actions.clear());
     }) as Box<dyn FnMut()>);
     element.add_event_listener_with_callback("click", handle_click.as_ref().unchecked_ref())?;
     handle_click.forget();
@@ -320,37 +605,14 @@ fn create_clear_element(
 
 fn create_preview_image_element(
     document: &Document,
-    canvas: &HtmlCanvasElement,
-    preview: &Element,
-    state: &Rc<RefCell<State>>,
+    icons: &Icons,
+    actions: &Actions,
 ) -> Result<Element, JsValue> {
-    let element = document.create_element("div")?;
-    element.set_attribute(
-        "style",
-        "height: 50px; width: 50px; display: flex; align-items: center; justify-content: center; background-image:url(https://image.flaticon.com/icons/svg/1562/1562881.svg); background-size: 100%;",
-    )?;
-
-    let canvas = canvas.clone();
-    let state = state.clone();
-    let preview = preview.clone();
-    let document_copy = document.clone();
+    let element = create_icon_element(document, icons, "add-frame", "add frame", Some("n"))?;
 
+    let actions = actions.clone();
     let handle_click = Closure::wrap(Box::new(move || {
-        let img = document_copy
-            .create_element("img")
-            .unwrap()
-            .dyn_into::<HtmlImageElement>()
-            .unwrap();
-
-        let url = canvas.to_data_url_with_type("image/png").unwrap();
-        state.borrow_mut().add_preview_image(url.clone());
-
-        // img set_src URL string
-        img.set_src(&url);
-        img.set_attribute("class", "preview-img").unwrap();
-        img.set_width(state.borrow().get_preview_width());
-        img.set_height(state.borrow().get_preview_height());
-        preview.append_child(&img).unwrap();
+        notify::report("add frame", actions.add_frame());
     }) as Box<dyn FnMut()>);
     element.add_event_listener_with_callback("click", handle_click.as_ref().unchecked_ref())?;
     handle_click.forget();
@@ -360,36 +622,73 @@ fn create_preview_image_element(
 
 fn create_preview_clear_element(
     document: &Document,
-    preview: &Element,
-    state: &Rc<RefCell<State>>,
+    icons: &Icons,
+    actions: &Actions,
 ) -> Result<Element, JsValue> {
-    let element = document.create_element("div")?;
-    element.set_attribute(
-        "style",
-        "height: 50px; width: 50px; margin-left: 1em; display: flex; align-items: center; justify-content: center; background-image:url(https://image.flaticon.com/icons/svg/1276/1276490.svg); background-size: 100%;",
-    )?;
-
-    let state = state.clone();
-    let preview = preview.clone();
+    let element = create_icon_element(document, icons, "clear-frames", "clear all frames", None)?;
 
+    let actions = actions.clone();
     let handle_click = Closure::wrap(Box::new(move || {
-        let mut child_has = true;
-        while child_has {
-            let mut i = preview.child_nodes().length() - 1;
-            match preview.child_nodes().item(i) {
-                Some(v) => {
-                    preview.remove_child(&v).unwrap();
-                }
-                None => {
-                    child_has = false;
-                }
-            }
-            i -= 1;
-        }
-        state.borrow_mut().delete_all_images();
+        notify::report("clear all frames", actions.clear_frames());
     }) as Box<dyn FnMut()>);
     element.add_event_listener_with_callback("click", handle_click.as_ref().unchecked_ref())?;
     handle_click.forget();
 
     Ok(element)
 }
+
+fn append_preview_image(
+    document: &Document,
+    preview: &Element,
+    state: &Rc<RefCell<State>>,
+    url: &str,
+    selected: bool,
+) -> Result<(), JsValue> {
+    let img = document
+        .create_element("img")?
+        .dyn_into::<HtmlImageElement>()?;
+
+    // img set_src URL string
+    img.set_src(url);
+    img.set_attribute(
+        "class",
+        if selected {
+            "preview-img selected"
+        } else {
+            "preview-img"
+        },
+    )?;
+    img.set_width(state.borrow().get_preview_width());
+    img.set_height(state.borrow().get_preview_height());
+    if state.borrow().is_pixel_art() {
+        img.set_attribute("style", "image-rendering: pixelated;")?;
+    }
+    preview.append_child(&img)?;
+
+    Ok(())
+}
+
+// rebuild the preview list from the frames in `State`
+pub fn render_preview(
+    document: &Document,
+    preview: &Element,
+    state: &Rc<RefCell<State>>,
+) -> Result<(), JsValue> {
+    while let Some(v) = preview.last_child() {
+        preview.remove_child(&v)?;
+    }
+
+    let images = state.borrow().get_preview_image();
+    let current = state.borrow().get_current_frame();
+    for (i, url) in images.iter().enumerate() {
+        append_preview_image(document, preview, state, url, current == Some(i))?;
+    }
+
+    // every change to the frame list ends up here
+    let state = state.borrow();
+    state
+        .get_hooks()
+        .frames_changed(current, &state.get_frame_delays());
+
+    Ok(())
+}
@@ -0,0 +1,237 @@
+use image::{imageops, FilterType, RgbaImage};
+
+// where the existing picture is pinned when the canvas size changes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    pub fn all() -> [Anchor; 9] {
+        [
+            Anchor::TopLeft,
+            Anchor::Top,
+            Anchor::TopRight,
+            Anchor::Left,
+            Anchor::Center,
+            Anchor::Right,
+            Anchor::BottomLeft,
+            Anchor::Bottom,
+            Anchor::BottomRight,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Anchor::TopLeft => "top-left",
+            Anchor::Top => "top",
+            Anchor::TopRight => "top-right",
+            Anchor::Left => "left",
+            Anchor::Center => "center",
+            Anchor::Right => "right",
+            Anchor::BottomLeft => "bottom-left",
+            Anchor::Bottom => "bottom",
+            Anchor::BottomRight => "bottom-right",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Anchor> {
+        Anchor::all().iter().find(|a| a.name() == name).copied()
+    }
+
+    // offset of the old picture inside the new canvas (may be negative when shrinking)
+    fn offset(self, old_w: u32, old_h: u32, new_w: u32, new_h: u32) -> (i64, i64) {
+        let dx = new_w as i64 - old_w as i64;
+        let dy = new_h as i64 - old_h as i64;
+
+        let x = match self {
+            Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => 0,
+            Anchor::Top | Anchor::Center | Anchor::Bottom => dx / 2,
+            Anchor::TopRight | Anchor::Right | Anchor::BottomRight => dx,
+        };
+        let y = match self {
+            Anchor::TopLeft | Anchor::Top | Anchor::TopRight => 0,
+            Anchor::Left | Anchor::Center | Anchor::Right => dy / 2,
+            Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => dy,
+        };
+
+        (x, y)
+    }
+}
+
+// largest canvas side, a typo in a size field must not ask for gigabytes of pixels
+pub static MAX_SIDE: u32 = 4096;
+
+pub static FILTERS: [(&str, FilterType); 5] = [
+    ("nearest", FilterType::Nearest),
+    ("bilinear", FilterType::Triangle),
+    ("bicubic", FilterType::CatmullRom),
+    ("gaussian", FilterType::Gaussian),
+    ("lanczos", FilterType::Lanczos3),
+];
+
+pub fn filter_from_name(name: &str) -> Option<FilterType> {
+    FILTERS.iter().find(|(n, _)| *n == name).map(|(_, f)| *f)
+}
+
+// change the canvas size without scaling, padding with transparent pixels or cutting off
+pub fn resize_canvas(img: &RgbaImage, w: u32, h: u32, anchor: Anchor) -> RgbaImage {
+    let mut out = RgbaImage::new(w, h);
+    let (ox, oy) = anchor.offset(img.width(), img.height(), w, h);
+
+    for (x, y, pixel) in img.enumerate_pixels() {
+        let nx = x as i64 + ox;
+        let ny = y as i64 + oy;
+        if nx >= 0 && ny >= 0 && nx < w as i64 && ny < h as i64 {
+            out.put_pixel(nx as u32, ny as u32, *pixel);
+        }
+    }
+
+    out
+}
+
+pub fn scale_image(img: &RgbaImage, w: u32, h: u32, filter: FilterType) -> RgbaImage {
+    imageops::resize(img, w, h, filter)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// smallest rectangle containing every non transparent pixel of all frames
+pub fn content_bounds(images: &[RgbaImage]) -> Option<Rect> {
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+
+    for img in images {
+        for (x, y, pixel) in img.enumerate_pixels() {
+            if pixel[3] == 0 {
+                continue;
+            }
+            bounds = Some(match bounds {
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                None => (x, y, x, y),
+            });
+        }
+    }
+
+    bounds.map(|(x0, y0, x1, y1)| Rect {
+        x: x0,
+        y: y0,
+        width: x1 - x0 + 1,
+        height: y1 - y0 + 1,
+    })
+}
+
+pub fn crop(img: &RgbaImage, rect: Rect) -> RgbaImage {
+    RgbaImage::from_fn(rect.width, rect.height, |x, y| {
+        *img.get_pixel(rect.x + x, rect.y + y)
+    })
+}
+
+// `w`x`h` centered inside `max_w`x`max_h`, scaled down to fit but never enlarged,
+// as `(x, y, width, height)`
+pub fn contain(w: f64, h: f64, max_w: f64, max_h: f64) -> (f64, f64, f64, f64) {
+    if w <= 0.0 || h <= 0.0 {
+        return (0.0, 0.0, 0.0, 0.0);
+    }
+    let scale = (max_w / w).min(max_h / h).min(1.0);
+    let (fit_w, fit_h) = (w * scale, h * scale);
+
+    ((max_w - fit_w) / 2.0, (max_h - fit_h) / 2.0, fit_w, fit_h)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    static RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+    fn dot(w: u32, h: u32, x: u32, y: u32) -> RgbaImage {
+        let mut img = RgbaImage::new(w, h);
+        img.put_pixel(x, y, RED);
+        img
+    }
+
+    fn find_dot(img: &RgbaImage) -> Option<(u32, u32)> {
+        img.enumerate_pixels()
+            .find(|(_, _, p)| **p == RED)
+            .map(|(x, y, _)| (x, y))
+    }
+
+    #[test]
+    fn resize_pins_the_picture_to_the_anchor() {
+        let img = dot(2, 2, 0, 0);
+        let cases = [
+            (Anchor::TopLeft, (0, 0)),
+            (Anchor::Top, (1, 0)),
+            (Anchor::BottomRight, (2, 2)),
+            (Anchor::Center, (1, 1)),
+            (Anchor::Left, (0, 1)),
+        ];
+        for (anchor, expected) in cases.iter() {
+            let out = resize_canvas(&img, 4, 4, *anchor);
+            assert_eq!((out.width(), out.height()), (4, 4));
+            assert_eq!(find_dot(&out), Some(*expected), "{:?}", anchor);
+        }
+
+        // shrinking cuts off from the side away from the anchor
+        let img = dot(4, 4, 3, 3);
+        assert_eq!(find_dot(&resize_canvas(&img, 2, 2, Anchor::TopLeft)), None);
+        assert_eq!(
+            find_dot(&resize_canvas(&img, 2, 2, Anchor::BottomRight)),
+            Some((1, 1))
+        );
+    }
+
+    #[test]
+    fn bounds_cover_every_frame() {
+        let frames = vec![dot(5, 4, 1, 2), dot(5, 4, 3, 1)];
+        assert_eq!(
+            content_bounds(&frames),
+            Some(Rect {
+                x: 1,
+                y: 1,
+                width: 3,
+                height: 2,
+            })
+        );
+
+        assert_eq!(content_bounds(&[RgbaImage::new(3, 3)]), None);
+        assert_eq!(content_bounds(&[]), None);
+    }
+
+    #[test]
+    fn crop_keeps_content_touching_the_edges() {
+        let frames = vec![dot(4, 3, 0, 0), dot(4, 3, 3, 2)];
+        let rect = content_bounds(&frames).unwrap();
+        assert_eq!(
+            rect,
+            Rect {
+                x: 0,
+                y: 0,
+                width: 4,
+                height: 3,
+            }
+        );
+        let cropped = crop(&frames[1], rect);
+        assert_eq!(find_dot(&cropped), Some((3, 2)));
+
+        let frames = vec![dot(4, 3, 3, 1)];
+        let cropped = crop(&frames[0], content_bounds(&frames).unwrap());
+        assert_eq!((cropped.width(), cropped.height()), (1, 1));
+        assert_eq!(find_dot(&cropped), Some((0, 0)));
+    }
+}
@@ -0,0 +1,120 @@
+use wasm_bindgen::prelude::*;
+use web_sys::{Document, Element};
+
+// an element described by semantic classes, data attributes and css variables,
+// how it looks is left to the stylesheet so a host page can restyle it
+pub struct Widget {
+    tag: &'static str,
+    classes: Vec<String>,
+    attributes: Vec<(String, String)>,
+    // css custom properties, for the few values only known at runtime
+    vars: Vec<(String, String)>,
+    text: Option<String>,
+}
+
+impl Widget {
+    pub fn new(tag: &'static str) -> Widget {
+        Widget {
+            tag,
+            classes: vec![],
+            attributes: vec![],
+            vars: vec![],
+            text: None,
+        }
+    }
+
+    // `drawasm-` is prefixed, e.g. `tool` becomes `drawasm-tool`
+    pub fn class(mut self, name: &str) -> Widget {
+        self.classes.push(format!("drawasm-{}", name));
+        self
+    }
+
+    pub fn data(self, key: &str, value: &str) -> Widget {
+        self.attr(&format!("data-{}", key), value)
+    }
+
+    pub fn attr(mut self, key: &str, value: &str) -> Widget {
+        self.attributes.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    // read in the stylesheet as `var(--drawasm-<name>)`
+    pub fn var(mut self, name: &str, value: &str) -> Widget {
+        self.vars
+            .push((format!("--drawasm-{}", name), value.to_string()));
+        self
+    }
+
+    pub fn text(mut self, text: &str) -> Widget {
+        self.text = Some(text.to_string());
+        self
+    }
+
+    // a tooltip that screen readers announce too
+    pub fn label(self, label: &str, shortcut: Option<&str>) -> Widget {
+        let title = match shortcut {
+            Some(key) => format!("{} ({})", label, key),
+            None => label.to_string(),
+        };
+        self.attr("aria-label", label).attr("title", &title)
+    }
+
+    // every attribute the element gets, classes and variables included
+    fn attributes(&self) -> Vec<(String, String)> {
+        let mut attributes = vec![];
+        if !self.classes.is_empty() {
+            attributes.push(("class".to_string(), self.classes.join(" ")));
+        }
+        attributes.extend(self.attributes.iter().cloned());
+        if !self.vars.is_empty() {
+            let style: Vec<String> = self
+                .vars
+                .iter()
+                .map(|(name, value)| format!("{}: {};", name, value))
+                .collect();
+            attributes.push(("style".to_string(), style.join(" ")));
+        }
+        attributes
+    }
+
+    pub fn build(self, document: &Document) -> Result<Element, JsValue> {
+        let element = document.create_element(self.tag)?;
+        for (key, value) in self.attributes() {
+            element.set_attribute(&key, &value)?;
+        }
+        if let Some(text) = &self.text {
+            element.set_text_content(Some(text));
+        }
+        Ok(element)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emits_classes_data_and_vars() {
+        let widget = Widget::new("div")
+            .class("tool")
+            .class("icon")
+            .data("tool", "pen")
+            .var("icon", "url(\"pen.svg\")")
+            .label("pen", Some("b"));
+
+        assert_eq!(
+            widget.attributes(),
+            vec![
+                ("class".to_string(), "drawasm-tool drawasm-icon".to_string()),
+                ("data-tool".to_string(), "pen".to_string()),
+                ("aria-label".to_string(), "pen".to_string()),
+                ("title".to_string(), "pen (b)".to_string()),
+                (
+                    "style".to_string(),
+                    "--drawasm-icon: url(\"pen.svg\");".to_string()
+                ),
+            ]
+        );
+        assert!(Widget::new("span").attributes().is_empty());
+    }
+}
@@ -0,0 +1,331 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{DedicatedWorkerGlobalScope, ErrorEvent, MessageEvent, Worker};
+
+use crate::budget::{self, Budget};
+use crate::codec;
+use crate::error::Error;
+use crate::generate::{self, Job};
+use crate::palette::Color;
+use crate::presets::{self, Violation};
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console, js_name = error)]
+    fn console_error(msg: &str);
+}
+
+// bundled from app/worker.js, it instantiates this same wasm module and calls `encode_job`.
+// hosts that serve it from elsewhere pass `workerUrl` to the editor
+pub static WORKER_URL: &str = "./worker.js";
+
+type Handler = Closure<dyn FnMut(JsValue)>;
+
+// what the worker makes of the frames it is sent along with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Task {
+    // the apng as the generate panel is set up
+    Generate(Job),
+    // shrunk until it fits, pixel art keeps its palette
+    Budget {
+        delays: Vec<f64>,
+        palette: Option<Vec<Color>>,
+        budget: Budget,
+    },
+    // checked against a preset by name, `fix` changes the animation to follow it first
+    Preset {
+        delays: Vec<f64>,
+        palette: Option<Vec<Color>>,
+        preset: String,
+        fix: bool,
+    },
+}
+
+// how a task went, besides the apng itself
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Outcome {
+    // a line on the size and on what had to give
+    pub report: String,
+    // false when the apng misses the budget or the preset and is not worth downloading
+    pub accepted: bool,
+    pub violations: Vec<Violation>,
+}
+
+impl Task {
+    pub fn run(
+        &self,
+        images: Vec<RgbaImage>,
+        progress: &mut dyn FnMut(&str, usize, usize),
+    ) -> Result<(Vec<u8>, Outcome), Error> {
+        match self {
+            Task::Generate(job) => {
+                let (apng, report) = generate::generate(images, job, progress)?;
+                Ok((
+                    apng,
+                    Outcome {
+                        report,
+                        accepted: true,
+                        violations: vec![],
+                    },
+                ))
+            }
+            Task::Budget {
+                delays,
+                palette,
+                budget,
+            } => budget::run(images, delays, palette.as_deref(), budget, progress),
+            Task::Preset {
+                delays,
+                palette,
+                preset,
+                fix,
+            } => {
+                let preset = presets::find(preset)
+                    .ok_or_else(|| Error::Input(format!("unknown preset `{}`", preset)))?;
+                presets::run(preset, images, delays, palette.as_deref(), *fix, progress)
+            }
+        }
+    }
+}
+
+pub enum JobEvent {
+    Progress {
+        stage: String,
+        done: usize,
+        total: usize,
+    },
+    Done {
+        apng: Vec<u8>,
+        outcome: Outcome,
+    },
+    Failed(Error),
+}
+
+// encode jobs run off the main thread, one at a time, so the page keeps responding.
+// the worker is kept between jobs and thrown away on cancel
+#[derive(Clone)]
+pub struct EncodeWorker {
+    url: String,
+    worker: Rc<RefCell<Option<Worker>>>,
+    busy: Rc<Cell<bool>>,
+    // the handlers of the running job, dropped with the next one
+    handlers: Rc<RefCell<Vec<Handler>>>,
+}
+
+impl EncodeWorker {
+    // the worker script is only loaded once the first job starts
+    pub fn new(url: &str) -> EncodeWorker {
+        EncodeWorker {
+            url: url.to_string(),
+            worker: Rc::default(),
+            busy: Rc::default(),
+            handlers: Rc::default(),
+        }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.busy.get()
+    }
+
+    // the frames are png files, their buffers are moved to the worker rather than copied
+    pub fn start(
+        &self,
+        task: &Task,
+        frames: Vec<Vec<u8>>,
+        on_event: impl FnMut(JobEvent) + 'static,
+    ) -> Result<(), JsValue> {
+        let worker = self.worker()?;
+        let on_event = Rc::new(RefCell::new(on_event));
+
+        let handle_message = {
+            let busy = self.busy.clone();
+            let on_event = on_event.clone();
+            Closure::wrap(Box::new(move |message: JsValue| {
+                let data = message
+                    .dyn_into::<MessageEvent>()
+                    .map(|m| m.data())
+                    .unwrap_or(JsValue::UNDEFINED);
+                let event = read_event(&data);
+                if !matches!(event, JobEvent::Progress { .. }) {
+                    busy.set(false);
+                }
+                (on_event.borrow_mut())(event);
+            }) as Box<dyn FnMut(JsValue)>)
+        };
+        // the script failing to load or the wasm failing to start
+        let handle_error = {
+            let busy = self.busy.clone();
+            let slot = self.worker.clone();
+            Closure::wrap(Box::new(move |error: JsValue| {
+                let message = error
+                    .dyn_into::<ErrorEvent>()
+                    .map(|e| e.message())
+                    .unwrap_or_else(|_| "encoder worker failed".to_string());
+                busy.set(false);
+                if let Some(worker) = slot.borrow_mut().take() {
+                    worker.terminate();
+                }
+                (on_event.borrow_mut())(JobEvent::Failed(Error::Worker(message)));
+            }) as Box<dyn FnMut(JsValue)>)
+        };
+        worker.set_onmessage(Some(handle_message.as_ref().unchecked_ref()));
+        worker.set_onerror(Some(handle_error.as_ref().unchecked_ref()));
+        *self.handlers.borrow_mut() = vec![handle_message, handle_error];
+
+        let settings =
+            serde_json::to_string(task).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let buffers = js_sys::Array::new();
+        for frame in frames.iter() {
+            buffers.push(&js_sys::Uint8Array::from(frame.as_slice()).buffer());
+        }
+        let message = js_sys::Object::new();
+        js_sys::Reflect::set(&message, &"job".into(), &settings.into())?;
+        js_sys::Reflect::set(&message, &"frames".into(), &buffers)?;
+        worker.post_message_with_transfer(&message, &buffers)?;
+
+        self.busy.set(true);
+        Ok(())
+    }
+
+    // stops the job where it is, the next one starts a fresh worker
+    pub fn cancel(&self) {
+        if let Some(worker) = self.worker.borrow_mut().take() {
+            worker.terminate();
+        }
+        self.handlers.borrow_mut().clear();
+        self.busy.set(false);
+    }
+
+    fn worker(&self) -> Result<Worker, JsValue> {
+        let mut slot = self.worker.borrow_mut();
+        if let Some(worker) = slot.as_ref() {
+            return Ok(worker.clone());
+        }
+        let worker = Worker::new(&self.url)?;
+        *slot = Some(worker.clone());
+        Ok(worker)
+    }
+}
+
+fn read_event(data: &JsValue) -> JobEvent {
+    let get = |key: &str| js_sys::Reflect::get(data, &key.into()).unwrap_or(JsValue::UNDEFINED);
+    let number = |key: &str| get(key).as_f64().unwrap_or(0.0) as usize;
+    let text = |key: &str| get(key).as_string().unwrap_or_default();
+
+    match text("type").as_str() {
+        "progress" => JobEvent::Progress {
+            stage: text("stage"),
+            done: number("done"),
+            total: number("total"),
+        },
+        "done" => match serde_json::from_str(&text("outcome")) {
+            Ok(outcome) => JobEvent::Done {
+                apng: js_sys::Uint8Array::new(&get("apng")).to_vec(),
+                outcome,
+            },
+            Err(err) => JobEvent::Failed(Error::Worker(format!(
+                "broken result from the encoder worker: {}",
+                err
+            ))),
+        },
+        "error" => JobEvent::Failed(Error::Worker(text("message"))),
+        other => JobEvent::Failed(Error::Worker(format!(
+            "unexpected message from the encoder worker: {:?}",
+            other
+        ))),
+    }
+}
+
+// runs inside the worker: decodes the frames, runs the task and posts progress on the way
+#[wasm_bindgen]
+pub fn encode_job(message: JsValue) {
+    let scope = js_sys::global().unchecked_into::<DedicatedWorkerGlobalScope>();
+    let result = run_job(&scope, &message).and_then(|(apng, outcome)| {
+        let outcome = serde_json::to_string(&outcome)
+            .map_err(|e| Error::Worker(format!("could not send the result: {}", e)))?;
+        Ok((apng, outcome))
+    });
+    let posted = match result {
+        Ok((apng, outcome)) => {
+            let buffer = js_sys::Uint8Array::from(apng.as_slice()).buffer();
+            post(
+                &scope,
+                &[
+                    ("type", "done".into()),
+                    ("apng", buffer.clone().into()),
+                    ("outcome", outcome.into()),
+                ],
+                Some(&buffer),
+            )
+        }
+        Err(err) => post(
+            &scope,
+            &[
+                ("type", "error".into()),
+                ("message", err.to_string().into()),
+            ],
+            None,
+        ),
+    };
+    // with the main thread unreachable the console is all that is left
+    if let Err(err) = posted {
+        console_error(&format!("encode job: {}", Error::from(err)));
+    }
+}
+
+fn run_job(
+    scope: &DedicatedWorkerGlobalScope,
+    message: &JsValue,
+) -> Result<(Vec<u8>, Outcome), Error> {
+    let settings = js_sys::Reflect::get(message, &"job".into())?
+        .as_string()
+        .unwrap_or_default();
+    let task: Task = serde_json::from_str(&settings)
+        .map_err(|e| Error::Worker(format!("broken encode job: {}", e)))?;
+    let buffers = js_sys::Array::from(&js_sys::Reflect::get(message, &"frames".into())?);
+
+    let total = buffers.length() as usize;
+    let mut images = Vec::with_capacity(total);
+    for (i, buffer) in buffers.iter().enumerate() {
+        let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+        images.push(codec::decode_png(&bytes).map_err(|e| Error::frame(i, e))?);
+        post_progress(scope, "decoding", i + 1, total);
+    }
+
+    task.run(images, &mut |stage, done, total| {
+        post_progress(scope, stage, done, total)
+    })
+}
+
+fn post_progress(scope: &DedicatedWorkerGlobalScope, stage: &str, done: usize, total: usize) {
+    // progress is best effort, a lost update is caught up by the next one
+    let _ = post(
+        scope,
+        &[
+            ("type", "progress".into()),
+            ("stage", stage.into()),
+            ("done", (done as f64).into()),
+            ("total", (total as f64).into()),
+        ],
+        None,
+    );
+}
+
+fn post(
+    scope: &DedicatedWorkerGlobalScope,
+    fields: &[(&str, JsValue)],
+    transfer: Option<&js_sys::ArrayBuffer>,
+) -> Result<(), JsValue> {
+    let message = js_sys::Object::new();
+    for (key, value) in fields.iter() {
+        js_sys::Reflect::set(&message, &(*key).into(), value)?;
+    }
+    match transfer {
+        Some(buffer) => scope.post_message_with_transfer(&message, &js_sys::Array::of1(buffer)),
+        None => scope.post_message(&message),
+    }
+}
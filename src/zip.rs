@@ -0,0 +1,143 @@
+use flate2::Crc;
+use std::io::{self, Write};
+
+// zip writer for files that are already compressed (png), so every entry is stored as is
+static LOCAL_HEADER: u32 = 0x0403_4b50;
+static CENTRAL_HEADER: u32 = 0x0201_4b50;
+static END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+// zip 2.0, the oldest version that every unzip tool reads
+static VERSION: u16 = 20;
+// 1980-01-01 00:00 in ms-dos format, the entries carry no real timestamp
+static DOS_TIME: u16 = 0;
+static DOS_DATE: u16 = 0x21;
+
+struct Entry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+pub fn write_zip(files: &[(String, Vec<u8>)]) -> io::Result<Vec<u8>> {
+    let mut buf = vec![];
+    let mut entries = vec![];
+
+    for (name, data) in files {
+        let mut crc = Crc::new();
+        crc.update(data);
+        let entry = Entry {
+            name: name.clone(),
+            crc: crc.sum(),
+            size: data.len() as u32,
+            offset: buf.len() as u32,
+        };
+
+        write_u32(&mut buf, LOCAL_HEADER)?;
+        write_u16(&mut buf, VERSION)?;
+        write_entry_fields(&mut buf, &entry)?;
+        // no extra field
+        write_u16(&mut buf, 0)?;
+        buf.write_all(name.as_bytes())?;
+        buf.write_all(data)?;
+
+        entries.push(entry);
+    }
+
+    let central_offset = buf.len() as u32;
+    for entry in &entries {
+        write_u32(&mut buf, CENTRAL_HEADER)?;
+        // made by, needed to extract
+        write_u16(&mut buf, VERSION)?;
+        write_u16(&mut buf, VERSION)?;
+        write_entry_fields(&mut buf, entry)?;
+        // extra field, comment, disk number, internal and external attributes
+        write_u16(&mut buf, 0)?;
+        write_u16(&mut buf, 0)?;
+        write_u16(&mut buf, 0)?;
+        write_u16(&mut buf, 0)?;
+        write_u32(&mut buf, 0)?;
+        write_u32(&mut buf, entry.offset)?;
+        buf.write_all(entry.name.as_bytes())?;
+    }
+    let central_size = buf.len() as u32 - central_offset;
+
+    write_u32(&mut buf, END_OF_CENTRAL_DIRECTORY)?;
+    // this disk and the disk the central directory starts on
+    write_u16(&mut buf, 0)?;
+    write_u16(&mut buf, 0)?;
+    write_u16(&mut buf, entries.len() as u16)?;
+    write_u16(&mut buf, entries.len() as u16)?;
+    write_u32(&mut buf, central_size)?;
+    write_u32(&mut buf, central_offset)?;
+    // comment
+    write_u16(&mut buf, 0)?;
+
+    Ok(buf)
+}
+
+// the part shared by local and central headers, from the flags to the name length
+fn write_entry_fields(buf: &mut Vec<u8>, entry: &Entry) -> io::Result<()> {
+    // flags, method 0 (stored)
+    write_u16(buf, 0)?;
+    write_u16(buf, 0)?;
+    write_u16(buf, DOS_TIME)?;
+    write_u16(buf, DOS_DATE)?;
+    write_u32(buf, entry.crc)?;
+    // compressed and uncompressed size are the same when stored
+    write_u32(buf, entry.size)?;
+    write_u32(buf, entry.size)?;
+    write_u16(buf, entry.name.len() as u16)
+}
+
+fn write_u16(buf: &mut Vec<u8>, v: u16) -> io::Result<()> {
+    buf.write_all(&v.to_le_bytes())
+}
+
+fn write_u32(buf: &mut Vec<u8>, v: u32) -> io::Result<()> {
+    buf.write_all(&v.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_u16(buf: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([buf[at], buf[at + 1]])
+    }
+
+    fn read_u32(buf: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+    }
+
+    #[test]
+    fn central_directory_points_at_entries() {
+        let files = vec![
+            ("frame_0001.png".to_string(), b"first".to_vec()),
+            ("frame_0002.png".to_string(), b"second frame".to_vec()),
+        ];
+        let zip = write_zip(&files).unwrap();
+
+        let end = zip.len() - 22;
+        assert_eq!(read_u32(&zip, end), END_OF_CENTRAL_DIRECTORY);
+        assert_eq!(read_u16(&zip, end + 10), 2);
+
+        let mut central = read_u32(&zip, end + 16) as usize;
+        for (name, data) in &files {
+            assert_eq!(read_u32(&zip, central), CENTRAL_HEADER);
+            let name_len = read_u16(&zip, central + 28) as usize;
+            assert_eq!(&zip[central + 46..central + 46 + name_len], name.as_bytes());
+
+            let local = read_u32(&zip, central + 42) as usize;
+            assert_eq!(read_u32(&zip, local), LOCAL_HEADER);
+            let start = local + 30 + name_len;
+            assert_eq!(&zip[start..start + data.len()], &data[..]);
+
+            let mut crc = Crc::new();
+            crc.update(data);
+            assert_eq!(read_u32(&zip, local + 14), crc.sum());
+
+            central += 46 + name_len;
+        }
+        assert_eq!(central, end);
+    }
+}